@@ -0,0 +1,105 @@
+// Read and write the control registers of the first connected Wiimote.
+//
+// Commands:
+//   r ADDR LEN      read LEN bytes starting at ADDR, e.g. `r a400fa 6`
+//   w ADDR BYTES..  write up to 16 bytes starting at ADDR, e.g. `w a400f0 55`
+//   q               quit
+//
+// Addresses and bytes are in hex, with or without a `0x` prefix.
+
+use std::io::{self, BufRead, Write};
+
+use wiimote_rs::event::WiimoteEvent;
+use wiimote_rs::wiimote::Wiimote;
+
+fn parse_hex(s: &str) -> Result<u32, String> {
+    let digits = s.trim_start_matches("0x");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("{s:?} isn't a hex number"))
+}
+
+fn parse_byte(s: &str) -> Result<u8, String> {
+    match parse_hex(s)? {
+        byte @ 0..=0xff => Ok(byte as u8),
+        _ => Err(format!("{s:?} doesn't fit in a byte")),
+    }
+}
+
+fn dump(address: u32, data: &[u8]) {
+    for (i, line) in data.chunks(16).enumerate() {
+        let bytes: Vec<String> = line.iter().map(|byte| format!("{byte:02x}")).collect();
+        println!("{:06x}: {}", address + i as u32 * 16, bytes.join(" "));
+    }
+}
+
+fn run_command(wiimote: &Wiimote, line: &str) -> Result<(), String> {
+    let mut args = line.split_whitespace();
+    match args.next() {
+        Some("r") => {
+            let address = parse_hex(args.next().ok_or("Usage: r ADDR LEN")?)?;
+            let len = parse_hex(args.next().ok_or("Usage: r ADDR LEN")?)?;
+            let len = u16::try_from(len).map_err(|_| "LEN is too big".to_string())?;
+
+            let data = wiimote
+                .read_register(address, len)
+                .map_err(|e| e.to_string())?;
+            dump(address, &data);
+        }
+        Some("w") => {
+            let address = parse_hex(args.next().ok_or("Usage: w ADDR BYTES..")?)?;
+            let data = args.map(parse_byte).collect::<Result<Vec<u8>, String>>()?;
+            if data.is_empty() {
+                return Err("Usage: w ADDR BYTES..".to_string());
+            }
+
+            wiimote
+                .write_register(address, &data)
+                .map_err(|e| e.to_string())?;
+
+            // Read the data back to check it was written
+            let data = wiimote
+                .read_register(address, data.len() as u16)
+                .map_err(|e| e.to_string())?;
+            dump(address, &data);
+        }
+        Some(command) => return Err(format!("Unknown command {command:?}")),
+        None => {}
+    }
+
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let mut wiimote = match Wiimote::connect_first()? {
+        Some(wiimote) => wiimote,
+        None => {
+            eprintln!("No Wiimote found, pair one first");
+            return Ok(());
+        }
+    };
+    println!("Connected to {}", wiimote.device_path());
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 || line.trim() == "q" {
+            break;
+        }
+
+        // We don't care about the input, but don't let it pile up
+        while let Some(event) = wiimote.poll() {
+            if event == WiimoteEvent::Disconnected {
+                eprintln!("The Wiimote disconnected");
+                return Ok(());
+            }
+        }
+
+        if let Err(e) = run_command(&wiimote, &line) {
+            eprintln!("Error: {e}");
+        }
+    }
+
+    Ok(())
+}
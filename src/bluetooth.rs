@@ -1,3 +1,4 @@
+use thiserror::Error;
 use windows::{
     core::GUID,
     Win32::{Devices::Bluetooth::*, Foundation::*},
@@ -5,19 +6,55 @@ use windows::{
 
 use std::fmt;
 use std::mem;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
+use crate::hid::WiimoteKind;
 use crate::util;
 
 // TODO: Where is this from?? (bthdef.h?) This is different to HidD_GetHidGuid
 // 00001124-0000-1000-8000-00805f9b34fb
 const HID_SERVICE_CLASS_GUID: GUID = GUID::from_u128(0x00001124_0000_1000_8000_00805f9b34fb);
 
-#[derive(Clone, Copy)]
+// The inquiry lasts for this many multiples of 1.28 seconds, and
+// `BluetoothFindFirstDevice` blocks for the whole inquiry.
+const INQUIRY_TIMEOUT_MULTIPLIER: u8 = 2;
+
+/// The address of a Bluetooth device.
+///
+/// The bytes are stored least significant byte first, which is the order used
+/// by [`BLUETOOTH_ADDRESS`]. Addresses are usually written the other way
+/// around, most significant byte first, e.g. `00:17:AB:12:34:56`, which is
+/// how they are displayed and parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Address([u8; 6]);
 
+impl Address {
+    /// Create an address from its bytes, least significant byte first (the
+    /// same order as [`BLUETOOTH_ADDRESS`]).
+    pub fn from_bytes(bytes: [u8; 6]) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of the address, least significant byte first (the same order
+    /// as [`BLUETOOTH_ADDRESS`]).
+    pub fn bytes(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// The bytes of the address, most significant byte first (the order they
+    /// are usually written in).
+    pub fn reversed(&self) -> [u8; 6] {
+        let mut bytes = self.0;
+        bytes.reverse();
+        bytes
+    }
+}
+
 impl fmt::Display for Address {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let addr = self.0;
+        let addr = self.reversed();
 
         write!(
             f,
@@ -27,44 +64,117 @@ impl fmt::Display for Address {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid Bluetooth address {0:?}, expected e.g. \"00:17:AB:12:34:56\"")]
+pub struct ParseAddressError(pub String);
+
+impl FromStr for Address {
+    type Err = ParseAddressError;
+
+    /// Parse an address written most significant byte first, with the bytes
+    /// separated by colons or dashes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseAddressError(s.to_string());
+
+        let mut bytes = [0u8; 6];
+        let mut parts = s.split([':', '-']);
+        for byte in bytes.iter_mut().rev() {
+            let part = parts.next().ok_or_else(error)?;
+            // `from_str_radix` would also accept a sign, e.g. "+6"
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(error());
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| error())?;
+        }
+
+        if parts.next().is_some() {
+            return Err(error());
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
 impl From<BLUETOOTH_ADDRESS> for Address {
     fn from(addr: BLUETOOTH_ADDRESS) -> Self {
         unsafe { Self(addr.Anonymous.rgBytes) }
     }
 }
 
+/// Convert a UTC [`SYSTEMTIME`] to a [`SystemTime`], or `None` if it is
+/// zeroed, which is how Windows says that something never happened.
+fn to_system_time(st: &SYSTEMTIME) -> Option<SystemTime> {
+    if st.wYear == 0 {
+        return None;
+    }
+
+    // Count the days since 1970-01-01 in the proleptic Gregorian calendar,
+    // using years that start in March so that the leap day is at the end
+    let (year, month) = match st.wMonth {
+        1 | 2 => (st.wYear as i64 - 1, st.wMonth as i64 + 9),
+        _ => (st.wYear as i64, st.wMonth as i64 - 3),
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + st.wDay as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let secs = days * 86400 + st.wHour as i64 * 3600 + st.wMinute as i64 * 60 + st.wSecond as i64;
+    let since_epoch =
+        Duration::from_secs(secs.unsigned_abs()) + Duration::from_millis(st.wMilliseconds as u64);
+
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(since_epoch)
+    }
+}
+
 pub struct Radio {
     h_radio: HANDLE,
-    h_find_radio: HANDLE,
 }
 
 impl Radio {
-    // Returns `None` if there are no radios i.e. we don't have bluetooth
-    fn find_first_radio() -> Option<Self> {
+    /// Find all of the Bluetooth radios.
+    ///
+    /// Returns an error if there are no radios i.e. we don't have bluetooth,
+    /// or the bluetooth stack is unavailable.
+    pub fn all() -> windows::core::Result<Vec<Self>> {
         let radio_params = BLUETOOTH_FIND_RADIO_PARAMS {
             dwSize: mem::size_of::<BLUETOOTH_FIND_RADIO_PARAMS>() as u32,
         };
 
         let mut h_radio = HANDLE::default();
         let h_find_radio = unsafe { HANDLE(BluetoothFindFirstRadio(&radio_params, &mut h_radio)) };
-
-        if !h_find_radio.is_invalid() {
-            Some(Self {
-                h_radio,
-                h_find_radio,
-            })
-        } else {
-            None
+        if h_find_radio.is_invalid() {
+            return Err(windows::core::Error::from_win32());
         }
-    }
 
-    fn find_next_radio(mut self) -> Option<Self> {
+        let mut radios = vec![Self { h_radio }];
         unsafe {
-            if BluetoothFindNextRadio(self.h_find_radio.0, &mut self.h_radio).into() {
-                Some(self)
-            } else {
-                None
+            let mut h_radio = HANDLE::default();
+            while BluetoothFindNextRadio(h_find_radio.0, &mut h_radio).into() {
+                radios.push(Self { h_radio });
             }
+
+            BluetoothFindRadioClose(h_find_radio.0);
+        }
+
+        Ok(radios)
+    }
+
+    /// Find the radio with the given address, or the first radio if `address`
+    /// is `None`.
+    pub fn find(address: Option<Address>) -> windows::core::Result<Self> {
+        let mut radios = Self::all()?.into_iter();
+
+        match address {
+            Some(address) => radios
+                .find(|radio| radio.address().ok() == Some(address))
+                .ok_or_else(|| ERROR_NOT_FOUND.to_hresult().into()),
+            // `all` never returns an empty list
+            None => Ok(radios.next().unwrap()),
         }
     }
 
@@ -88,9 +198,8 @@ impl Drop for Radio {
     fn drop(&mut self) {
         unsafe {
             CloseHandle(self.h_radio);
-            BluetoothFindRadioClose(self.h_find_radio.0);
-            self.h_find_radio = HANDLE::default();
         }
+        self.h_radio = HANDLE::default();
     }
 }
 
@@ -112,7 +221,7 @@ impl Device {
             fReturnConnected: true.into(),
             fReturnUnknown: true.into(),
             fIssueInquiry: new_scan.into(),
-            cTimeoutMultiplier: 2,
+            cTimeoutMultiplier: INQUIRY_TIMEOUT_MULTIPLIER,
             hRadio: radio.h_radio,
         };
 
@@ -169,6 +278,17 @@ impl Device {
         self.device_info.Address.into()
     }
 
+    /// When the device was last seen by the radio, or `None` if it never
+    /// was.
+    pub fn last_seen(&self) -> Option<SystemTime> {
+        to_system_time(&self.device_info.stLastSeen)
+    }
+
+    /// When the device was last connected to, or `None` if it never was.
+    pub fn last_used(&self) -> Option<SystemTime> {
+        to_system_time(&self.device_info.stLastUsed)
+    }
+
     // pub fn radio_address(&self) -> Address {
     //     self.radio.address()
     // }
@@ -244,9 +364,9 @@ struct Scanner {
 }
 
 impl Scanner {
-    pub fn new(should_scan: bool) -> Self {
+    pub fn new(radio: Radio, should_scan: bool) -> Self {
         Self {
-            current_radio: Radio::find_first_radio(),
+            current_radio: Some(radio),
             current_device: None,
             should_scan,
         }
@@ -263,15 +383,154 @@ impl Scanner {
     }
 }
 
-pub fn iter_devices<F>(should_scan: bool, mut f: F)
+/// Calls `f` on each Bluetooth device, stopping early if `f` returns
+/// [`ControlFlow::Break`].
+///
+/// The devices are found using the radio with `radio_address`, or the first
+/// radio if it is `None`. If `should_scan` is true, this will block while a
+/// Bluetooth inquiry is issued before the first device is found.
+///
+/// Returns an error if the Bluetooth radio isn't available.
+pub fn iter_devices<F>(
+    radio_address: Option<Address>,
+    should_scan: bool,
+    mut f: F,
+) -> windows::core::Result<()>
 where
-    F: FnMut(&mut Device),
+    F: FnMut(&mut Device) -> ControlFlow<()>,
 {
-    let mut scanner = Scanner::new(should_scan);
+    let mut scanner = Scanner::new(Radio::find(radio_address)?, should_scan);
     while let Some(device) = scanner.next() {
         // Sometimes the device's name is empty, so filter it out
-        if !device.name().is_empty() {
-            f(device);
+        if !device.name().is_empty() && f(device).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the address of the radio that the device with `address` is connected
+/// to, e.g. to see how Wiimotes are spread across several dongles.
+///
+/// Every radio's list of devices is searched (without an inquiry) for a
+/// connected device with `address`. Entries that are only remembered don't
+/// count, as a Wiimote can be paired with more than one radio. Returns `None`
+/// if no radio has the device connected, or if more than one claims to, as
+/// the radio can't be told apart then.
+pub fn radio_address_of(address: Address) -> Option<Address> {
+    let mut found = None;
+    for radio in Radio::all().ok()? {
+        let radio_address = match radio.address() {
+            Ok(radio_address) => radio_address,
+            Err(_) => continue,
+        };
+
+        let mut scanner = Scanner::new(radio, false);
+        while let Some(device) = scanner.next() {
+            if device.address() == address && device.is_connected() {
+                if found.is_some() {
+                    return None;
+                }
+                found = Some(radio_address);
+                break;
+            }
+        }
+    }
+
+    found
+}
+
+/// A Wiimote that the Bluetooth stack remembers, from
+/// [`remembered_wiimotes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RememberedWiimote {
+    pub name: String,
+    pub address: Address,
+    pub kind: WiimoteKind,
+    pub is_connected: bool,
+    pub is_authenticated: bool,
+    /// When the Wiimote was last seen, if the Bluetooth stack knows.
+    pub last_seen: Option<SystemTime>,
+    /// When the Wiimote was last used, if the Bluetooth stack knows.
+    pub last_used: Option<SystemTime>,
+}
+
+/// List the Wiimotes that the radio with `radio_address` (or the first radio
+/// if it is `None`) remembers, whether they are connected or not.
+///
+/// This doesn't issue an inquiry, so it returns quickly. Returns an error if
+/// the Bluetooth radio isn't available.
+pub fn remembered_wiimotes(
+    radio_address: Option<Address>,
+) -> windows::core::Result<Vec<RememberedWiimote>> {
+    let mut wiimotes = Vec::new();
+    iter_devices(radio_address, false, |device| {
+        if let Some(kind) = WiimoteKind::from_name(device.name()) {
+            if device.is_remembered() {
+                wiimotes.push(RememberedWiimote {
+                    name: device.name().to_string(),
+                    address: device.address(),
+                    kind,
+                    is_connected: device.is_connected(),
+                    is_authenticated: device.is_authenticated(),
+                    last_seen: device.last_seen(),
+                    last_used: device.last_used(),
+                });
+            }
+        }
+
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(wiimotes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_round_trips_through_a_string() {
+        let address: Address = "00:17:AB:12:34:56".parse().unwrap();
+        assert_eq!(address.bytes(), [0x56, 0x34, 0x12, 0xab, 0x17, 0x00]);
+        assert_eq!(address.reversed(), [0x00, 0x17, 0xab, 0x12, 0x34, 0x56]);
+        assert_eq!(address.to_string(), "00:17:AB:12:34:56");
+        assert_eq!(Address::from_bytes(address.bytes()), address);
+
+        // Dashes and lowercase are accepted too
+        assert_eq!("00-17-ab-12-34-56".parse(), Ok(address));
+    }
+
+    #[test]
+    fn address_parsing_rejects_malformed_strings() {
+        for s in [
+            "",
+            "00:17:AB:12:34",
+            "00:17:AB:12:34:56:78",
+            "00:17:AB:12:34:5G",
+            "0:17:AB:12:34:567",
+            "001:7:AB:12:34:56",
+            "00:17:AB:12:34:+6",
+        ] {
+            assert_eq!(
+                s.parse::<Address>(),
+                Err(ParseAddressError(s.to_string())),
+                "{s:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn remembered_wiimotes_without_an_inquiry() {
+        // Without a radio this is an error rather than a panic or a hang
+        let wiimotes = match remembered_wiimotes(None) {
+            Ok(wiimotes) => wiimotes,
+            Err(_) => return,
+        };
+
+        for wiimote in wiimotes {
+            assert_eq!(WiimoteKind::from_name(&wiimote.name), Some(wiimote.kind));
         }
     }
 }
@@ -0,0 +1,402 @@
+use bitflags::bitflags;
+
+use core::fmt;
+
+use crate::protocol::Report;
+use crate::report::{self, DecodeError};
+
+bitflags! {
+    /// The buttons on the Wiimote itself.
+    ///
+    /// These are sent as two bytes at the start of almost every input report.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct CoreButtons: u16 {
+        const TWO = 0x0001;
+        const ONE = 0x0002;
+        const B = 0x0004;
+        const A = 0x0008;
+        const MINUS = 0x0010;
+        const HOME = 0x0080;
+        const LEFT = 0x0100;
+        const RIGHT = 0x0200;
+        const DOWN = 0x0400;
+        const UP = 0x0800;
+        const PLUS = 0x1000;
+    }
+}
+
+impl CoreButtons {
+    /// The bits of the button bytes that hold other data in some report modes:
+    /// the least significant bits of the accelerometer data, or of the Z axis
+    /// in [`crate::protocol::ReportMode::Interleaved`].
+    pub const NON_BUTTON_BITS: u16 = 0x6060;
+
+    /// Decode the buttons from the two button bytes of an input report.
+    ///
+    /// Any bits that don't correspond to a button are ignored, so this works
+    /// the same in every report mode, including the ones that pack the
+    /// accelerometer data into [`CoreButtons::NON_BUTTON_BITS`].
+    pub fn from_bytes(bytes: [u8; 2]) -> Self {
+        Self::from_bits_truncate(u16::from_be_bytes(bytes) & !Self::NON_BUTTON_BITS)
+    }
+
+    /// Whether `button` is pressed.
+    pub fn contains_button(&self, button: Button) -> bool {
+        self.contains(button.to_flag())
+    }
+
+    /// The pressed buttons, one at a time, from the lowest bit to the highest.
+    pub fn iter_pressed(&self) -> impl Iterator<Item = Button> {
+        let buttons = *self;
+        Button::ALL
+            .into_iter()
+            .filter(move |button| buttons.contains_button(*button))
+    }
+
+    /// The direction of the D-pad, including diagonals.
+    ///
+    /// If opposite directions are pressed at the same time, they cancel out.
+    pub fn dpad(&self) -> DPad {
+        match self.dpad_vector() {
+            (0, 0) => DPad::Neutral,
+            (0, 1) => DPad::Up,
+            (1, 1) => DPad::UpRight,
+            (1, 0) => DPad::Right,
+            (1, -1) => DPad::DownRight,
+            (0, -1) => DPad::Down,
+            (-1, -1) => DPad::DownLeft,
+            (-1, 0) => DPad::Left,
+            (-1, 1) => DPad::UpLeft,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The direction of the D-pad as an (x, y) vector, where each component is
+    /// -1, 0, or 1 and up is positive y.
+    ///
+    /// If opposite directions are pressed at the same time, they cancel out.
+    pub fn dpad_vector(&self) -> (i8, i8) {
+        let axis =
+            |negative, positive| self.contains(positive) as i8 - self.contains(negative) as i8;
+
+        (
+            axis(CoreButtons::LEFT, CoreButtons::RIGHT),
+            axis(CoreButtons::DOWN, CoreButtons::UP),
+        )
+    }
+}
+
+impl TryFrom<&Report> for CoreButtons {
+    type Error = DecodeError;
+
+    /// Decode the buttons from any input report that has them.
+    fn try_from(report: &Report) -> Result<Self, Self::Error> {
+        let id = report::input_report_id(report, 4)?;
+
+        // Every input report from 0x20 onwards starts with the core buttons,
+        // apart from the extension-only report 0x3d
+        match id {
+            0x20..=0x3f if id != 0x3d => Ok(Self::from_bytes([report[2], report[3]])),
+            _ => Err(DecodeError::UnexpectedId {
+                expected_id: None,
+                got_id: id,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for CoreButtons {
+    /// Lists the pressed buttons, e.g. `A B +`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [(CoreButtons, &str); 11] = [
+            (CoreButtons::UP, "Up"),
+            (CoreButtons::DOWN, "Down"),
+            (CoreButtons::LEFT, "Left"),
+            (CoreButtons::RIGHT, "Right"),
+            (CoreButtons::A, "A"),
+            (CoreButtons::B, "B"),
+            (CoreButtons::ONE, "1"),
+            (CoreButtons::TWO, "2"),
+            (CoreButtons::MINUS, "-"),
+            (CoreButtons::HOME, "Home"),
+            (CoreButtons::PLUS, "+"),
+        ];
+
+        let mut pressed = NAMES
+            .iter()
+            .filter(|(button, _)| self.contains(*button))
+            .map(|(_, name)| name);
+
+        match pressed.next() {
+            Some(name) => write!(f, "{name}")?,
+            None => return write!(f, "None"),
+        }
+        for name in pressed {
+            write!(f, " {name}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single button on the Wiimote itself.
+///
+/// This is for matching on one button at a time, e.g. from
+/// [`CoreButtons::iter_pressed`], whereas [`CoreButtons`] holds any number of
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Button {
+    A,
+    B,
+    One,
+    Two,
+    Plus,
+    Minus,
+    Home,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Button {
+    /// Every button, in the order of their bits in [`CoreButtons`].
+    pub const ALL: [Button; 11] = [
+        Button::Two,
+        Button::One,
+        Button::B,
+        Button::A,
+        Button::Minus,
+        Button::Home,
+        Button::Left,
+        Button::Right,
+        Button::Down,
+        Button::Up,
+        Button::Plus,
+    ];
+
+    /// The flag of this button in [`CoreButtons`].
+    pub fn to_flag(self) -> CoreButtons {
+        match self {
+            Button::A => CoreButtons::A,
+            Button::B => CoreButtons::B,
+            Button::One => CoreButtons::ONE,
+            Button::Two => CoreButtons::TWO,
+            Button::Plus => CoreButtons::PLUS,
+            Button::Minus => CoreButtons::MINUS,
+            Button::Home => CoreButtons::HOME,
+            Button::Up => CoreButtons::UP,
+            Button::Down => CoreButtons::DOWN,
+            Button::Left => CoreButtons::LEFT,
+            Button::Right => CoreButtons::RIGHT,
+        }
+    }
+}
+
+impl From<Button> for CoreButtons {
+    fn from(button: Button) -> Self {
+        button.to_flag()
+    }
+}
+
+/// The direction that the D-pad is being pressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DPad {
+    Neutral,
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+/// The buttons held down in the current and previous frame, for consumers that
+/// poll the buttons once per frame instead of handling events.
+///
+/// A "button" here can be any combination of [`CoreButtons`], in which case
+/// all of them have to match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ButtonState {
+    current: CoreButtons,
+    previous: CoreButtons,
+}
+
+impl ButtonState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new frame with `buttons` held down.
+    pub fn update(&mut self, buttons: CoreButtons) {
+        self.previous = self.current;
+        self.current = buttons;
+    }
+
+    /// Whether `button` is held down in this frame.
+    pub fn is_held(&self, button: CoreButtons) -> bool {
+        self.current.contains(button)
+    }
+
+    /// Whether `button` started being held down in this frame.
+    pub fn just_pressed(&self, button: CoreButtons) -> bool {
+        self.current.contains(button) && !self.previous.contains(button)
+    }
+
+    /// Whether `button` stopped being held down in this frame.
+    pub fn just_released(&self, button: CoreButtons) -> bool {
+        !self.current.contains(button) && self.previous.contains(button)
+    }
+
+    /// The buttons held down in this frame.
+    pub fn current(&self) -> CoreButtons {
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accel_bits_are_not_buttons() {
+        use crate::motion::AccelData;
+        use crate::protocol::INPUT_REPORT;
+
+        let pressed = CoreButtons::A | CoreButtons::LEFT | CoreButtons::PLUS;
+        let [b1, b2] = pressed.bits().to_be_bytes();
+        let core: Report = [INPUT_REPORT, 0x30, b1, b2].into_iter().collect();
+        // The same press with every accelerometer LSB set
+        let accel: Report = [INPUT_REPORT, 0x31, b1 | 0x60, b2 | 0x60, 0x80, 0x80, 0x80]
+            .into_iter()
+            .collect();
+
+        assert_eq!(CoreButtons::try_from(&core), Ok(pressed));
+        assert_eq!(CoreButtons::try_from(&accel), Ok(pressed));
+        assert_eq!(
+            AccelData::try_from(&accel),
+            Ok(AccelData {
+                x: 0x203,
+                y: 0x202,
+                z: 0x202,
+            })
+        );
+    }
+
+    #[test]
+    fn iter_pressed_yields_exactly_the_pressed_buttons() {
+        let buttons = CoreButtons::UP | CoreButtons::A | CoreButtons::HOME | CoreButtons::TWO;
+        assert!(buttons
+            .iter_pressed()
+            .eq([Button::Two, Button::A, Button::Home, Button::Up]));
+        assert_eq!(CoreButtons::empty().iter_pressed().next(), None);
+        assert!(CoreButtons::all().iter_pressed().eq(Button::ALL));
+
+        // Each button is its own flag, and the flags cover every button
+        let flags = Button::ALL.map(Button::to_flag);
+        assert_eq!(
+            flags
+                .iter()
+                .fold(CoreButtons::empty(), |all, &flag| all | flag),
+            CoreButtons::all()
+        );
+        for button in Button::ALL {
+            assert_eq!(button.to_flag().bits().count_ones(), 1, "{button:?}");
+            assert!(button.to_flag().contains_button(button));
+        }
+    }
+
+    #[test]
+    fn dpad_from_every_combination() {
+        use DPad::*;
+
+        // Indexed by the bits UP, DOWN, LEFT, RIGHT, from most significant
+        let expected = [
+            (Neutral, (0, 0)),
+            (Right, (1, 0)),
+            (Left, (-1, 0)),
+            (Neutral, (0, 0)),
+            (Down, (0, -1)),
+            (DownRight, (1, -1)),
+            (DownLeft, (-1, -1)),
+            (Down, (0, -1)),
+            (Up, (0, 1)),
+            (UpRight, (1, 1)),
+            (UpLeft, (-1, 1)),
+            (Up, (0, 1)),
+            (Neutral, (0, 0)),
+            (Right, (1, 0)),
+            (Left, (-1, 0)),
+            (Neutral, (0, 0)),
+        ];
+
+        for (bits, (dpad, vector)) in expected.into_iter().enumerate() {
+            let flags = [
+                CoreButtons::RIGHT,
+                CoreButtons::LEFT,
+                CoreButtons::DOWN,
+                CoreButtons::UP,
+            ];
+            let buttons = flags
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| bits & 1 << i != 0)
+                .fold(CoreButtons::A, |buttons, (_, flag)| buttons | flag);
+
+            assert_eq!(buttons.dpad(), dpad, "{buttons:?}");
+            assert_eq!(buttons.dpad_vector(), vector, "{buttons:?}");
+        }
+    }
+
+    #[test]
+    fn button_state_edges_last_one_frame() {
+        let frames = [
+            CoreButtons::empty(),
+            CoreButtons::A,
+            CoreButtons::A,
+            CoreButtons::A | CoreButtons::B,
+            CoreButtons::B,
+            CoreButtons::empty(),
+        ];
+        let mut state = ButtonState::new();
+        let edges = frames.map(|buttons| {
+            state.update(buttons);
+            (
+                state.just_pressed(CoreButtons::A),
+                state.is_held(CoreButtons::A),
+                state.just_released(CoreButtons::A),
+            )
+        });
+
+        assert_eq!(
+            edges,
+            [
+                (false, false, false),
+                (true, true, false),
+                (false, true, false),
+                (false, true, false),
+                (false, false, true),
+                (false, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn button_state_combinations_need_every_button() {
+        let mut state = ButtonState::new();
+        let combo = CoreButtons::A | CoreButtons::B;
+
+        state.update(CoreButtons::A);
+        assert!(!state.just_pressed(combo));
+        state.update(combo);
+        assert!(state.just_pressed(combo));
+        assert!(state.is_held(combo));
+        state.update(CoreButtons::B);
+        assert!(state.just_released(combo));
+        assert_eq!(state.current(), CoreButtons::B);
+    }
+}
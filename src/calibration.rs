@@ -0,0 +1,30 @@
+use crate::extension::NunchukCalibration;
+use crate::motion::AccelCalibration;
+
+/// The calibration data stored in a Wiimote and its extension, read with
+/// [`WiimotePollThread::read_all_calibration`].
+///
+/// [`WiimotePollThread::read_all_calibration`]: crate::wiimote::WiimotePollThread::read_all_calibration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    /// The accelerometer calibration, or `None` if its checksum didn't match.
+    pub accel: Option<AccelCalibration>,
+    /// The raw calibration block of the extension, if one was connected.
+    ///
+    /// How this is decoded depends on the type of the extension.
+    pub extension: Option<[u8; Self::EXTENSION_LENGTH]>,
+}
+
+impl Calibration {
+    /// The address of the calibration block in the extension's registers.
+    pub const EXTENSION_ADDRESS: u32 = 0xa40020;
+    pub const EXTENSION_LENGTH: usize = 16;
+
+    /// Decode the extension calibration as a Nunchuk's, if there is one and
+    /// its checksum matches.
+    pub fn nunchuk(&self) -> Option<NunchukCalibration> {
+        self.extension
+            .as_ref()
+            .and_then(NunchukCalibration::from_bytes)
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::buttons::{Button, CoreButtons};
+use crate::extension::{
+    ClassicController, MotionPlusData, MotionPlusMode, Nunchuk, PassthroughData, PassthroughDecoder,
+};
+use crate::gesture::{Gesture, GestureDetector};
+use crate::hid::{self, INPUT_REPORT};
+use crate::motion::{AccelCalibration, AccelData, Tilt, TiltSmoother};
+use crate::report::DecodedReport;
+use crate::status::StatusReport;
+use crate::wiimote::WiimoteConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WiimoteEvent {
+    /// The current state of the core buttons.
+    ///
+    /// When decoded by an [`EventDecoder`], this is only emitted when the
+    /// buttons differ from the last report.
+    ButtonsChanged(CoreButtons),
+    /// The same buttons as the last report, resent by the Wiimote.
+    ButtonsResent(CoreButtons),
+    /// A single button was released.
+    ///
+    /// This is only emitted when the Wiimote disconnects, once for every
+    /// button that was held down at the time.
+    ButtonUp(Button),
+    /// An extension was connected (or was still connected when the Wiimote
+    /// reconnected), and the poll thread has initialized it.
+    ///
+    /// Any extension data decoded before this is from the old extension, or
+    /// is garbage. This is only emitted by an [`EventDecoder`] from
+    /// [`WiimotePollThread::event_decoder`], along with the first report
+    /// decoded after the extension was initialized.
+    ///
+    /// [`WiimotePollThread::event_decoder`]: crate::wiimote::WiimotePollThread::event_decoder
+    ExtensionReinitialized,
+    /// A gesture was made with the Wiimote.
+    ///
+    /// This is only emitted by an [`EventDecoder`] when
+    /// [`WiimoteConfig::gestures`] is set.
+    Gesture(Gesture),
+    /// The smoothed tilt of the Wiimote changed.
+    ///
+    /// This is only emitted by an [`EventDecoder`] when
+    /// [`WiimoteConfig::tilt`] is set, in report modes that include the
+    /// accelerometer data.
+    Tilt(Tilt),
+    /// A sample of the gyroscope data from the MotionPlus.
+    ///
+    /// This is only emitted by an [`EventDecoder`] when the MotionPlus is
+    /// active (see [`WiimoteConfig::motion_plus`]), in report modes that
+    /// include the extension data. In the passthrough modes the MotionPlus
+    /// alternates its own samples with the extension's, so each arrives at
+    /// half the report rate.
+    MotionPlus(MotionPlusData),
+    /// A sample from a Nunchuk plugged into the MotionPlus, in
+    /// [`MotionPlusMode::NunchukPassthrough`].
+    Nunchuk(Nunchuk),
+    /// A sample from a Classic Controller plugged into the MotionPlus, in
+    /// [`MotionPlusMode::ClassicPassthrough`].
+    Classic(ClassicController),
+    /// A status report was received, e.g. because one was requested every
+    /// [`WiimoteConfig::status_interval`].
+    StatusUpdated(StatusReport),
+    /// The battery level (as a rough percentage) dropped below
+    /// [`BatteryWarning::low_percent`].
+    ///
+    /// This is only emitted once until [`WiimoteEvent::BatteryOk`] is.
+    BatteryLow(u8),
+    /// The battery level went back up after [`WiimoteEvent::BatteryLow`], e.g.
+    /// because the batteries were changed.
+    BatteryOk(u8),
+    /// The Wiimote has disconnected, so no more events will be received.
+    Disconnected,
+}
+
+impl WiimoteEvent {
+    /// Decode the event carried by an input report, if there is one.
+    ///
+    /// This doesn't know about any previous reports, so the buttons are
+    /// always decoded as [`WiimoteEvent::ButtonsChanged`].
+    pub fn from_report(report: &hid::Report) -> Option<Self> {
+        match report.as_slice() {
+            // Every input report from 0x20 onwards starts with the core
+            // buttons, apart from the extension-only report 0x3d
+            [INPUT_REPORT, id @ 0x20..=0x3f, b1, b2, ..] if *id != 0x3d => {
+                Some(Self::ButtonsChanged(CoreButtons::from_bytes([*b1, *b2])))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this event is new information, rather than a resend of
+    /// something that was already known.
+    pub fn is_change(&self) -> bool {
+        !matches!(self, Self::ButtonsResent(_))
+    }
+}
+
+/// Maps the buttons that are physically pressed onto the logical buttons that
+/// an application cares about, e.g. so that the same code works with the
+/// Wiimote held upright or sideways.
+///
+/// Logical buttons are also [`CoreButtons`], so an application can name its
+/// own, e.g. `const JUMP: CoreButtons = CoreButtons::A;`. A binding maps a
+/// chord of one or more physical buttons onto one or more logical buttons.
+/// Several bindings can map onto the same logical button, and physical buttons
+/// that aren't in any binding are ignored.
+///
+/// When a chord is held down, its buttons aren't also used by any smaller
+/// bindings, so binding `A | B` to `HOME` doesn't also press whatever `A` and
+/// `B` are bound to. Until every button in the chord is held down, the smaller
+/// bindings are used as normal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ButtonRemap {
+    // Sorted so that bindings with bigger chords come first
+    bindings: Vec<(CoreButtons, CoreButtons)>,
+}
+
+impl ButtonRemap {
+    /// A remap with no bindings, so no buttons are pressed at all.
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// A remap that binds every button to itself.
+    pub fn identity() -> Self {
+        let mut remap = Self::new();
+        let mut buttons = CoreButtons::all();
+        while !buttons.is_empty() {
+            // Take the lowest button that's left
+            let button =
+                CoreButtons::from_bits_truncate(buttons.bits() & buttons.bits().wrapping_neg());
+            remap.bind(button, button);
+            buttons -= button;
+        }
+
+        remap
+    }
+
+    /// A remap for a Wiimote held sideways like an NES controller, with the
+    /// D-pad on the left.
+    ///
+    /// 2 and 1 become A and B, and the D-pad is rotated to match. The
+    /// physical A and B buttons are also still bound to themselves.
+    pub fn sideways() -> Self {
+        let mut remap = Self::new();
+        let bindings = [
+            (CoreButtons::TWO, CoreButtons::A),
+            (CoreButtons::ONE, CoreButtons::B),
+            (CoreButtons::A, CoreButtons::A),
+            (CoreButtons::B, CoreButtons::B),
+            (CoreButtons::MINUS, CoreButtons::MINUS),
+            (CoreButtons::HOME, CoreButtons::HOME),
+            (CoreButtons::PLUS, CoreButtons::PLUS),
+            // The D-pad is rotated a quarter turn anticlockwise
+            (CoreButtons::UP, CoreButtons::LEFT),
+            (CoreButtons::DOWN, CoreButtons::RIGHT),
+            (CoreButtons::LEFT, CoreButtons::DOWN),
+            (CoreButtons::RIGHT, CoreButtons::UP),
+        ];
+        for (physical, logical) in bindings {
+            remap.bind(physical, logical);
+        }
+
+        remap
+    }
+
+    /// Press `logical` whenever all of `physical` is held down.
+    ///
+    /// If `physical` is already bound, `logical` is added to what it presses.
+    /// Binding an empty chord does nothing.
+    pub fn bind(&mut self, physical: CoreButtons, logical: CoreButtons) -> &mut Self {
+        if let Some((_, buttons)) = self
+            .bindings
+            .iter_mut()
+            .find(|(chord, _)| *chord == physical)
+        {
+            *buttons |= logical;
+        } else if !physical.is_empty() {
+            let chord_len = physical.bits().count_ones();
+            let index = self
+                .bindings
+                .iter()
+                .position(|(chord, _)| chord.bits().count_ones() < chord_len)
+                .unwrap_or(self.bindings.len());
+            self.bindings.insert(index, (physical, logical));
+        }
+
+        self
+    }
+
+    /// Remove the binding of the chord `physical`.
+    pub fn unbind(&mut self, physical: CoreButtons) -> &mut Self {
+        self.bindings.retain(|(chord, _)| *chord != physical);
+        self
+    }
+
+    /// The logical buttons that are pressed when `physical` is held down.
+    pub fn apply(&self, physical: CoreButtons) -> CoreButtons {
+        let mut used = CoreButtons::empty();
+        let mut logical = CoreButtons::empty();
+
+        for &(chord, buttons) in &self.bindings {
+            if physical.contains(chord) && !used.intersects(chord) {
+                logical |= buttons;
+                used |= chord;
+            }
+        }
+
+        logical
+    }
+}
+
+impl Default for ButtonRemap {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// When to warn that a Wiimote's battery is running low.
+///
+/// The battery level is noisy, so it has to rise to `low_percent + margin`
+/// before the warning is cleared, instead of flapping around `low_percent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatteryWarning {
+    /// The battery level below which [`WiimoteEvent::BatteryLow`] is emitted.
+    pub low_percent: u8,
+    /// How far above `low_percent` the battery level has to go before
+    /// [`WiimoteEvent::BatteryOk`] is emitted.
+    pub margin: u8,
+}
+
+impl Default for BatteryWarning {
+    fn default() -> Self {
+        Self {
+            low_percent: 15,
+            margin: 5,
+        }
+    }
+}
+
+/// Decodes the input reports from a Wiimote into events, keeping track of the
+/// state needed to do so.
+///
+/// Reports are compared against the previous one to tell changes apart from
+/// resends. This is most meaningful for the buttons: the IR and accelerometer
+/// data is noisy, so it will almost always differ between reports.
+#[derive(Debug, Clone)]
+pub struct EventDecoder {
+    dedup_button_reports: bool,
+    button_remap: ButtonRemap,
+    buttons: Option<CoreButtons>,
+    // How many times the poll thread has initialized an extension, and how
+    // many of those have been decoded
+    extension_inits: Option<Arc<AtomicUsize>>,
+    decoded_extension_inits: usize,
+    gestures: Option<GestureDetector>,
+    tilt: Option<TiltSmoother>,
+    last_tilt: Option<(Tilt, Instant)>,
+    passthrough: Option<PassthroughDecoder>,
+    battery_warning: Option<BatteryWarning>,
+    // Whether the last battery level was low, if it is known
+    battery_low: Option<bool>,
+    accel_calibration: AccelCalibration,
+    // Events that were decoded from the same report as an earlier event
+    pending: VecDeque<WiimoteEvent>,
+}
+
+impl EventDecoder {
+    pub fn new(config: &WiimoteConfig) -> Self {
+        Self {
+            dedup_button_reports: config.dedup_button_reports,
+            button_remap: config.button_remap.clone(),
+            buttons: None,
+            extension_inits: None,
+            decoded_extension_inits: 0,
+            gestures: config.gestures.map(GestureDetector::new),
+            tilt: config.tilt.map(TiltSmoother::new),
+            last_tilt: None,
+            passthrough: config.motion_plus.map(PassthroughDecoder::new),
+            battery_warning: config.battery_warning,
+            battery_low: None,
+            accel_calibration: AccelCalibration::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    // A decoder that knows which mode the poll thread activated the
+    // MotionPlus in, which can be chosen automatically, and when it
+    // initializes an extension
+    pub(crate) fn for_poll_thread(
+        config: &WiimoteConfig,
+        motion_plus: Option<MotionPlusMode>,
+        extension_inits: Arc<AtomicUsize>,
+    ) -> Self {
+        let decoded_extension_inits = extension_inits.load(Ordering::SeqCst);
+        Self {
+            extension_inits: Some(extension_inits),
+            decoded_extension_inits,
+            passthrough: motion_plus.map(PassthroughDecoder::new),
+            ..Self::new(config)
+        }
+    }
+
+    /// Use `cal` to calibrate the accelerometer data used to detect gestures,
+    /// instead of the typical calibration.
+    pub fn set_accel_calibration(&mut self, cal: AccelCalibration) {
+        self.accel_calibration = cal;
+    }
+
+    /// The last buttons that were decoded, after being remapped, if any have
+    /// been.
+    pub fn buttons(&self) -> Option<CoreButtons> {
+        self.buttons
+    }
+
+    /// Decode the event carried by an input report, if there is one.
+    ///
+    /// A report can carry more than one event, e.g. a change to the buttons
+    /// and a gesture. The rest of them are returned by [`Self::pending`].
+    pub fn decode(&mut self, report: &hid::Report) -> Option<WiimoteEvent> {
+        // The extension was initialized before this report was sent on, at
+        // the latest
+        let extension = self.decode_extension();
+        if extension.is_some() {
+            // The samples from before are from the old extension
+            if let Some(passthrough) = &mut self.passthrough {
+                passthrough.reset();
+            }
+        }
+        let buttons = self.decode_buttons(report);
+        let status = StatusReport::from_report(report).map(WiimoteEvent::StatusUpdated);
+        let battery = self.decode_battery(report);
+        let gesture = self.decode_gesture(report).map(WiimoteEvent::Gesture);
+        let tilt = self.decode_tilt(report).map(WiimoteEvent::Tilt);
+        let passthrough = self.decode_passthrough(report);
+
+        let mut events = [
+            extension,
+            buttons,
+            status,
+            battery,
+            gesture,
+            tilt,
+            passthrough,
+        ]
+        .into_iter()
+        .flatten();
+        let event = events.next();
+        self.pending.extend(events);
+
+        event
+    }
+
+    /// Get the next event that was decoded from an earlier report but hasn't
+    /// been returned yet.
+    ///
+    /// This should be checked before decoding the next report.
+    pub fn pending(&mut self) -> Option<WiimoteEvent> {
+        self.pending.pop_front()
+    }
+
+    fn decode_extension(&mut self) -> Option<WiimoteEvent> {
+        let inits = self.extension_inits.as_ref()?.load(Ordering::SeqCst);
+        let was_initialized = inits != self.decoded_extension_inits;
+        self.decoded_extension_inits = inits;
+
+        was_initialized.then_some(WiimoteEvent::ExtensionReinitialized)
+    }
+
+    fn decode_battery(&mut self, report: &hid::Report) -> Option<WiimoteEvent> {
+        let warning = self.battery_warning?;
+        let percent = StatusReport::from_report(report)?.battery_percent();
+
+        let was_low = self.battery_low;
+        let is_low = if percent < warning.low_percent {
+            true
+        } else if percent >= warning.low_percent.saturating_add(warning.margin) {
+            false
+        } else {
+            // Keep the last state while in the margin, but assume the battery
+            // is fine if this is the first time it's been seen
+            was_low.unwrap_or(false)
+        };
+        self.battery_low = Some(is_low);
+
+        match (was_low, is_low) {
+            (None | Some(false), true) => Some(WiimoteEvent::BatteryLow(percent)),
+            (Some(true), false) => Some(WiimoteEvent::BatteryOk(percent)),
+            _ => None,
+        }
+    }
+
+    fn decode_gesture(&mut self, report: &hid::Report) -> Option<Gesture> {
+        let detector = self.gestures.as_mut()?;
+        let accel = AccelData::try_from(report).ok()?;
+
+        detector.update(accel.calibrated(&self.accel_calibration), Instant::now())
+    }
+
+    fn decode_tilt(&mut self, report: &hid::Report) -> Option<Tilt> {
+        let smoother = self.tilt.as_mut()?;
+        let accel = AccelData::try_from(report).ok()?;
+        let tilt = smoother.update(accel.calibrated(&self.accel_calibration))?;
+
+        // Only emit changes, and no more often than the interval
+        let now = Instant::now();
+        if let Some((last_tilt, last_time)) = self.last_tilt {
+            if tilt == last_tilt || now - last_time < smoother.config().interval {
+                return None;
+            }
+        }
+        self.last_tilt = Some((tilt, now));
+
+        Some(tilt)
+    }
+
+    fn decode_passthrough(&mut self, report: &hid::Report) -> Option<WiimoteEvent> {
+        let passthrough = self.passthrough.as_mut()?;
+        let extension = DecodedReport::try_from(report).ok()?.extension?;
+        let bytes = extension.get(..6)?.try_into().ok()?;
+
+        Some(match passthrough.push(bytes) {
+            PassthroughData::MotionPlus(motion_plus) => WiimoteEvent::MotionPlus(motion_plus),
+            PassthroughData::Nunchuk(nunchuk) => WiimoteEvent::Nunchuk(nunchuk),
+            PassthroughData::Classic(classic) => WiimoteEvent::Classic(classic),
+        })
+    }
+
+    fn decode_buttons(&mut self, report: &hid::Report) -> Option<WiimoteEvent> {
+        let event = match WiimoteEvent::from_report(report)? {
+            WiimoteEvent::ButtonsChanged(buttons) => {
+                WiimoteEvent::ButtonsChanged(self.button_remap.apply(buttons))
+            }
+            event => event,
+        };
+
+        if let WiimoteEvent::ButtonsChanged(buttons) = event {
+            if self.buttons == Some(buttons) {
+                if self.dedup_button_reports {
+                    return None;
+                }
+                return Some(WiimoteEvent::ButtonsResent(buttons));
+            }
+            self.buttons = Some(buttons);
+        }
+
+        Some(event)
+    }
+
+    /// Get the next event after the Wiimote has disconnected.
+    ///
+    /// The buttons that were held down are released one at a time with
+    /// [`WiimoteEvent::ButtonUp`], so they don't stay pressed forever. After
+    /// that, this always returns [`WiimoteEvent::Disconnected`].
+    pub fn disconnect(&mut self) -> WiimoteEvent {
+        if let Some(event) = self.pending() {
+            return event;
+        }
+
+        let held = self.buttons.unwrap_or_else(CoreButtons::empty);
+        if held.is_empty() {
+            return WiimoteEvent::Disconnected;
+        }
+
+        // Release the lowest held button
+        let button = held.iter_pressed().next().unwrap();
+        self.buttons = Some(held - button.to_flag());
+
+        WiimoteEvent::ButtonUp(button)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(bytes: &[u8]) -> hid::Report {
+        bytes.iter().copied().collect()
+    }
+
+    #[test]
+    fn identity_remap_keeps_every_button() {
+        let remap = ButtonRemap::default();
+        assert_eq!(remap.apply(CoreButtons::all()), CoreButtons::all());
+        assert_eq!(remap.apply(CoreButtons::empty()), CoreButtons::empty());
+        assert_eq!(
+            ButtonRemap::new().apply(CoreButtons::all()),
+            CoreButtons::empty()
+        );
+    }
+
+    #[test]
+    fn sideways_remap() {
+        let remap = ButtonRemap::sideways();
+        assert_eq!(remap.apply(CoreButtons::TWO), CoreButtons::A);
+        assert_eq!(remap.apply(CoreButtons::ONE), CoreButtons::B);
+        assert_eq!(remap.apply(CoreButtons::A), CoreButtons::A);
+        assert_eq!(remap.apply(CoreButtons::UP), CoreButtons::LEFT);
+        assert_eq!(remap.apply(CoreButtons::DOWN), CoreButtons::RIGHT);
+        assert_eq!(remap.apply(CoreButtons::LEFT), CoreButtons::DOWN);
+        assert_eq!(remap.apply(CoreButtons::RIGHT), CoreButtons::UP);
+        assert_eq!(
+            remap.apply(CoreButtons::TWO | CoreButtons::RIGHT),
+            CoreButtons::A | CoreButtons::UP
+        );
+        // Both 2 and A press A
+        assert_eq!(
+            remap.apply(CoreButtons::TWO | CoreButtons::A),
+            CoreButtons::A
+        );
+    }
+
+    #[test]
+    fn chords_take_their_buttons_from_smaller_bindings() {
+        let mut remap = ButtonRemap::identity();
+        remap.bind(CoreButtons::A | CoreButtons::B, CoreButtons::HOME);
+
+        assert_eq!(remap.apply(CoreButtons::A), CoreButtons::A);
+        assert_eq!(
+            remap.apply(CoreButtons::A | CoreButtons::B),
+            CoreButtons::HOME
+        );
+        assert_eq!(
+            remap.apply(CoreButtons::A | CoreButtons::B | CoreButtons::ONE),
+            CoreButtons::HOME | CoreButtons::ONE
+        );
+
+        // One binding pressing several logical buttons
+        remap.bind(CoreButtons::A | CoreButtons::B, CoreButtons::PLUS);
+        assert_eq!(
+            remap.apply(CoreButtons::A | CoreButtons::B),
+            CoreButtons::HOME | CoreButtons::PLUS
+        );
+
+        remap.unbind(CoreButtons::A | CoreButtons::B);
+        assert_eq!(
+            remap.apply(CoreButtons::A | CoreButtons::B),
+            CoreButtons::A | CoreButtons::B
+        );
+    }
+
+    #[test]
+    fn decoder_applies_the_remap() {
+        let config = WiimoteConfig {
+            button_remap: ButtonRemap::sideways(),
+            ..Default::default()
+        };
+        let mut decoder = EventDecoder::new(&config);
+
+        let events: Vec<_> = decoder
+            .decode(&report(&[INPUT_REPORT, 0x30, 0x01, 0x01]))
+            .into_iter()
+            .collect();
+        assert_eq!(
+            events,
+            [WiimoteEvent::ButtonsChanged(
+                CoreButtons::A | CoreButtons::DOWN
+            )]
+        );
+    }
+
+    #[test]
+    fn battery_warning_has_hysteresis() {
+        let config = WiimoteConfig {
+            battery_warning: Some(BatteryWarning {
+                low_percent: 15,
+                margin: 5,
+            }),
+            ..Default::default()
+        };
+        let mut decoder = EventDecoder::new(&config);
+
+        // Down through the threshold, wobbling around it, then back up
+        let percents = [30, 16, 14, 16, 12, 14, 19, 16, 20, 18, 14];
+        let mut events = Vec::new();
+        for percent in percents {
+            let status = [INPUT_REPORT, 0x20, 0, 0, 0, 0, 0, percent * 2];
+            events.extend(decoder.decode(&report(&status)));
+            events.extend(std::iter::from_fn(|| decoder.pending()));
+        }
+
+        let warnings: Vec<_> = events
+            .into_iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    WiimoteEvent::BatteryLow(_) | WiimoteEvent::BatteryOk(_)
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                WiimoteEvent::BatteryLow(14),
+                WiimoteEvent::BatteryOk(20),
+                WiimoteEvent::BatteryLow(14),
+            ]
+        );
+    }
+
+    #[test]
+    fn battery_is_low_from_the_first_status() {
+        let mut decoder = EventDecoder::new(&WiimoteConfig::default());
+        let status = report(&[INPUT_REPORT, 0x20, 0, 0, 0, 0, 0, 10]);
+
+        let mut events: Vec<_> = decoder.decode(&status).into_iter().collect();
+        events.extend(std::iter::from_fn(|| decoder.pending()));
+        assert!(events.contains(&WiimoteEvent::BatteryLow(5)));
+    }
+
+    #[test]
+    fn passthrough_samples_alternate() {
+        let config = WiimoteConfig {
+            motion_plus: Some(MotionPlusMode::NunchukPassthrough),
+            ..Default::default()
+        };
+        let mut decoder = EventDecoder::new(&config);
+
+        let motion_plus = [0x80, 0x80, 0x80, 0x80, 0x80, 0x82];
+        let nunchuk = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80];
+        let mut events = Vec::new();
+        for i in 0..4 {
+            let sample = if i % 2 == 0 { motion_plus } else { nunchuk };
+            let mut bytes = vec![INPUT_REPORT, 0x32, 0, 0];
+            bytes.extend_from_slice(&sample);
+            bytes.extend_from_slice(&[0, 0]);
+            events.extend(decoder.decode(&report(&bytes)));
+            events.extend(std::iter::from_fn(|| decoder.pending()));
+        }
+
+        // Ignoring the buttons, which are decoded from the first report
+        let kinds: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WiimoteEvent::MotionPlus(_) => Some("motion plus"),
+                WiimoteEvent::Nunchuk(_) => Some("nunchuk"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds, ["motion plus", "nunchuk", "motion plus", "nunchuk"]);
+    }
+}
@@ -0,0 +1,204 @@
+// The log format is JSON Lines: one object per event, with the time since the
+// log started in microseconds, the player number, and the event itself, e.g.
+// {"micros":1500,"player":0,"event":{"BatteryLow":10}}
+
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::event::WiimoteEvent;
+
+/// An event read back from an event log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoggedEvent {
+    /// The time since the log started.
+    pub time: Duration,
+    /// The (zero-based) player number of the Wiimote that sent the event.
+    pub player: usize,
+    pub event: WiimoteEvent,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    micros: u64,
+    player: usize,
+    event: WiimoteEvent,
+}
+
+/// Logs the decoded events of any number of Wiimotes, along with when they
+/// happened, in a human readable format that can be read back by an
+/// [`EventLogReader`].
+///
+/// Events are written in the order that they are logged, so to log several
+/// Wiimotes the writer has to be shared, e.g. behind a mutex.
+pub struct EventLogWriter<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl EventLogWriter<BufWriter<File>> {
+    /// Start a new log in the file at `path`, replacing it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> EventLogWriter<W> {
+    /// Start a new log, writing it to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Add an event from `player` to the log, timestamped with the current
+    /// time.
+    pub fn log(&mut self, player: usize, event: &WiimoteEvent) -> io::Result<()> {
+        self.log_at(self.start.elapsed(), player, event)
+    }
+
+    /// Add an event from `player` to the log, `time` after the log started.
+    pub fn log_at(
+        &mut self,
+        time: Duration,
+        player: usize,
+        event: &WiimoteEvent,
+    ) -> io::Result<()> {
+        let entry = Entry {
+            micros: time.as_micros() as u64,
+            player,
+            event: *event,
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// Finish the log, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads back a log written by an [`EventLogWriter`], one event at a time in
+/// the order they were logged.
+pub struct EventLogReader<R: BufRead> {
+    lines: io::Lines<R>,
+}
+
+impl EventLogReader<BufReader<File>> {
+    /// Open the log in the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> EventLogReader<R> {
+    /// Read a log from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EventLogReader<R> {
+    type Item = io::Result<LoggedEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = loop {
+            match self.lines.next()? {
+                // Blank lines are allowed, e.g. from editing a log by hand
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => break line,
+                Err(e) => return Some(Err(e)),
+            }
+        };
+
+        let entry = match serde_json::from_str::<Entry>(&line) {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        Some(Ok(LoggedEvent {
+            time: Duration::from_micros(entry.micros),
+            player: entry.player,
+            event: entry.event,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buttons::{Button, CoreButtons};
+
+    #[test]
+    fn events_round_trip_in_order() {
+        let events = [
+            (
+                Duration::from_micros(0),
+                0,
+                WiimoteEvent::ButtonsChanged(CoreButtons::A),
+            ),
+            (Duration::from_micros(1500), 1, WiimoteEvent::BatteryLow(10)),
+            (
+                Duration::from_millis(20),
+                0,
+                WiimoteEvent::ButtonUp(Button::A),
+            ),
+            (
+                Duration::from_millis(20),
+                1,
+                WiimoteEvent::ExtensionReinitialized,
+            ),
+        ];
+
+        let mut writer = EventLogWriter::new(Vec::new());
+        for (time, player, event) in &events {
+            writer.log_at(*time, *player, event).unwrap();
+        }
+        let log = writer.finish().unwrap();
+        assert_eq!(
+            log.iter().filter(|&&byte| byte == b'\n').count(),
+            events.len()
+        );
+
+        let read: Vec<_> = EventLogReader::new(&log[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        let expected: Vec<_> = events
+            .iter()
+            .map(|&(time, player, event)| LoggedEvent {
+                time,
+                player,
+                event,
+            })
+            .collect();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn blank_lines_are_skipped_and_bad_lines_are_errors() {
+        let log = "\n{\"micros\":1500,\"player\":0,\"event\":{\"BatteryLow\":10}}\n  \nnot json\n";
+        let mut reader = EventLogReader::new(log.as_bytes());
+
+        assert_eq!(
+            reader.next().unwrap().unwrap(),
+            LoggedEvent {
+                time: Duration::from_micros(1500),
+                player: 0,
+                event: WiimoteEvent::BatteryLow(10),
+            }
+        );
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(reader.next().is_none());
+    }
+}
@@ -0,0 +1,737 @@
+use bitflags::bitflags;
+
+use crate::motion::Vec3;
+
+/// The register writes that initialize an extension with encryption disabled,
+/// as (address, value) pairs.
+pub const EXTENSION_INIT_WRITES: [(u32, u8); 2] = [(0xa400f0, 0x55), (0xa400fb, 0x00)];
+
+/// The state of a Nunchuk, decoded from the first 6 bytes of extension data.
+///
+/// This assumes the extension was initialized without encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Nunchuk {
+    /// The raw X position of the stick, where ~128 is the center.
+    pub stick_x: u8,
+    /// The raw Y position of the stick, where ~128 is the center.
+    pub stick_y: u8,
+    /// The raw 10-bit accelerometer values, in X, Y, Z order.
+    pub accel: [u16; 3],
+    pub c: bool,
+    pub z: bool,
+}
+
+impl Nunchuk {
+    pub fn from_bytes(bytes: &[u8; 6]) -> Self {
+        let accel_lsb = |shift: u8| ((bytes[5] >> shift) & 0b11) as u16;
+
+        Self {
+            stick_x: bytes[0],
+            stick_y: bytes[1],
+            accel: [
+                (bytes[2] as u16) << 2 | accel_lsb(2),
+                (bytes[3] as u16) << 2 | accel_lsb(4),
+                (bytes[4] as u16) << 2 | accel_lsb(6),
+            ],
+            // The buttons are active low
+            c: bytes[5] & 0x02 == 0,
+            z: bytes[5] & 0x01 == 0,
+        }
+    }
+
+    /// Decode Nunchuk data passed through a MotionPlus in
+    /// [`MotionPlusMode::NunchukPassthrough`].
+    ///
+    /// The MotionPlus drops the lowest bit of each accelerometer value (and
+    /// the two lowest of Z) to make room for its own flags, so those are zero.
+    pub fn from_passthrough_bytes(bytes: &[u8; 6]) -> Self {
+        let accel_bit = |shift: u8| ((bytes[5] >> shift) & 0b1) as u16;
+
+        Self {
+            stick_x: bytes[0],
+            stick_y: bytes[1],
+            accel: [
+                (bytes[2] as u16) << 2 | accel_bit(4) << 1,
+                (bytes[3] as u16) << 2 | accel_bit(5) << 1,
+                ((bytes[4] & 0xfe) as u16) << 2 | ((bytes[5] >> 6) as u16) << 1,
+            ],
+            // The buttons are active low
+            c: bytes[5] & 0x08 == 0,
+            z: bytes[5] & 0x04 == 0,
+        }
+    }
+
+    /// The position of the stick, mapped to -1.0..=1.0 on each axis using the
+    /// Nunchuk's calibration.
+    #[cfg(feature = "std")]
+    pub fn stick_normalized(&self, cal: &NunchukCalibration) -> (f32, f32) {
+        self.stick_normalized_with_deadzone(cal, 0.0)
+    }
+
+    /// The same as [`Nunchuk::stick_normalized`], but positions within
+    /// `deadzone` of the center are mapped to (0.0, 0.0).
+    ///
+    /// The deadzone is radial, and the rest of the range is rescaled so that
+    /// the stick still reaches 1.0 at the edge.
+    #[cfg(feature = "std")]
+    pub fn stick_normalized_with_deadzone(
+        &self,
+        cal: &NunchukCalibration,
+        deadzone: f32,
+    ) -> (f32, f32) {
+        let x = cal.stick_x.normalize(self.stick_x);
+        let y = cal.stick_y.normalize(self.stick_y);
+
+        let magnitude = x.hypot(y);
+        if magnitude <= deadzone {
+            return (0.0, 0.0);
+        }
+
+        let scale = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0) / magnitude;
+        (x * scale, y * scale)
+    }
+}
+
+/// The calibration of a single stick axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisCalibration {
+    pub min: u8,
+    pub center: u8,
+    pub max: u8,
+}
+
+impl AxisCalibration {
+    /// Map a raw value on this axis to -1.0..=1.0.
+    pub fn normalize(&self, raw: u8) -> f32 {
+        let offset = raw as f32 - self.center as f32;
+        let range = if offset >= 0.0 {
+            self.max.saturating_sub(self.center)
+        } else {
+            self.center.saturating_sub(self.min)
+        };
+
+        if range == 0 {
+            0.0
+        } else {
+            (offset / range as f32).clamp(-1.0, 1.0)
+        }
+    }
+}
+
+/// The calibration data stored in a Nunchuk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NunchukCalibration {
+    /// The raw 10-bit accelerometer values at 0G, in X, Y, Z order.
+    pub accel_zero: [u16; 3],
+    /// The raw 10-bit accelerometer values at 1G, in X, Y, Z order.
+    pub accel_one_g: [u16; 3],
+    pub stick_x: AxisCalibration,
+    pub stick_y: AxisCalibration,
+}
+
+impl NunchukCalibration {
+    /// The address of the calibration data in the Nunchuk's registers.
+    pub const ADDRESS: u32 = 0xa40020;
+    /// The length of the calibration data, including the checksum.
+    pub const LENGTH: usize = 16;
+
+    /// Decode the calibration data read from [`NunchukCalibration::ADDRESS`].
+    ///
+    /// Returns `None` if the checksum doesn't match, which happens with some
+    /// third-party Nunchuks.
+    pub fn from_bytes(bytes: &[u8; Self::LENGTH]) -> Option<Self> {
+        let sum = bytes[..14]
+            .iter()
+            .fold(0u8, |acc, &byte| acc.wrapping_add(byte));
+        if bytes[14] != sum.wrapping_add(0x55) || bytes[15] != sum.wrapping_add(0xaa) {
+            return None;
+        }
+
+        let accel = |offset: usize| {
+            let lsb = bytes[offset + 3];
+            [
+                (bytes[offset] as u16) << 2 | (lsb >> 4 & 0b11) as u16,
+                (bytes[offset + 1] as u16) << 2 | (lsb >> 2 & 0b11) as u16,
+                (bytes[offset + 2] as u16) << 2 | (lsb & 0b11) as u16,
+            ]
+        };
+
+        Some(Self {
+            accel_zero: accel(0),
+            accel_one_g: accel(4),
+            stick_x: AxisCalibration {
+                max: bytes[8],
+                min: bytes[9],
+                center: bytes[10],
+            },
+            stick_y: AxisCalibration {
+                max: bytes[11],
+                min: bytes[12],
+                center: bytes[13],
+            },
+        })
+    }
+}
+
+impl Default for NunchukCalibration {
+    /// Typical calibration values, for when the Nunchuk's own calibration
+    /// can't be read.
+    fn default() -> Self {
+        Self {
+            accel_zero: [512, 512, 512],
+            accel_one_g: [716, 716, 716],
+            stick_x: AxisCalibration {
+                min: 35,
+                center: 128,
+                max: 228,
+            },
+            stick_y: AxisCalibration {
+                min: 27,
+                center: 128,
+                max: 220,
+            },
+        }
+    }
+}
+
+/// The types of Classic Controller, which share the same data layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClassicKind {
+    Classic,
+    /// The Classic Controller Pro, which has grips and no analog triggers.
+    Pro,
+}
+
+impl ClassicKind {
+    /// Identify a Classic Controller from the 6-byte extension identifier at
+    /// 0xa400fa.
+    pub fn from_id(id: &[u8; 6]) -> Option<Self> {
+        match id {
+            [0x00, 0x00, 0xa4, 0x20, _, 0x01] => Some(Self::Classic),
+            [0x01, 0x00, 0xa4, 0x20, _, 0x01] => Some(Self::Pro),
+            _ => None,
+        }
+    }
+}
+
+/// The formats that a Classic Controller can send its data in.
+///
+/// The current format is the fifth byte of the extension identifier, and can
+/// be changed by writing to [`ClassicFormat::ADDRESS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum ClassicFormat {
+    /// 6 bytes, with reduced precision sticks and triggers.
+    Standard = 0x01,
+    /// 8 bytes, with full 8-bit sticks and triggers. Some adapters use this
+    /// to pass through other controllers.
+    HighRes = 0x03,
+}
+
+impl ClassicFormat {
+    /// The register that the format is written to and read from.
+    pub const ADDRESS: u32 = 0xa400fe;
+
+    /// Get the data format from the extension identifier.
+    pub fn from_id(id: &[u8; 6]) -> Option<Self> {
+        match id[4] {
+            0x01 => Some(Self::Standard),
+            0x03 => Some(Self::HighRes),
+            _ => None,
+        }
+    }
+
+    /// How many bytes of extension data the controller sends in this format.
+    pub fn data_length(self) -> usize {
+        match self {
+            Self::Standard => 6,
+            Self::HighRes => 8,
+        }
+    }
+}
+
+impl From<ClassicFormat> for u8 {
+    fn from(val: ClassicFormat) -> Self {
+        val as u8
+    }
+}
+
+bitflags! {
+    /// The buttons on a Classic Controller.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct ClassicButtons: u16 {
+        const RIGHT = 0x8000;
+        const DOWN = 0x4000;
+        const L = 0x2000;
+        const MINUS = 0x1000;
+        const HOME = 0x0800;
+        const PLUS = 0x0400;
+        const R = 0x0200;
+        const ZL = 0x0080;
+        const B = 0x0040;
+        const Y = 0x0020;
+        const A = 0x0010;
+        const X = 0x0008;
+        const ZR = 0x0004;
+        const LEFT = 0x0002;
+        const UP = 0x0001;
+    }
+}
+
+impl ClassicButtons {
+    /// Decode the buttons from the two button bytes of the extension data.
+    pub fn from_bytes(bytes: [u8; 2]) -> Self {
+        // The buttons are active low
+        Self::from_bits_truncate(!u16::from_be_bytes(bytes))
+    }
+}
+
+/// The state of a Classic Controller, decoded from the extension data.
+///
+/// The sticks and triggers are always scaled to 0..=255, whatever the format,
+/// so the low bits are zero in [`ClassicFormat::Standard`]. This assumes the
+/// extension was initialized without encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassicController {
+    pub kind: ClassicKind,
+    pub format: ClassicFormat,
+    /// The raw (X, Y) position of the left stick, where ~128 is the center.
+    pub left_stick: (u8, u8),
+    /// The raw (X, Y) position of the right stick, where ~128 is the center.
+    pub right_stick: (u8, u8),
+    /// How far the left trigger is pressed.
+    ///
+    /// The Classic Controller Pro has no analog triggers, so this is either 0
+    /// or 255 depending on [`ClassicButtons::L`].
+    pub left_trigger: u8,
+    /// How far the right trigger is pressed. See
+    /// [`ClassicController::left_trigger`].
+    pub right_trigger: u8,
+    pub buttons: ClassicButtons,
+}
+
+impl ClassicController {
+    /// Decode the extension data, which has to be at least
+    /// [`ClassicFormat::data_length`] bytes long.
+    pub fn decode(kind: ClassicKind, format: ClassicFormat, bytes: &[u8]) -> Option<Self> {
+        let mut classic = match format {
+            ClassicFormat::Standard => Self::from_standard_bytes(bytes.get(..6)?.try_into().ok()?),
+            ClassicFormat::HighRes => Self::from_high_res_bytes(bytes.get(..8)?.try_into().ok()?),
+        };
+        classic.kind = kind;
+
+        // The Pro has no analog triggers, so don't trust whatever it sends
+        if kind == ClassicKind::Pro {
+            let digital = |button| {
+                if classic.buttons.contains(button) {
+                    0xff
+                } else {
+                    0x00
+                }
+            };
+            classic.left_trigger = digital(ClassicButtons::L);
+            classic.right_trigger = digital(ClassicButtons::R);
+        }
+
+        Some(classic)
+    }
+
+    /// Decode data in [`ClassicFormat::Standard`], as a standard Classic
+    /// Controller.
+    pub fn from_standard_bytes(bytes: &[u8; 6]) -> Self {
+        let left_x = bytes[0] & 0x3f;
+        let left_y = bytes[1] & 0x3f;
+        let right_x = (bytes[0] >> 6) << 3 | (bytes[1] >> 6) << 1 | bytes[2] >> 7;
+        let right_y = bytes[2] & 0x1f;
+        let left_trigger = (bytes[2] >> 5 & 0b11) << 3 | bytes[3] >> 5;
+        let right_trigger = bytes[3] & 0x1f;
+
+        Self {
+            kind: ClassicKind::Classic,
+            format: ClassicFormat::Standard,
+            left_stick: (left_x << 2, left_y << 2),
+            right_stick: (right_x << 3, right_y << 3),
+            left_trigger: left_trigger << 3,
+            right_trigger: right_trigger << 3,
+            buttons: ClassicButtons::from_bytes([bytes[4], bytes[5]]),
+        }
+    }
+
+    /// Decode Classic Controller data passed through a MotionPlus in
+    /// [`MotionPlusMode::ClassicPassthrough`], as a standard Classic
+    /// Controller.
+    ///
+    /// The MotionPlus moves the Up and Left buttons into the lowest bits of
+    /// the left stick to make room for its own flags, so those bits are zero.
+    pub fn from_passthrough_bytes(bytes: &[u8; 6]) -> Self {
+        // Put the bytes back into the standard layout, where the unused bit
+        // of byte 4 reads as released
+        let standard = [
+            bytes[0] & 0xfe,
+            bytes[1] & 0xfe,
+            bytes[2],
+            bytes[3],
+            bytes[4] | 0x01,
+            bytes[5] & 0xfc | (bytes[1] & 0x01) << 1 | bytes[0] & 0x01,
+        ];
+
+        Self::from_standard_bytes(&standard)
+    }
+
+    /// Decode data in [`ClassicFormat::HighRes`], as a standard Classic
+    /// Controller.
+    pub fn from_high_res_bytes(bytes: &[u8; 8]) -> Self {
+        Self {
+            kind: ClassicKind::Classic,
+            format: ClassicFormat::HighRes,
+            left_stick: (bytes[0], bytes[2]),
+            right_stick: (bytes[1], bytes[3]),
+            left_trigger: bytes[4],
+            right_trigger: bytes[5],
+            buttons: ClassicButtons::from_bytes([bytes[6], bytes[7]]),
+        }
+    }
+}
+
+/// The gyroscope data from a MotionPlus, decoded from 6 bytes of extension
+/// data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MotionPlusData {
+    /// The raw 14-bit rotation rates about the X, Y, and Z axes (pitch, roll,
+    /// and yaw), where ~8192 is still.
+    pub raw: [u16; 3],
+    /// Whether each axis is in slow (high precision) mode.
+    pub slow: [bool; 3],
+    /// Whether an extension is plugged into the MotionPlus.
+    pub extension_connected: bool,
+}
+
+impl MotionPlusData {
+    // Raw units per degree per second in slow mode
+    const SLOW_SCALE: f32 = 8192.0 / 595.0;
+    // Fast mode covers a wider range at a lower precision
+    const FAST_MULTIPLIER: f32 = 2000.0 / 440.0;
+
+    pub fn from_bytes(bytes: &[u8; 6]) -> Self {
+        let value = |lsb: u8, msb: u8| (msb as u16 >> 2) << 8 | lsb as u16;
+
+        Self {
+            raw: [
+                value(bytes[2], bytes[5]),
+                value(bytes[1], bytes[4]),
+                value(bytes[0], bytes[3]),
+            ],
+            slow: [
+                bytes[3] & 0x01 != 0,
+                bytes[4] & 0x02 != 0,
+                bytes[3] & 0x02 != 0,
+            ],
+            extension_connected: bytes[4] & 0x01 != 0,
+        }
+    }
+
+    /// The angular velocity about the X, Y, and Z axes in radians per second.
+    ///
+    /// This uses the nominal zero point, so it will drift slightly.
+    pub fn angular_velocity(&self) -> Vec3 {
+        let mut velocity = [0.0; 3];
+        for (i, v) in velocity.iter_mut().enumerate() {
+            let mut degrees = (self.raw[i] as f32 - 8192.0) / Self::SLOW_SCALE;
+            if !self.slow[i] {
+                degrees *= Self::FAST_MULTIPLIER;
+            }
+            *v = degrees.to_radians();
+        }
+
+        velocity.into()
+    }
+}
+
+/// The modes that a MotionPlus can be activated in.
+///
+/// In the passthrough modes, the MotionPlus interleaves its own data with the
+/// data from the extension plugged into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MotionPlusMode {
+    Standalone = 0x04,
+    NunchukPassthrough = 0x05,
+    ClassicPassthrough = 0x07,
+}
+
+impl MotionPlusMode {
+    /// The register that is written to when initializing a MotionPlus.
+    pub const INIT_ADDRESS: u32 = 0xa600f0;
+    /// The register that the mode is written to, which activates the
+    /// MotionPlus.
+    pub const ACTIVATE_ADDRESS: u32 = 0xa600fe;
+
+    /// The extension identifier reported once the MotionPlus is active in this
+    /// mode.
+    pub fn extension_id(&self) -> [u8; 6] {
+        [0x00, 0x00, 0xa4, 0x20, *self as u8, 0x05]
+    }
+
+    /// Whether an extension has to be plugged into the MotionPlus for this mode.
+    pub fn is_passthrough(&self) -> bool {
+        *self != Self::Standalone
+    }
+}
+
+/// One sample of extension data from an active MotionPlus.
+///
+/// In the passthrough modes, the MotionPlus alternates between sending its
+/// own data and the data of the extension plugged into it, so each kind of
+/// sample arrives at half the report rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassthroughData {
+    MotionPlus(MotionPlusData),
+    Nunchuk(Nunchuk),
+    Classic(ClassicController),
+}
+
+impl PassthroughData {
+    // Set in byte 5 of every sample of the MotionPlus's own data, and clear in
+    // every sample that was passed through
+    const MOTION_PLUS_BIT: u8 = 0x02;
+
+    /// Decode the first 6 bytes of extension data from a MotionPlus that was
+    /// activated in `mode`.
+    pub fn decode(mode: MotionPlusMode, bytes: &[u8; 6]) -> Self {
+        if bytes[5] & Self::MOTION_PLUS_BIT != 0 {
+            return Self::MotionPlus(MotionPlusData::from_bytes(bytes));
+        }
+
+        match mode {
+            // Nothing is passed through in standalone mode
+            MotionPlusMode::Standalone => Self::MotionPlus(MotionPlusData::from_bytes(bytes)),
+            MotionPlusMode::NunchukPassthrough => {
+                Self::Nunchuk(Nunchuk::from_passthrough_bytes(bytes))
+            }
+            MotionPlusMode::ClassicPassthrough => {
+                Self::Classic(ClassicController::from_passthrough_bytes(bytes))
+            }
+        }
+    }
+}
+
+/// Routes the interleaved samples from a MotionPlus in a passthrough mode,
+/// keeping the latest sample of each kind.
+#[derive(Debug, Clone)]
+pub struct PassthroughDecoder {
+    mode: MotionPlusMode,
+    motion_plus: Option<MotionPlusData>,
+    extension: Option<PassthroughData>,
+}
+
+impl PassthroughDecoder {
+    pub fn new(mode: MotionPlusMode) -> Self {
+        Self {
+            mode,
+            motion_plus: None,
+            extension: None,
+        }
+    }
+
+    pub fn mode(&self) -> MotionPlusMode {
+        self.mode
+    }
+
+    /// Decode the next sample of extension data.
+    pub fn push(&mut self, bytes: &[u8; 6]) -> PassthroughData {
+        let data = PassthroughData::decode(self.mode, bytes);
+        match data {
+            PassthroughData::MotionPlus(motion_plus) => self.motion_plus = Some(motion_plus),
+            _ => self.extension = Some(data),
+        }
+
+        data
+    }
+
+    /// The latest sample of the MotionPlus's own data.
+    pub fn motion_plus(&self) -> Option<MotionPlusData> {
+        self.motion_plus
+    }
+
+    /// The latest sample of the Nunchuk's data, in
+    /// [`MotionPlusMode::NunchukPassthrough`].
+    pub fn nunchuk(&self) -> Option<Nunchuk> {
+        match self.extension? {
+            PassthroughData::Nunchuk(nunchuk) => Some(nunchuk),
+            _ => None,
+        }
+    }
+
+    /// The latest sample of the Classic Controller's data, in
+    /// [`MotionPlusMode::ClassicPassthrough`].
+    pub fn classic(&self) -> Option<ClassicController> {
+        match self.extension? {
+            PassthroughData::Classic(classic) => Some(classic),
+            _ => None,
+        }
+    }
+
+    /// Forget the latest samples, e.g. after the extension was reinitialized.
+    pub fn reset(&mut self) {
+        self.motion_plus = None;
+        self.extension = None;
+    }
+}
+
+impl From<MotionPlusMode> for u8 {
+    fn from(val: MotionPlusMode) -> Self {
+        val as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Calibration data with the stick's X axis from 30 to 220 centered on 125,
+    // and its Y axis from 40 to 210 centered on 130
+    fn nunchuk_calibration_bytes() -> [u8; NunchukCalibration::LENGTH] {
+        let mut bytes = [
+            0x80, 0x80, 0x80, 0x00, 0xb3, 0xb3, 0xb3, 0x00, 220, 30, 125, 210, 40, 130, 0, 0,
+        ];
+        let sum = bytes[..14]
+            .iter()
+            .fold(0u8, |acc, &byte| acc.wrapping_add(byte));
+        bytes[14] = sum.wrapping_add(0x55);
+        bytes[15] = sum.wrapping_add(0xaa);
+
+        bytes
+    }
+
+    #[cfg(feature = "std")]
+    fn nunchuk_at(stick_x: u8, stick_y: u8) -> Nunchuk {
+        Nunchuk {
+            stick_x,
+            stick_y,
+            accel: [512, 512, 512],
+            c: false,
+            z: false,
+        }
+    }
+
+    #[test]
+    fn nunchuk_calibration_checks_the_checksum() {
+        let mut bytes = nunchuk_calibration_bytes();
+        let cal = NunchukCalibration::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            cal.stick_x,
+            AxisCalibration {
+                min: 30,
+                center: 125,
+                max: 220,
+            }
+        );
+        assert_eq!(cal.accel_zero, [512, 512, 512]);
+
+        bytes[14] = bytes[14].wrapping_add(1);
+        assert_eq!(NunchukCalibration::from_bytes(&bytes), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn nunchuk_stick_uses_the_calibration() {
+        let cal = NunchukCalibration::from_bytes(&nunchuk_calibration_bytes()).unwrap();
+
+        assert_eq!(nunchuk_at(125, 130).stick_normalized(&cal), (0.0, 0.0));
+        assert_eq!(nunchuk_at(220, 130).stick_normalized(&cal), (1.0, 0.0));
+        assert_eq!(nunchuk_at(125, 40).stick_normalized(&cal), (0.0, -1.0));
+        assert_eq!(nunchuk_at(30, 130).stick_normalized(&cal), (-1.0, 0.0));
+        // The corners are pulled in to the edge of the circle
+        let (x, y) = nunchuk_at(220, 210).stick_normalized(&cal);
+        assert!((x.hypot(y) - 1.0).abs() < 0.001 && (x - y).abs() < 0.001);
+        // Halfway between the center and the edge on each side
+        let (x, y) = nunchuk_at(125 + 95 / 2, 130 - 90 / 2).stick_normalized(&cal);
+        assert!((x - 0.5).abs() < 0.01 && (y + 0.5).abs() < 0.01);
+        // Sticks that go past their calibration are clamped
+        assert_eq!(nunchuk_at(255, 130).stick_normalized(&cal), (1.0, 0.0));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn nunchuk_deadzone_is_radial_and_rescaled() {
+        let cal = NunchukCalibration::from_bytes(&nunchuk_calibration_bytes()).unwrap();
+
+        let small = nunchuk_at(130, 134).stick_normalized_with_deadzone(&cal, 0.1);
+        assert_eq!(small, (0.0, 0.0));
+        let edge = nunchuk_at(220, 130).stick_normalized_with_deadzone(&cal, 0.1);
+        assert_eq!(edge, (1.0, 0.0));
+        let (x, _) = nunchuk_at(125 + 95 / 2, 130).stick_normalized_with_deadzone(&cal, 0.1);
+        assert!(x > 0.0 && x < 0.5);
+    }
+
+    #[test]
+    fn classic_kind_and_format_from_the_id() {
+        let classic = [0x00, 0x00, 0xa4, 0x20, 0x01, 0x01];
+        let pro_high_res = [0x01, 0x00, 0xa4, 0x20, 0x03, 0x01];
+
+        assert_eq!(ClassicKind::from_id(&classic), Some(ClassicKind::Classic));
+        assert_eq!(ClassicKind::from_id(&pro_high_res), Some(ClassicKind::Pro));
+        assert_eq!(
+            ClassicFormat::from_id(&classic),
+            Some(ClassicFormat::Standard)
+        );
+        assert_eq!(
+            ClassicFormat::from_id(&pro_high_res),
+            Some(ClassicFormat::HighRes)
+        );
+
+        let nunchuk = [0x00, 0x00, 0xa4, 0x20, 0x00, 0x00];
+        assert_eq!(ClassicKind::from_id(&nunchuk), None);
+    }
+
+    #[test]
+    fn classic_standard_layout() {
+        let bytes = [0xa0, 0x10, 0x68, 0xe4, 0xff, 0xef];
+        let classic =
+            ClassicController::decode(ClassicKind::Classic, ClassicFormat::Standard, &bytes)
+                .unwrap();
+
+        assert_eq!(classic.left_stick, (0x80, 0x40));
+        assert_eq!(classic.right_stick, (0x80, 0x40));
+        assert_eq!(classic.left_trigger, 0xf8);
+        assert_eq!(classic.right_trigger, 0x20);
+        assert_eq!(classic.buttons, ClassicButtons::A);
+    }
+
+    #[test]
+    fn classic_high_res_layout() {
+        let bytes = [0x81, 0x82, 0x83, 0x84, 0x10, 0x20, 0xff, 0xef];
+        let classic =
+            ClassicController::decode(ClassicKind::Classic, ClassicFormat::HighRes, &bytes)
+                .unwrap();
+
+        assert_eq!(classic.format, ClassicFormat::HighRes);
+        assert_eq!(classic.left_stick, (0x81, 0x83));
+        assert_eq!(classic.right_stick, (0x82, 0x84));
+        assert_eq!((classic.left_trigger, classic.right_trigger), (0x10, 0x20));
+        assert_eq!(classic.buttons, ClassicButtons::A);
+
+        // The data has to be long enough for the format
+        assert_eq!(
+            ClassicController::decode(ClassicKind::Classic, ClassicFormat::HighRes, &bytes[..6]),
+            None
+        );
+    }
+
+    #[test]
+    fn classic_pro_triggers_are_digital() {
+        // L is held, and the trigger bytes are garbage
+        let bytes = [0x80, 0x80, 0x80, 0x80, 0x50, 0x60, 0xdf, 0xff];
+        let pro =
+            ClassicController::decode(ClassicKind::Pro, ClassicFormat::HighRes, &bytes).unwrap();
+
+        assert_eq!(pro.kind, ClassicKind::Pro);
+        assert_eq!((pro.left_trigger, pro.right_trigger), (0xff, 0x00));
+    }
+}
@@ -0,0 +1,373 @@
+// Maps the input of a Wiimote and its extensions onto a generic gamepad, so
+// that Wiimotes can be used by code that only knows about gamepads. This is
+// behind the `gamepad` feature.
+
+use crate::buttons::CoreButtons;
+use crate::event::WiimoteEvent;
+use crate::extension::{ClassicButtons, ClassicController, Nunchuk, NunchukCalibration};
+
+/// A button of a generic gamepad, named by position rather than by label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// An axis of a generic gamepad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    /// The left analog trigger.
+    LeftZ,
+    /// The right analog trigger.
+    RightZ,
+}
+
+impl GamepadAxis {
+    const COUNT: usize = 6;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    ButtonPressed(GamepadButton),
+    ButtonReleased(GamepadButton),
+    /// An axis moved to a new value, which is -1.0..=1.0 for the sticks (up
+    /// and right are positive) and 0.0..=1.0 for the triggers.
+    AxisChanged(GamepadAxis, f32),
+    /// The Wiimote disconnected.
+    Disconnected,
+}
+
+/// Converts Wiimote input into [`GamepadEvent`]s.
+///
+/// The Wiimote's own buttons can be remapped, e.g. with the
+/// [`GamepadMapping::sideways`] preset. Nunchuks and Classic Controllers
+/// always use the same mapping, since their layouts already resemble a
+/// gamepad.
+#[derive(Debug, Clone)]
+pub struct GamepadMapping {
+    /// Which gamepad button each of the Wiimote's buttons is mapped to.
+    ///
+    /// Buttons that aren't in here are ignored.
+    pub core: Vec<(CoreButtons, GamepadButton)>,
+    core_buttons: CoreButtons,
+    nunchuk_buttons: (bool, bool),
+    classic_buttons: ClassicButtons,
+    axes: [f32; GamepadAxis::COUNT],
+}
+
+impl GamepadMapping {
+    const NUNCHUK: [GamepadButton; 2] = [GamepadButton::LeftShoulder, GamepadButton::LeftTrigger];
+
+    const CLASSIC: [(ClassicButtons, GamepadButton); 15] = [
+        (ClassicButtons::B, GamepadButton::South),
+        (ClassicButtons::A, GamepadButton::East),
+        (ClassicButtons::X, GamepadButton::North),
+        (ClassicButtons::Y, GamepadButton::West),
+        (ClassicButtons::ZL, GamepadButton::LeftShoulder),
+        (ClassicButtons::ZR, GamepadButton::RightShoulder),
+        (ClassicButtons::L, GamepadButton::LeftTrigger),
+        (ClassicButtons::R, GamepadButton::RightTrigger),
+        (ClassicButtons::MINUS, GamepadButton::Select),
+        (ClassicButtons::PLUS, GamepadButton::Start),
+        (ClassicButtons::HOME, GamepadButton::Mode),
+        (ClassicButtons::UP, GamepadButton::DPadUp),
+        (ClassicButtons::DOWN, GamepadButton::DPadDown),
+        (ClassicButtons::LEFT, GamepadButton::DPadLeft),
+        (ClassicButtons::RIGHT, GamepadButton::DPadRight),
+    ];
+
+    pub fn new(core: Vec<(CoreButtons, GamepadButton)>) -> Self {
+        Self {
+            core,
+            core_buttons: CoreButtons::empty(),
+            nunchuk_buttons: (false, false),
+            classic_buttons: ClassicButtons::empty(),
+            axes: [0.0; GamepadAxis::COUNT],
+        }
+    }
+
+    /// The mapping for a Wiimote held upright, pointing at the screen.
+    pub fn upright() -> Self {
+        Self::new(vec![
+            (CoreButtons::A, GamepadButton::South),
+            (CoreButtons::B, GamepadButton::RightTrigger),
+            (CoreButtons::ONE, GamepadButton::West),
+            (CoreButtons::TWO, GamepadButton::East),
+            (CoreButtons::MINUS, GamepadButton::Select),
+            (CoreButtons::PLUS, GamepadButton::Start),
+            (CoreButtons::HOME, GamepadButton::Mode),
+            (CoreButtons::UP, GamepadButton::DPadUp),
+            (CoreButtons::DOWN, GamepadButton::DPadDown),
+            (CoreButtons::LEFT, GamepadButton::DPadLeft),
+            (CoreButtons::RIGHT, GamepadButton::DPadRight),
+        ])
+    }
+
+    /// The mapping for a Wiimote held sideways like an NES controller, with
+    /// the D-pad on the left.
+    pub fn sideways() -> Self {
+        Self::new(vec![
+            (CoreButtons::TWO, GamepadButton::South),
+            (CoreButtons::ONE, GamepadButton::West),
+            (CoreButtons::A, GamepadButton::East),
+            (CoreButtons::B, GamepadButton::RightShoulder),
+            (CoreButtons::MINUS, GamepadButton::Select),
+            (CoreButtons::PLUS, GamepadButton::Start),
+            (CoreButtons::HOME, GamepadButton::Mode),
+            // The D-pad is rotated a quarter turn anticlockwise
+            (CoreButtons::UP, GamepadButton::DPadLeft),
+            (CoreButtons::DOWN, GamepadButton::DPadRight),
+            (CoreButtons::LEFT, GamepadButton::DPadDown),
+            (CoreButtons::RIGHT, GamepadButton::DPadUp),
+        ])
+    }
+
+    /// Convert an event from the Wiimote.
+    pub fn map_event(&mut self, event: &WiimoteEvent) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+
+        match *event {
+            WiimoteEvent::ButtonsChanged(buttons) | WiimoteEvent::ButtonsResent(buttons) => {
+                self.update_core(buttons, &mut events);
+            }
+            WiimoteEvent::ButtonUp(button) => {
+                self.update_core(self.core_buttons - button.to_flag(), &mut events);
+            }
+            WiimoteEvent::Disconnected => {
+                self.update_core(CoreButtons::empty(), &mut events);
+                events.push(GamepadEvent::Disconnected);
+            }
+            WiimoteEvent::Classic(classic) => {
+                events = self.map_classic(&classic);
+            }
+            // The Nunchuk can't be mapped without its calibration, so it is
+            // left to `map_nunchuk`
+            WiimoteEvent::ExtensionReinitialized
+            | WiimoteEvent::MotionPlus(_)
+            | WiimoteEvent::Nunchuk(_)
+            | WiimoteEvent::Gesture(_)
+            | WiimoteEvent::Tilt(_)
+            | WiimoteEvent::StatusUpdated(_)
+            | WiimoteEvent::BatteryLow(_)
+            | WiimoteEvent::BatteryOk(_) => {}
+        }
+
+        events
+    }
+
+    /// Convert the state of a Nunchuk, which is mapped to the left stick, the
+    /// left shoulder (C) and the left trigger (Z).
+    pub fn map_nunchuk(
+        &mut self,
+        nunchuk: &Nunchuk,
+        cal: &NunchukCalibration,
+    ) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+
+        let (c, z) = self.nunchuk_buttons;
+        push_button(&mut events, Self::NUNCHUK[0], c, nunchuk.c);
+        push_button(&mut events, Self::NUNCHUK[1], z, nunchuk.z);
+        self.nunchuk_buttons = (nunchuk.c, nunchuk.z);
+
+        let (x, y) = nunchuk.stick_normalized(cal);
+        self.update_axis(GamepadAxis::LeftStickX, x, &mut events);
+        self.update_axis(GamepadAxis::LeftStickY, y, &mut events);
+
+        events
+    }
+
+    /// Convert the state of a Classic Controller, using the positions of its
+    /// buttons (so B is south and A is east).
+    pub fn map_classic(&mut self, classic: &ClassicController) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+
+        for (classic_button, button) in Self::CLASSIC {
+            push_button(
+                &mut events,
+                button,
+                self.classic_buttons.contains(classic_button),
+                classic.buttons.contains(classic_button),
+            );
+        }
+        self.classic_buttons = classic.buttons;
+
+        let stick = |raw: u8| ((raw as f32 - 128.0) / 128.0).clamp(-1.0, 1.0);
+        let trigger = |raw: u8| raw as f32 / 255.0;
+        let axes = [
+            (GamepadAxis::LeftStickX, stick(classic.left_stick.0)),
+            (GamepadAxis::LeftStickY, stick(classic.left_stick.1)),
+            (GamepadAxis::RightStickX, stick(classic.right_stick.0)),
+            (GamepadAxis::RightStickY, stick(classic.right_stick.1)),
+            (GamepadAxis::LeftZ, trigger(classic.left_trigger)),
+            (GamepadAxis::RightZ, trigger(classic.right_trigger)),
+        ];
+        for (axis, value) in axes {
+            self.update_axis(axis, value, &mut events);
+        }
+
+        events
+    }
+
+    fn update_core(&mut self, buttons: CoreButtons, events: &mut Vec<GamepadEvent>) {
+        for &(core_button, button) in &self.core {
+            push_button(
+                events,
+                button,
+                self.core_buttons.contains(core_button),
+                buttons.contains(core_button),
+            );
+        }
+        self.core_buttons = buttons;
+    }
+
+    fn update_axis(&mut self, axis: GamepadAxis, value: f32, events: &mut Vec<GamepadEvent>) {
+        let last = &mut self.axes[axis as usize];
+        if *last != value {
+            *last = value;
+            events.push(GamepadEvent::AxisChanged(axis, value));
+        }
+    }
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        Self::upright()
+    }
+}
+
+fn push_button(events: &mut Vec<GamepadEvent>, button: GamepadButton, was: bool, is: bool) {
+    match (was, is) {
+        (false, true) => events.push(GamepadEvent::ButtonPressed(button)),
+        (true, false) => events.push(GamepadEvent::ButtonReleased(button)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buttons::Button;
+    use crate::extension::{ClassicFormat, ClassicKind};
+
+    use GamepadEvent::*;
+
+    fn centered_classic() -> ClassicController {
+        ClassicController {
+            kind: ClassicKind::Classic,
+            format: ClassicFormat::Standard,
+            left_stick: (128, 128),
+            right_stick: (128, 128),
+            left_trigger: 0,
+            right_trigger: 0,
+            buttons: ClassicButtons::empty(),
+        }
+    }
+
+    #[test]
+    fn upright_buttons_are_pressed_and_released() {
+        let mut mapping = GamepadMapping::upright();
+
+        let pressed = CoreButtons::A | CoreButtons::UP;
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::ButtonsChanged(pressed)),
+            [
+                ButtonPressed(GamepadButton::South),
+                ButtonPressed(GamepadButton::DPadUp),
+            ]
+        );
+        // Resending the same buttons changes nothing
+        assert_eq!(mapping.map_event(&WiimoteEvent::ButtonsResent(pressed)), []);
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::ButtonsChanged(CoreButtons::UP)),
+            [ButtonReleased(GamepadButton::South)]
+        );
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::ButtonUp(Button::Up)),
+            [ButtonReleased(GamepadButton::DPadUp)]
+        );
+    }
+
+    #[test]
+    fn sideways_rotates_the_dpad() {
+        let mut mapping = GamepadMapping::sideways();
+
+        let pressed = CoreButtons::TWO | CoreButtons::RIGHT;
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::ButtonsChanged(pressed)),
+            [
+                ButtonPressed(GamepadButton::South),
+                ButtonPressed(GamepadButton::DPadUp),
+            ]
+        );
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::ButtonsChanged(CoreButtons::UP)),
+            [
+                ButtonReleased(GamepadButton::South),
+                ButtonPressed(GamepadButton::DPadLeft),
+                ButtonReleased(GamepadButton::DPadUp),
+            ]
+        );
+    }
+
+    #[test]
+    fn disconnecting_releases_held_buttons() {
+        let mut mapping = GamepadMapping::upright();
+        mapping.map_event(&WiimoteEvent::ButtonsChanged(CoreButtons::B));
+
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::Disconnected),
+            [ButtonReleased(GamepadButton::RightTrigger), Disconnected]
+        );
+    }
+
+    #[test]
+    fn classic_axes_only_change_when_they_move() {
+        let mut mapping = GamepadMapping::default();
+        let mut classic = centered_classic();
+        classic.buttons = ClassicButtons::A;
+
+        // Centered sticks and released triggers are where the axes start
+        assert_eq!(
+            mapping.map_classic(&classic),
+            [ButtonPressed(GamepadButton::East)]
+        );
+
+        classic.left_stick.0 = 255;
+        classic.right_trigger = 255;
+        assert_eq!(
+            mapping.map_event(&WiimoteEvent::Classic(classic)),
+            [
+                AxisChanged(GamepadAxis::LeftStickX, 127.0 / 128.0),
+                AxisChanged(GamepadAxis::RightZ, 1.0),
+            ]
+        );
+        assert_eq!(mapping.map_classic(&classic), []);
+
+        classic.left_stick.0 = 128;
+        classic.buttons = ClassicButtons::empty();
+        assert_eq!(
+            mapping.map_classic(&classic),
+            [
+                ButtonReleased(GamepadButton::East),
+                AxisChanged(GamepadAxis::LeftStickX, 0.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+use crate::motion::Vec3;
+
+/// A gesture made by moving the whole Wiimote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Gesture {
+    /// The Wiimote was shaken for a while.
+    Shake,
+    /// The Wiimote was tapped or flicked once.
+    Tap,
+}
+
+/// Settings for a [`GestureDetector`].
+///
+/// The thresholds are how far the magnitude of the acceleration is from 1 G,
+/// so that they don't depend on how the Wiimote is being held.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureConfig {
+    /// How strong the acceleration has to be to count as shaking, in G.
+    pub shake_threshold: f32,
+    /// How long the acceleration has to stay above `shake_threshold` before
+    /// [`Gesture::Shake`] is emitted.
+    pub shake_duration: Duration,
+    /// How strong a spike in acceleration has to be to count as a tap, in G.
+    pub tap_threshold: f32,
+    /// How long a spike can last and still count as a tap.
+    pub tap_max_duration: Duration,
+    /// How long to wait after a gesture before another one can be emitted.
+    pub debounce: Duration,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            shake_threshold: 1.0,
+            shake_duration: Duration::from_millis(300),
+            tap_threshold: 2.0,
+            tap_max_duration: Duration::from_millis(60),
+            debounce: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Detects [`Gesture`]s from calibrated accelerometer samples.
+#[derive(Debug, Clone)]
+pub struct GestureDetector {
+    config: GestureConfig,
+    // When the acceleration went above each threshold, if it still is
+    shake_start: Option<Instant>,
+    spike_start: Option<Instant>,
+    // Whether a shake was emitted since the acceleration went above the
+    // shake threshold, so that a shake isn't also counted as a tap
+    shook: bool,
+    last_gesture: Option<Instant>,
+}
+
+impl GestureDetector {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            shake_start: None,
+            spike_start: None,
+            shook: false,
+            last_gesture: None,
+        }
+    }
+
+    pub fn config(&self) -> &GestureConfig {
+        &self.config
+    }
+
+    /// Feed a calibrated acceleration (in G) that was measured at `now`.
+    ///
+    /// Returns the gesture that this sample completes, if there is one.
+    /// Samples should be fed in the order they were measured.
+    pub fn update(&mut self, accel: Vec3, now: Instant) -> Option<Gesture> {
+        let strength = (accel.length() - 1.0).abs();
+        let mut gesture = None;
+
+        if strength > self.config.tap_threshold {
+            self.spike_start.get_or_insert(now);
+        } else if let Some(spike_start) = self.spike_start.take() {
+            // A tap is a short spike that isn't part of a shake
+            if !self.shook && now - spike_start <= self.config.tap_max_duration {
+                gesture = Some(Gesture::Tap);
+            }
+        }
+
+        if strength > self.config.shake_threshold {
+            let shake_start = *self.shake_start.get_or_insert(now);
+            if !self.shook && now - shake_start >= self.config.shake_duration {
+                gesture = Some(Gesture::Shake);
+            }
+        } else {
+            self.shake_start = None;
+            self.shook = false;
+        }
+
+        // Don't emit gestures too close together. A shake that is held back
+        // is emitted later on if the shaking keeps going.
+        if let Some(last_gesture) = self.last_gesture {
+            if now - last_gesture < self.config.debounce {
+                return None;
+            }
+        }
+        if gesture.is_some() {
+            self.shook |= gesture == Some(Gesture::Shake);
+            self.last_gesture = Some(now);
+        }
+
+        gesture
+    }
+
+    /// Forget any partially detected gestures.
+    pub fn reset(&mut self) {
+        self.shake_start = None;
+        self.spike_start = None;
+        self.shook = false;
+        self.last_gesture = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+    // Feed `trace` to a detector one sample every 10ms, returning the
+    // gestures with the index of the sample that completed them
+    fn detect(trace: &[Vec3]) -> Vec<(usize, Gesture)> {
+        let mut detector = GestureDetector::new(GestureConfig::default());
+        let start = Instant::now();
+
+        trace
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &accel)| {
+                let now = start + SAMPLE_INTERVAL * i as u32;
+                detector.update(accel, now).map(|gesture| (i, gesture))
+            })
+            .collect()
+    }
+
+    const REST: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    #[test]
+    fn shake_is_emitted_once_it_has_lasted() {
+        let mut trace = vec![REST; 5];
+        // Shaking left and right for half a second
+        for i in 0..50 {
+            let x = if i % 2 == 0 { 2.5 } else { -2.5 };
+            trace.push(Vec3::new(x, 0.0, 1.0));
+        }
+        trace.extend([REST; 5]);
+
+        // 300ms after the shaking started, and only once
+        assert_eq!(detect(&trace), [(35, Gesture::Shake)]);
+    }
+
+    #[test]
+    fn tap_is_a_single_spike() {
+        let mut trace = vec![REST; 5];
+        trace.push(Vec3::new(0.0, 0.0, 4.0));
+        trace.extend([REST; 5]);
+
+        // Emitted once the spike is over
+        assert_eq!(detect(&trace), [(6, Gesture::Tap)]);
+    }
+
+    #[test]
+    fn shake_right_after_a_tap_is_held_back_until_the_debounce() {
+        // A tap while shaking gently, before the shake has lasted long enough
+        let shaking = |i: i32| {
+            let x = if i % 2 == 0 { 2.0 } else { -2.0 };
+            Vec3::new(x, 0.0, 1.0)
+        };
+        let mut trace = vec![REST; 5];
+        trace.extend((0..23).map(shaking));
+        trace.push(Vec3::new(0.0, 0.0, 4.0));
+        trace.extend((0..60).map(shaking));
+        trace.extend([REST; 5]);
+
+        // The shake lasted long enough at sample 35, but is only emitted once
+        // 250ms have passed since the tap
+        assert_eq!(detect(&trace), [(29, Gesture::Tap), (54, Gesture::Shake)]);
+    }
+
+    #[test]
+    fn long_spikes_and_quick_repeats_are_ignored() {
+        // A spike that lasts too long to be a tap
+        let mut trace = vec![REST; 5];
+        trace.extend([Vec3::new(0.0, 0.0, 4.0); 10]);
+        trace.extend([REST; 30]);
+        // Two taps 100ms apart, within the debounce time
+        trace.push(Vec3::new(0.0, 0.0, 4.0));
+        trace.extend([REST; 9]);
+        trace.push(Vec3::new(0.0, 0.0, 4.0));
+        trace.extend([REST; 5]);
+
+        assert_eq!(detect(&trace), [(46, Gesture::Tap)]);
+    }
+}
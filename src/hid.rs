@@ -8,31 +8,72 @@ use windows::{
     },
 };
 
-use arrayvec::ArrayVec;
 use thiserror::Error;
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::ffi::CString;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::mem::{self, MaybeUninit};
 use std::ptr;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::bluetooth::Address;
 use crate::util;
 
 // TODO: Add SAFETY comments
 // TODO: Box<str>?
 // TODO: io::Error::last_os_error()
 
-const WIIMOTE_READ_TIMEOUT: Duration = Duration::from_millis(200);
+pub(crate) const WIIMOTE_READ_TIMEOUT: Duration = Duration::from_millis(200);
 const WIIMOTE_WRITE_TIMEOUT: Duration = Duration::from_millis(1000);
+// How long to wait for cancelled operations to finish before closing the handle
+const CANCEL_TIMEOUT: Duration = Duration::from_millis(50);
+// How long to wait for a cancelled write to finish. Its buffer can't be freed
+// until then, so this waits for as long as it takes (`INFINITE`).
+const CANCELLED_WRITE_TIMEOUT: Duration = Duration::MAX;
+// How long a `MockDevice` takes for a read to time out
+const MOCK_READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+pub use crate::protocol::{Report, INPUT_REPORT, MAX_REPORT_LENGTH, OUTPUT_REPORT};
+
+/// Formats a raw report as a hex dump, labelled with its direction and report
+/// ID, e.g. `Input 0x30: 00 08`.
+///
+/// `{:?}` on a [`Report`] just prints a list of decimal bytes, which is hard to
+/// read in logs.
+pub struct ReportDump<'a>(pub &'a [u8]);
+
+impl fmt::Debug for ReportDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = match self.0 {
+            [] => return write!(f, "Empty report"),
+            [INPUT_REPORT, id, data @ ..] => {
+                write!(f, "Input {id:#04x}:")?;
+                data
+            }
+            [OUTPUT_REPORT, id, data @ ..] => {
+                write!(f, "Output {id:#04x}:")?;
+                data
+            }
+            data => {
+                write!(f, "Unknown report:")?;
+                data
+            }
+        };
 
-pub const INPUT_REPORT: u8 = 0xa1;
-pub const OUTPUT_REPORT: u8 = 0xa2;
-
-// NOTE: This size includes the HID header
-pub const MAX_REPORT_LENGTH: usize = 23;
+        for byte in data {
+            write!(f, " {byte:02x}")?;
+        }
 
-pub type Report = ArrayVec<u8, MAX_REPORT_LENGTH>;
+        Ok(())
+    }
+}
 
 #[derive(Debug, PartialEq, Error)]
 pub enum Error {
@@ -41,6 +82,16 @@ pub enum Error {
     #[error("The device is not connected")]
     // XXX: Check for this in From impl
     NotConnected,
+    #[error(
+        "The device isn't a compatible Wiimote: its input and output reports are \
+         {input_report_length} and {output_report_length} bytes, instead of \
+         {expected}",
+        expected = Capabilities::WIIMOTE_REPORT_LENGTH
+    )]
+    UnexpectedCapabilities {
+        input_report_length: u16,
+        output_report_length: u16,
+    },
     #[error("A Windows error occured: {0:?}")]
     Windows(#[from] windows::core::Error),
 }
@@ -50,7 +101,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 struct Overlapped(OVERLAPPED);
 
 impl Overlapped {
-    pub fn new() -> io::Result<Self> {
+    pub fn new() -> Result<Self> {
         Ok(Self(OVERLAPPED {
             // XXX: Change manual reset to false?
             hEvent: unsafe { CreateEventA(ptr::null_mut(), true, false, None)? },
@@ -89,12 +140,14 @@ impl Overlapped {
         timeout: Duration,
     ) -> windows::core::Result<Option<usize>> {
         let mut bytes_transferred: u32 = 0;
+        // Saturate to `INFINITE` rather than wrapping around
+        let timeout_ms = timeout.as_millis().min(u32::MAX as u128) as u32;
         let res = unsafe {
             GetOverlappedResultEx(
                 handle,
                 self.raw(),
                 &mut bytes_transferred,
-                timeout.as_millis() as u32,
+                timeout_ms,
                 false,
             )
         };
@@ -123,36 +176,193 @@ impl Drop for Overlapped {
 unsafe impl Send for Overlapped {}
 unsafe impl Sync for Overlapped {}
 
+/// The parts of a HID device's capabilities that we care about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub usage: u16,
+    pub usage_page: u16,
+    /// The length of input reports, including the report ID.
+    pub input_report_length: u16,
+    /// The length of output reports, including the report ID.
+    pub output_report_length: u16,
+    /// The length of feature reports, including the report ID.
+    pub feature_report_length: u16,
+}
+
+impl Capabilities {
+    /// The length of a Wiimote's input and output reports, including the
+    /// report ID.
+    pub const WIIMOTE_REPORT_LENGTH: u16 = MAX_REPORT_LENGTH as u16 - 1;
+
+    /// Check that the device's reports are the same length as a Wiimote's.
+    ///
+    /// Some clones of the Wiimote use its VID and PID, but don't use the same
+    /// report lengths, so reading and writing them fails in confusing ways.
+    pub fn check_wiimote(&self) -> Result<()> {
+        if self.input_report_length != Self::WIIMOTE_REPORT_LENGTH
+            || self.output_report_length != Self::WIIMOTE_REPORT_LENGTH
+        {
+            return Err(Error::UnexpectedCapabilities {
+                input_report_length: self.input_report_length,
+                output_report_length: self.output_report_length,
+            });
+        }
+
+        Ok(())
+    }
+}
+
 pub struct Device {
     path: String,
     read_ol: Overlapped,
     write_ol: Overlapped,
     handle: HANDLE,
+    caps: Capabilities,
 }
 
 impl Device {
+    /// Open the device, allowing other processes to open it at the same time.
     pub fn open(path: &str) -> io::Result<Self> {
+        Self::open_with_share_mode(path, FILE_SHARE_READ | FILE_SHARE_WRITE)
+    }
+
+    /// Open the device, preventing any other process from opening it until it
+    /// is closed.
+    ///
+    /// This fails with [`io::ErrorKind::PermissionDenied`] if another process
+    /// already has the device open.
+    pub fn open_exclusive(path: &str) -> io::Result<Self> {
+        Self::open_with_share_mode(path, FILE_SHARE_NONE)
+    }
+
+    /// Open a device with `open`, e.g. `|| Device::open(path)`, trying up to
+    /// `attempts` times with `delay` in between if it fails in a way that
+    /// might be temporary.
+    ///
+    /// Freshly paired Wiimotes often can't be opened for a moment, e.g.
+    /// because their HID device hasn't finished being set up. This blocks
+    /// while waiting to try again, so only [`Wiimote::connect_by_path`] uses
+    /// it; the [`WiimoteManager`] tries again on its next update instead.
+    ///
+    /// [`Wiimote::connect_by_path`]: crate::wiimote::Wiimote::connect_by_path
+    ///
+    /// [`WiimoteManager`]: crate::manager::WiimoteManager
+    pub fn open_with_retry<T>(
+        mut open: impl FnMut() -> io::Result<T>,
+        attempts: u32,
+        delay: Duration,
+    ) -> io::Result<T> {
+        let mut attempt = 1;
+        loop {
+            match open() {
+                Err(e) if attempt < attempts && Self::is_recoverable_open_error(&e) => {
+                    println!("[HID] Failed to open device (attempt {attempt}/{attempts}): {e}");
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Whether an error from opening a device might go away by itself.
+    pub(crate) fn is_recoverable_open_error(e: &io::Error) -> bool {
+        const RECOVERABLE: [WIN32_ERROR; 4] = [
+            ERROR_FILE_NOT_FOUND,
+            ERROR_ACCESS_DENIED,
+            ERROR_GEN_FAILURE,
+            ERROR_DEVICE_NOT_CONNECTED,
+        ];
+
+        // Sharing violations are turned into `PermissionDenied`, and the
+        // other Windows errors keep their HRESULT
+        e.kind() == io::ErrorKind::PermissionDenied
+            || RECOVERABLE
+                .iter()
+                .any(|code| e.raw_os_error() == Some(code.to_hresult().0))
+    }
+
+    fn open_with_share_mode(path: &str, share_mode: FILE_SHARE_MODE) -> io::Result<Self> {
+        // Create the events before the device handle, so that if this fails
+        // there is no handle to leak. The events close themselves on drop.
+        let read_ol = Overlapped::new()?;
+        let write_ol = Overlapped::new()?;
+
         // Open a read/write handle to our device
         let handle = unsafe {
             CreateFileA(
                 path,
                 FILE_GENERIC_READ | FILE_GENERIC_WRITE,
-                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                share_mode,
                 ptr::null_mut(),
                 OPEN_EXISTING,
                 FILE_FLAG_OVERLAPPED,
                 None,
-            )?
+            )
+        };
+        let handle = match handle {
+            Ok(handle) => handle,
+            Err(e) if e.code() == ERROR_SHARING_VIOLATION.to_hresult() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "The device is already open in another process",
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let caps = match Self::get_caps(handle) {
+            Ok(caps) => caps,
+            Err(e) => {
+                unsafe {
+                    CloseHandle(handle);
+                }
+                return Err(e);
+            }
         };
 
         Ok(Self {
             path: path.to_string(),
-            read_ol: Overlapped::new()?,
-            write_ol: Overlapped::new()?,
+            read_ol,
+            write_ol,
             handle,
+            caps,
         })
     }
 
+    fn get_caps(handle: HANDLE) -> io::Result<Capabilities> {
+        unsafe {
+            let mut preparsed_data = 0;
+            // FIXME: Refactor if and when BOOLEAN is nicer
+            if HidD_GetPreparsedData(handle, &mut preparsed_data).0 == 0 {
+                return Err(windows::core::Error::from_win32().into());
+            }
+
+            let mut caps = HIDP_CAPS::default();
+            let res = HidP_GetCaps(preparsed_data, &mut caps);
+            HidD_FreePreparsedData(preparsed_data);
+            res?;
+
+            Ok(Capabilities {
+                usage: caps.Usage,
+                usage_page: caps.UsagePage,
+                input_report_length: caps.InputReportByteLength,
+                output_report_length: caps.OutputReportByteLength,
+                feature_report_length: caps.FeatureReportByteLength,
+            })
+        }
+    }
+
+    /// The capabilities of the device, which are queried when it is opened.
+    pub fn caps(&self) -> &Capabilities {
+        &self.caps
+    }
+
+    /// Get a way to cancel the pending IO of the device from another thread.
+    pub(crate) fn io_canceller(&self) -> IoCanceller {
+        IoCanceller(self.handle)
+    }
+
     pub fn read(&mut self) -> Result<Report> {
         // SAFETY: The buffer is a `MaybeUninit` array so that it may change
         // while the read operation is ongoing. We zero the buffer instead of
@@ -213,10 +423,15 @@ impl Device {
         // SAFETY: The read operation will have completed by this point, so the
         // values of the bytes in the buffer will be fixed. Therefore the buffer
         // is initialized and we can transmute to the initialized type.
-        let buf = unsafe { mem::transmute::<_, [u8; MAX_REPORT_LENGTH]>(buf) };
+        let buf = unsafe {
+            mem::transmute::<[MaybeUninit<u8>; MAX_REPORT_LENGTH], [u8; MAX_REPORT_LENGTH]>(buf)
+        };
 
         let mut report = Report::from(buf);
         if bytes_read > 0 {
+            // The driver shouldn't report reading more than we asked for, but
+            // don't trust it
+            let bytes_read = bytes_read.min(MAX_REPORT_LENGTH - 1);
             // TODO: Actually figure out the report size
             // The length of the full report includes the data report indicator byte
             report.truncate(bytes_read + 1);
@@ -231,11 +446,18 @@ impl Device {
     // XXX: If we write do we need to cancel the current read?
     // TODO: Change slice to Report parameter?
     pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        // Ignore the data report indicator byte, and pad the report to the
+        // length the HID stack expects since some drivers reject anything
+        // shorter. The padded buffer has to outlive the write operation, so it
+        // isn't dropped until the write has completed or been cancelled.
+        let mut buf = buf[1..].to_vec();
+        let output_report_length = self.caps.output_report_length as usize;
+        if buf.len() < output_report_length {
+            buf.resize(output_report_length, 0);
+        }
+
         // Start the write operation
         let res: Result<()> = {
-            // Ignore the data report indicator byte
-            let buf = &buf[1..];
-
             self.write_ol.reset_event();
             let write_res = unsafe {
                 WriteFile(
@@ -257,6 +479,8 @@ impl Device {
             res.map_err(Error::Windows)
         };
 
+        let is_pending = res.is_ok();
+
         // Wait until the write operation completes/times out
         let res: Result<usize> = res.and_then(|_| {
             match self
@@ -268,18 +492,57 @@ impl Device {
             }
         });
 
-        // If there were any errors, cancel the pending operation
+        // If there were any errors, cancel the pending operation, and wait
+        // for the cancellation to finish before `buf` is freed
         if res.is_err() {
             self.cancel_io();
+            if is_pending {
+                let _ = self
+                    .write_ol
+                    .get_overlapped_result_ex(self.handle, CANCELLED_WRITE_TIMEOUT);
+            }
         }
 
         res
     }
 
-    // NOTE: This will only cancel IO operations issued by the calling thread
+    /// Cancel all pending IO operations on the device, regardless of which
+    /// thread issued them.
     fn cancel_io(&mut self) {
         unsafe {
-            CancelIo(self.handle);
+            CancelIoEx(self.handle, ptr::null());
+        }
+    }
+
+    /// Get a feature report from the device, bypassing the usual input reports.
+    ///
+    /// `len` is the length of the report including the report ID, and the
+    /// returned buffer starts with the report ID.
+    pub fn get_feature_report(&self, report_id: u8, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len.max(1)];
+        buf[0] = report_id;
+
+        unsafe {
+            // FIXME: Refactor if and when BOOLEAN is nicer
+            if HidD_GetFeature(self.handle, buf.as_mut_ptr().cast(), buf.len() as u32).0 != 0 {
+                Ok(buf)
+            } else {
+                Err(windows::core::Error::from_win32().into())
+            }
+        }
+    }
+
+    /// Send a feature report to the device, bypassing the usual output reports.
+    ///
+    /// `data` must start with the report ID.
+    pub fn set_feature_report(&self, data: &[u8]) -> Result<()> {
+        unsafe {
+            // FIXME: Refactor if and when BOOLEAN is nicer
+            if HidD_SetFeature(self.handle, data.as_ptr().cast(), data.len() as u32).0 != 0 {
+                Ok(())
+            } else {
+                Err(windows::core::Error::from_win32().into())
+            }
         }
     }
 
@@ -314,20 +577,52 @@ impl Device {
         }
     }
 
-    fn get_info(&self) -> Option<DeviceInfo> {
-        self.get_attributes()
-            .zip(self.get_product_string())
-            .map(|((vid, pid), ps)| DeviceInfo {
-                path: self.path.clone(),
-                vendor_id: vid,
-                product_id: pid,
-                product_string: ps,
-            })
+    fn get_serial_number_string(&self) -> Option<String> {
+        let mut buf: [u16; 128] = [0; 128];
+
+        unsafe {
+            // FIXME: Refactor if and when BOOLEAN is nicer
+            if HidD_GetSerialNumberString(self.handle, buf.as_mut_ptr().cast(), buf.len() as u32).0
+                != 0
+            {
+                Some(util::wstring_to_utf8(&buf))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Get the identifying information of the device, if it is available.
+    ///
+    /// Only the VID and PID are required, as reading the strings can fail
+    /// transiently.
+    pub fn get_info(&self) -> Option<DeviceInfo> {
+        self.get_attributes().map(|(vid, pid)| DeviceInfo {
+            path: self.path.clone(),
+            vendor_id: vid,
+            product_id: pid,
+            product_string: self.get_product_string(),
+            serial_number: self.get_serial_number_string(),
+            usage_page: self.caps.usage_page,
+            usage: self.caps.usage,
+        })
     }
 }
 
 impl Drop for Device {
     fn drop(&mut self) {
+        // The OVERLAPPED structures are freed along with the device, so any
+        // in-flight operations have to be cancelled and given a chance to
+        // complete before the handle is closed.
+        self.cancel_io();
+        // We don't care whether the operations completed or were aborted
+        let _ = self
+            .read_ol
+            .get_overlapped_result_ex(self.handle, CANCEL_TIMEOUT);
+        let _ = self
+            .write_ol
+            .get_overlapped_result_ex(self.handle, CANCEL_TIMEOUT);
+
         unsafe {
             CloseHandle(self.handle);
         }
@@ -335,23 +630,539 @@ impl Drop for Device {
     }
 }
 
+/// Cancels the pending IO of a [`Device`] from another thread, e.g. to unstick
+/// a read that never completes.
+///
+/// This refers to the device's handle without owning it, so it must not be
+/// used after the device is dropped.
+#[derive(Debug)]
+pub(crate) struct IoCanceller(HANDLE);
+
+impl IoCanceller {
+    pub(crate) fn cancel(&self) {
+        unsafe {
+            CancelIoEx(self.0, ptr::null());
+        }
+    }
+}
+
+unsafe impl Send for IoCanceller {}
+
+/// A HID device that reports can be read from and written to.
+///
+/// This is implemented by [`Device`], and by [`MockDevice`] so that code which
+/// talks to a Wiimote can be run without one.
+pub trait HidDevice {
+    /// Read an input report, returning an empty report if the read timed out.
+    fn read(&mut self) -> Result<Report>;
+    /// Write an output report, returning the number of bytes written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+}
+
+impl HidDevice for Device {
+    fn read(&mut self) -> Result<Report> {
+        Device::read(self)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Device::write(self, buf)
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    reads: VecDeque<Report>,
+    writes: Vec<Report>,
+    time_out_when_empty: bool,
+}
+
+/// A fake [`HidDevice`] that returns scripted reports and records writes.
+///
+/// Clones of a `MockDevice` share the same state, so a clone can be kept to
+/// inspect the writes after the device has been moved into a poll thread.
+/// Once every scripted report has been read, reads fail with
+/// [`Error::NotConnected`], unless [`MockDevice::timing_out`] is used.
+#[derive(Debug, Clone, Default)]
+pub struct MockDevice {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockDevice {
+    pub fn new(reads: impl IntoIterator<Item = Report>) -> Self {
+        let state = MockState {
+            reads: reads.into_iter().collect(),
+            writes: Vec::new(),
+            time_out_when_empty: false,
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Return empty reports once the reads run out, as if every read timed
+    /// out, instead of failing as if the device had disconnected.
+    ///
+    /// This keeps a poll thread running for as long as it is needed.
+    pub fn timing_out(self) -> Self {
+        self.state.lock().unwrap().time_out_when_empty = true;
+        self
+    }
+
+    /// Add a report to the end of the reports to be read.
+    pub fn push_read(&self, report: Report) {
+        self.state.lock().unwrap().reads.push_back(report);
+    }
+
+    /// The reports that have been written to the device so far.
+    pub fn writes(&self) -> Vec<Report> {
+        self.state.lock().unwrap().writes.clone()
+    }
+}
+
+impl HidDevice for MockDevice {
+    fn read(&mut self) -> Result<Report> {
+        let mut state = self.state.lock().unwrap();
+        match state.reads.pop_front() {
+            Some(report) => Ok(report),
+            None if state.time_out_when_empty => {
+                drop(state);
+                // Don't spin while nothing is being read
+                thread::sleep(MOCK_READ_TIMEOUT);
+                Ok(Report::new())
+            }
+            None => Err(Error::NotConnected),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let report = Report::try_from(buf).expect("Output report is too long");
+        self.state.lock().unwrap().writes.push(report);
+
+        Ok(buf.len())
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::WriteTimedOut => io::Error::new(io::ErrorKind::TimedOut, e),
+            Error::NotConnected => io::Error::new(io::ErrorKind::NotConnected, e),
+            Error::UnexpectedCapabilities { .. } => io::Error::new(io::ErrorKind::InvalidData, e),
+            // Keep the error code, e.g. for `io::Error::raw_os_error`
+            Error::Windows(e) => e.into(),
+        }
+    }
+}
+
+/// Wraps a [`HidDevice`] so that writes are at least `min_interval` apart,
+/// sleeping before a write if needed.
+///
+/// The Wiimote only has a small buffer for output reports, so reports written
+/// back-to-back can be dropped, e.g. an LED report straight after a report
+/// mode report.
+#[derive(Debug)]
+pub struct PacedDevice<D: HidDevice> {
+    device: D,
+    min_interval: Duration,
+    last_write: Option<Instant>,
+}
+
+impl<D: HidDevice> PacedDevice<D> {
+    pub fn new(device: D, min_interval: Duration) -> Self {
+        Self {
+            device,
+            min_interval,
+            last_write: None,
+        }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: HidDevice> HidDevice for PacedDevice<D> {
+    fn read(&mut self) -> Result<Report> {
+        self.device.read()
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if let Some(last_write) = self.last_write {
+            if let Some(remaining) = self.min_interval.checked_sub(last_write.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        let result = self.device.write(buf);
+        self.last_write = Some(Instant::now());
+
+        result
+    }
+}
+
+/// The most recent reports read from a device, up to a fixed capacity, e.g.
+/// for dumping after something went wrong.
+#[derive(Debug, Clone, Default)]
+pub struct ReportRing {
+    reports: VecDeque<Report>,
+    capacity: usize,
+}
+
+impl ReportRing {
+    /// Create a ring that keeps the last `capacity` reports, or none at all if
+    /// `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            reports: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Add a report, dropping the oldest one if the ring is full.
+    pub fn push(&mut self, report: Report) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.reports.len() == self.capacity {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+    }
+
+    /// The reports in the ring, oldest first.
+    pub fn to_vec(&self) -> Vec<Report> {
+        self.reports.iter().cloned().collect()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+}
+
+/// Wraps a [`HidDevice`] so that every report read from it is also kept in a
+/// shared [`ReportRing`].
+#[derive(Debug)]
+pub struct RingDevice<D: HidDevice> {
+    device: D,
+    ring: Arc<Mutex<ReportRing>>,
+}
+
+impl<D: HidDevice> RingDevice<D> {
+    pub fn new(device: D, ring: Arc<Mutex<ReportRing>>) -> Self {
+        Self { device, ring }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: HidDevice> HidDevice for RingDevice<D> {
+    /// Read a report, keeping it in the ring unless the read timed out.
+    fn read(&mut self) -> Result<Report> {
+        let report = self.device.read()?;
+        if !report.is_empty() {
+            self.ring.lock().unwrap().push(report.clone());
+        }
+
+        Ok(report)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.device.write(buf)
+    }
+}
+
+/// Wraps a [`HidDevice`] so that it can be used as an [`io::Read`] and
+/// [`io::Write`], e.g. to pipe the raw reports into generic logging tools.
+///
+/// Every read and write is one whole report, starting with the HID header
+/// ([`INPUT_REPORT`] or [`OUTPUT_REPORT`]) and then the report ID. The typed
+/// [`HidDevice`] methods should be preferred otherwise.
+#[derive(Debug)]
+pub struct IoAdapter<D: HidDevice>(pub D);
+
+impl<D: HidDevice> io::Read for IoAdapter<D> {
+    /// Read one input report into `buf`.
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] if no report arrived in time,
+    /// rather than returning 0 (which would mean the end of the stream), and
+    /// with [`io::ErrorKind::InvalidInput`] if the report doesn't fit in
+    /// `buf`. A buffer of [`MAX_REPORT_LENGTH`] bytes is always big enough.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let report = self.0.read()?;
+        if report.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for an input report",
+            ));
+        }
+
+        let dest = buf.get_mut(..report.len()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The buffer is too small for the input report",
+            )
+        })?;
+        dest.copy_from_slice(&report);
+
+        Ok(report.len())
+    }
+}
+
+impl<D: HidDevice> io::Write for IoAdapter<D> {
+    /// Write `buf` as one output report.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.first() != Some(&OUTPUT_REPORT) || buf.len() > MAX_REPORT_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Expected a whole output report",
+            ));
+        }
+
+        Ok(self.0.write(buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Reports are written straight away
+        Ok(())
+    }
+}
+
+/// Information about a HID device.
+///
+/// Two `DeviceInfo`s are equal if they have the same path, since the path
+/// identifies the device for as long as it is plugged in (or paired), even if
+/// e.g. its product string couldn't be read the second time. They are also
+/// hashed and ordered by their path, so that a list of devices is always
+/// sorted the same way.
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
     // TODO: DevicePath wrapper type?
     pub path: String,
     pub vendor_id: u16,
     pub product_id: u16,
-    pub product_string: String,
+    /// The product string, or `None` if it couldn't be read.
+    pub product_string: Option<String>,
+    pub serial_number: Option<String>,
+    /// The usage page of the device's top-level collection.
+    pub usage_page: u16,
+    /// The usage of the device's top-level collection.
+    pub usage: u16,
+}
+
+impl PartialEq for DeviceInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for DeviceInfo {}
+
+impl Hash for DeviceInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+impl PartialOrd for DeviceInfo {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DeviceInfo {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.path.cmp(&other.path)
+    }
 }
 
 impl DeviceInfo {
+    /// The Bluetooth address of the device.
+    ///
+    /// Windows reports the address of a Bluetooth HID device as its serial
+    /// number (e.g. `"0017ab123456"`), so this will be `None` for devices that
+    /// aren't connected over Bluetooth.
+    pub fn address(&self) -> Option<Address> {
+        let serial_number = self.serial_number.as_deref()?;
+        if serial_number.len() != 12 {
+            return None;
+        }
+
+        // The serial number starts with the most significant byte
+        let mut bytes = [0u8; 6];
+        for (i, byte) in bytes.iter_mut().rev().enumerate() {
+            *byte = u8::from_str_radix(serial_number.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+
+        Some(Address::from_bytes(bytes))
+    }
+
     pub fn is_wiimote(&self) -> bool {
-        (self.vendor_id == 0x057e && (self.product_id == 0x0306 || self.product_id == 0x0330))
-            // TODO: Is this needed?
-            || util::is_valid_device_name(&self.product_string)
+        self.wiimote_kind().is_some()
     }
+
+    /// Whether this device matches any identifier in `identifiers`.
+    pub fn is_wiimote_in(&self, identifiers: &[WiimoteIdentifier]) -> bool {
+        self.wiimote_kind_in(identifiers).is_some()
+    }
+
+    /// The kind of Wiimote this device is, according to [`KNOWN_WIIMOTES`].
+    pub fn wiimote_kind(&self) -> Option<WiimoteKind> {
+        self.wiimote_kind_in(KNOWN_WIIMOTES)
+    }
+
+    /// The kind of Wiimote this device is, according to the first matching
+    /// identifier in `identifiers`.
+    ///
+    /// This can be used to recognise third-party Wiimotes that aren't in
+    /// [`KNOWN_WIIMOTES`].
+    pub fn wiimote_kind_in(&self, identifiers: &[WiimoteIdentifier]) -> Option<WiimoteKind> {
+        identifiers
+            .iter()
+            .find(|identifier| identifier.matches(self))
+            .map(|identifier| identifier.kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WiimoteKind {
+    /// The original Wiimote (RVL-CNT-01).
+    Original,
+    /// The Wiimote with MotionPlus built in (RVL-CNT-01-TR).
+    MotionPlusInside,
+    /// The Wii U Pro Controller (RVL-CNT-01-UC).
+    ProController,
 }
 
+impl WiimoteKind {
+    /// The kind of Wiimote with this Bluetooth name, or `None` if it isn't
+    /// the name of a Wiimote.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            util::WIIMOTE_NAME => Some(Self::Original),
+            util::WIIMOTE_TR_NAME => Some(Self::MotionPlusInside),
+            util::PRO_CONTROLLER_NAME => Some(Self::ProController),
+            _ => None,
+        }
+    }
+}
+
+/// Identifies a kind of Wiimote from its HID device information.
+///
+/// A device matches if all of the fields that are `Some` match.
+///
+/// The product string of a Wiimote is also its Bluetooth name, so the
+/// [`WiimoteScanner`](crate::scanner::WiimoteScanner) enables the Bluetooth
+/// devices named by the product strings in its identifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WiimoteIdentifier {
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub product_string: Option<Cow<'static, str>>,
+    pub usage_page: Option<u16>,
+    pub usage: Option<u16>,
+    pub kind: WiimoteKind,
+}
+
+impl WiimoteIdentifier {
+    /// Whether the device matches this identifier.
+    ///
+    /// If the identifier has a product string, but the device's product
+    /// string couldn't be read, the device doesn't match.
+    pub fn matches(&self, device_info: &DeviceInfo) -> bool {
+        let product_string = device_info.product_string.as_deref();
+
+        self.vendor_id.unwrap_or(device_info.vendor_id) == device_info.vendor_id
+            && self.product_id.unwrap_or(device_info.product_id) == device_info.product_id
+            && (self.product_string.is_none() || self.product_string.as_deref() == product_string)
+            && self.usage_page.unwrap_or(device_info.usage_page) == device_info.usage_page
+            && self.usage.unwrap_or(device_info.usage) == device_info.usage
+    }
+
+    /// Whether a Bluetooth device with this name matches the identifier's
+    /// product string.
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.product_string.as_deref() == Some(name)
+    }
+}
+
+const NINTENDO_VENDOR_ID: u16 = 0x057e;
+
+/// The identifiers used by [`DeviceInfo::is_wiimote`], in the order they are
+/// checked.
+///
+/// Every entry matches on the VID/PID or the name. The Wiimote's reports are in
+/// the vendor-defined usage page 0xff00, but so are those of many unrelated
+/// devices, which shouldn't be sent output reports.
+///
+/// The Wii U Pro Controller is included on purpose: it speaks the Wiimote's
+/// protocol, with its sticks and buttons reported as an extension, so the
+/// scanner enables and connects it like a Wiimote. To leave it alone, give the
+/// scanner a table without it, with
+/// [`WiimoteScanner::with_identifiers`](crate::scanner::WiimoteScanner::with_identifiers).
+pub const KNOWN_WIIMOTES: &[WiimoteIdentifier] = &[
+    WiimoteIdentifier {
+        vendor_id: Some(NINTENDO_VENDOR_ID),
+        product_id: Some(0x0306),
+        product_string: None,
+        usage_page: None,
+        usage: None,
+        kind: WiimoteKind::Original,
+    },
+    // The Pro Controller shares its PID with the -TR, so check its name first
+    WiimoteIdentifier {
+        vendor_id: Some(NINTENDO_VENDOR_ID),
+        product_id: Some(0x0330),
+        product_string: Some(Cow::Borrowed(util::PRO_CONTROLLER_NAME)),
+        usage_page: None,
+        usage: None,
+        kind: WiimoteKind::ProController,
+    },
+    WiimoteIdentifier {
+        vendor_id: Some(NINTENDO_VENDOR_ID),
+        product_id: Some(0x0330),
+        product_string: None,
+        usage_page: None,
+        usage: None,
+        kind: WiimoteKind::MotionPlusInside,
+    },
+    // Third-party Wiimotes often have a different VID/PID, but the same name
+    WiimoteIdentifier {
+        vendor_id: None,
+        product_id: None,
+        product_string: Some(Cow::Borrowed(util::WIIMOTE_NAME)),
+        usage_page: None,
+        usage: None,
+        kind: WiimoteKind::Original,
+    },
+    WiimoteIdentifier {
+        vendor_id: None,
+        product_id: None,
+        product_string: Some(Cow::Borrowed(util::WIIMOTE_TR_NAME)),
+        usage_page: None,
+        usage: None,
+        kind: WiimoteKind::MotionPlusInside,
+    },
+    WiimoteIdentifier {
+        vendor_id: None,
+        product_id: None,
+        product_string: Some(Cow::Borrowed(util::PRO_CONTROLLER_NAME)),
+        usage_page: None,
+        usage: None,
+        kind: WiimoteKind::ProController,
+    },
+];
+
 pub struct DeviceEnumerator {
     /// The GUID for the HID class.
     guid: GUID,
@@ -362,7 +1173,8 @@ pub struct DeviceEnumerator {
 }
 
 impl DeviceEnumerator {
-    pub fn new() -> Self {
+    /// Get the list of HID devices that are currently present.
+    pub fn new() -> io::Result<Self> {
         let guid = unsafe {
             let mut guid = MaybeUninit::<GUID>::uninit();
             HidD_GetHidGuid(guid.as_mut_ptr());
@@ -371,10 +1183,10 @@ impl DeviceEnumerator {
 
         let h_dev_info = unsafe {
             let flags = DIGCF_DEVICEINTERFACE | DIGCF_PRESENT;
-            SetupDiGetClassDevsA(&guid, None, None, flags).unwrap()
+            SetupDiGetClassDevsA(&guid, None, None, flags)?
         };
 
-        Self { guid, h_dev_info }
+        Ok(Self { guid, h_dev_info })
     }
 
     pub fn devices(&self) -> impl Iterator<Item = DeviceInfo> + '_ {
@@ -512,3 +1324,196 @@ impl<'a> Iterator for DeviceEnumeration<'a> {
 //     let device_enumerator = DeviceEnumerator::new();
 //     device_enumerator.devices()
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device_info(vendor_id: u16, product_id: u16, product_string: &str) -> DeviceInfo {
+        DeviceInfo {
+            path: String::new(),
+            vendor_id,
+            product_id,
+            product_string: Some(product_string.to_string()),
+            serial_number: None,
+            usage_page: 0xff00,
+            usage: 0x0005,
+        }
+    }
+
+    #[test]
+    fn open_is_retried_after_a_recoverable_error() {
+        let mut attempts = 0;
+        let res = Device::open_with_retry(
+            || {
+                attempts += 1;
+                match attempts {
+                    1 => Err(io::Error::from(io::ErrorKind::PermissionDenied)),
+                    _ => Ok(attempts),
+                }
+            },
+            3,
+            Duration::ZERO,
+        );
+        assert_eq!(res.unwrap(), 2);
+
+        let res = Device::open_with_retry(
+            || -> io::Result<()> { Err(io::Error::from(io::ErrorKind::InvalidInput)) },
+            3,
+            Duration::ZERO,
+        );
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn errors_keep_their_kind_as_io_errors() {
+        let kind = |e: Error| io::Error::from(e).kind();
+        assert_eq!(kind(Error::WriteTimedOut), io::ErrorKind::TimedOut);
+        assert_eq!(kind(Error::NotConnected), io::ErrorKind::NotConnected);
+        assert_eq!(
+            kind(Error::UnexpectedCapabilities {
+                input_report_length: 64,
+                output_report_length: 64,
+            }),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn only_some_open_errors_are_recoverable() {
+        let os_error = |code: WIN32_ERROR| io::Error::from_raw_os_error(code.to_hresult().0);
+
+        assert!(Device::is_recoverable_open_error(&os_error(
+            ERROR_DEVICE_NOT_CONNECTED
+        )));
+        assert!(Device::is_recoverable_open_error(&os_error(
+            ERROR_FILE_NOT_FOUND
+        )));
+        assert!(Device::is_recoverable_open_error(&io::Error::from(
+            io::ErrorKind::PermissionDenied
+        )));
+        assert!(!Device::is_recoverable_open_error(&os_error(
+            windows::Win32::Foundation::ERROR_INVALID_PARAMETER
+        )));
+        assert!(!Device::is_recoverable_open_error(&io::Error::from(
+            Error::UnexpectedCapabilities {
+                input_report_length: 64,
+                output_report_length: 64,
+            }
+        )));
+    }
+
+    #[test]
+    fn kind_from_each_name() {
+        let names = [
+            (util::WIIMOTE_NAME, WiimoteKind::Original),
+            (util::WIIMOTE_TR_NAME, WiimoteKind::MotionPlusInside),
+            (util::PRO_CONTROLLER_NAME, WiimoteKind::ProController),
+        ];
+        for (name, kind) in names {
+            assert_eq!(WiimoteKind::from_name(name), Some(kind));
+            assert!(util::is_valid_device_name(name, KNOWN_WIIMOTES));
+        }
+
+        assert_eq!(WiimoteKind::from_name("Nintendo RVL-WBC-01"), None);
+        assert!(!util::is_valid_device_name(
+            "Nintendo RVL-WBC-01",
+            KNOWN_WIIMOTES
+        ));
+    }
+
+    #[test]
+    fn paced_writes_are_spaced_out() {
+        let interval = Duration::from_millis(20);
+        let status: Report = [INPUT_REPORT, 0x20, 0, 0, 0, 0, 0, 0xc8]
+            .into_iter()
+            .collect();
+        let mock = MockDevice::new([status.clone()]);
+        let mut device = PacedDevice::new(mock.clone(), interval);
+
+        let start = Instant::now();
+        device.write(&[OUTPUT_REPORT, 0x11, 0x10]).unwrap();
+        // The first write doesn't have to wait for anything
+        assert!(start.elapsed() < interval);
+
+        let mut last_write = Instant::now();
+        for _ in 0..3 {
+            device.write(&[OUTPUT_REPORT, 0x11, 0x10]).unwrap();
+            assert!(last_write.elapsed() >= interval);
+            last_write = Instant::now();
+        }
+        assert_eq!(mock.writes().len(), 4);
+
+        // Reads aren't held up
+        let start = Instant::now();
+        assert_eq!(device.read().unwrap(), status);
+        assert!(start.elapsed() < interval);
+    }
+
+    #[test]
+    fn ring_keeps_only_the_last_reports() {
+        let reports: Vec<Report> = (0..5)
+            .map(|i| [INPUT_REPORT, 0x30, 0, i].into_iter().collect())
+            .collect();
+
+        let mut ring = ReportRing::new(3);
+        for report in &reports {
+            ring.push(report.clone());
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.capacity(), 3);
+        assert_eq!(ring.to_vec(), reports[2..]);
+
+        let mut off = ReportRing::new(0);
+        off.push(reports[0].clone());
+        assert!(off.is_empty());
+    }
+
+    #[test]
+    fn ring_device_skips_timed_out_reads() {
+        let status: Report = [INPUT_REPORT, 0x20, 0, 0, 0, 0, 0, 0xc8]
+            .into_iter()
+            .collect();
+        let ring = Arc::new(Mutex::new(ReportRing::new(4)));
+        let mock = MockDevice::new([status.clone(), Report::new()]);
+        let mut device = RingDevice::new(mock, Arc::clone(&ring));
+
+        assert_eq!(device.read().unwrap(), status);
+        assert!(device.read().unwrap().is_empty());
+        assert_eq!(ring.lock().unwrap().to_vec(), [status]);
+    }
+
+    #[test]
+    fn pro_controller_is_told_apart_from_the_tr() {
+        let pro = device_info(NINTENDO_VENDOR_ID, 0x0330, util::PRO_CONTROLLER_NAME);
+        let tr = device_info(NINTENDO_VENDOR_ID, 0x0330, util::WIIMOTE_TR_NAME);
+
+        assert_eq!(pro.wiimote_kind(), Some(WiimoteKind::ProController));
+        assert_eq!(tr.wiimote_kind(), Some(WiimoteKind::MotionPlusInside));
+    }
+
+    #[test]
+    fn third_party_wiimotes_match_a_custom_table() {
+        let name = String::from("Generic Remote");
+        let device = device_info(0x1234, 0x5678, &name);
+        assert!(!device.is_wiimote());
+
+        let identifiers = [WiimoteIdentifier {
+            vendor_id: None,
+            product_id: None,
+            product_string: Some(Cow::Owned(name.clone())),
+            usage_page: None,
+            usage: None,
+            kind: WiimoteKind::Original,
+        }];
+        assert_eq!(
+            device.wiimote_kind_in(&identifiers),
+            Some(WiimoteKind::Original)
+        );
+        assert!(util::is_valid_device_name(&name, &identifiers));
+        assert!(!util::is_valid_device_name(
+            util::WIIMOTE_NAME,
+            &identifiers
+        ));
+    }
+}
@@ -0,0 +1,362 @@
+/// The resolution of the IR camera.
+pub const CAMERA_WIDTH: u16 = 1024;
+pub const CAMERA_HEIGHT: u16 = 768;
+
+/// A dot of IR light seen by the Wiimote's camera, such as one end of the
+/// sensor bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrDot {
+    /// The X position in the camera's view, from 0 to 1023.
+    pub x: u16,
+    /// The Y position in the camera's view, from 0 to 767.
+    pub y: u16,
+}
+
+impl IrDot {
+    /// Decode a pair of dots from 5 bytes of IR data in the basic format.
+    ///
+    /// Dots that the camera can't see are `None`.
+    pub fn from_basic(bytes: &[u8; 5]) -> [Option<Self>; 2] {
+        let dot = |x_lo: u8, y_lo: u8, x_hi: u8, y_hi: u8| {
+            let x = (x_hi as u16 & 0b11) << 8 | x_lo as u16;
+            let y = (y_hi as u16 & 0b11) << 8 | y_lo as u16;
+
+            // Missing dots are sent with every bit set
+            (x != 0x3ff || y != 0x3ff).then_some(Self { x, y })
+        };
+
+        [
+            dot(bytes[0], bytes[1], bytes[2] >> 4, bytes[2] >> 6),
+            dot(bytes[3], bytes[4], bytes[2], bytes[2] >> 2),
+        ]
+    }
+}
+
+/// An [`IrDot`] along with the extra information sent in the extended and full
+/// IR formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrDotInfo {
+    pub dot: IrDot,
+    /// How big the dot is, from 0 to 15.
+    pub size: u8,
+    /// The box around the dot, which is only sent in the full format.
+    pub bounding_box: Option<IrBoundingBox>,
+    /// How bright the dot is, which is only sent in the full format.
+    pub intensity: Option<u8>,
+}
+
+/// The box around an IR dot, in the camera's view scaled down to 0..=127 on
+/// both axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrBoundingBox {
+    pub x_min: u8,
+    pub y_min: u8,
+    pub x_max: u8,
+    pub y_max: u8,
+}
+
+impl IrDotInfo {
+    /// Decode all four dots from 12 bytes of IR data in the extended format,
+    /// which is used in [`crate::protocol::ReportMode::CoreAccelIr12`].
+    ///
+    /// Dots that the camera can't see are `None`.
+    pub fn from_extended(bytes: &[u8; 12]) -> [Option<Self>; 4] {
+        let mut dots = [None; 4];
+        for (dot, chunk) in dots.iter_mut().zip(bytes.chunks_exact(3)) {
+            *dot = Self::decode_extended(chunk);
+        }
+
+        dots
+    }
+
+    /// Decode all four dots from 36 bytes of IR data in the full format, which
+    /// is split across the two halves of
+    /// [`crate::protocol::ReportMode::Interleaved`].
+    ///
+    /// Dots that the camera can't see are `None`.
+    pub fn from_full(bytes: &[u8; 36]) -> [Option<Self>; 4] {
+        let mut dots = [None; 4];
+        for (dot, chunk) in dots.iter_mut().zip(bytes.chunks_exact(9)) {
+            // Each dot starts with the same 3 bytes as the extended format
+            *dot = Self::decode_extended(&chunk[..3]).map(|info| Self {
+                bounding_box: Some(IrBoundingBox {
+                    x_min: chunk[3] & 0x7f,
+                    y_min: chunk[4] & 0x7f,
+                    x_max: chunk[5] & 0x7f,
+                    y_max: chunk[6] & 0x7f,
+                }),
+                intensity: Some(chunk[8]),
+                ..info
+            });
+        }
+
+        dots
+    }
+
+    fn decode_extended(bytes: &[u8]) -> Option<Self> {
+        let x = (bytes[2] as u16 >> 4 & 0b11) << 8 | bytes[0] as u16;
+        let y = (bytes[2] as u16 >> 6 & 0b11) << 8 | bytes[1] as u16;
+
+        // Missing dots are sent with every bit set
+        (x != 0x3ff || y != 0x3ff).then_some(Self {
+            dot: IrDot { x, y },
+            size: bytes[2] & 0x0f,
+            bounding_box: None,
+            intensity: None,
+        })
+    }
+}
+
+/// Turns the sensor bar dots seen by the IR camera into a position on the
+/// screen.
+///
+/// The two dots are assumed to be the ends of the sensor bar, and the
+/// Wiimote's roll is compensated for using the angle between them. If only one
+/// dot is visible, its position relative to the last pair of dots is used to
+/// estimate where the other one would be.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct IrPointer {
+    pub screen_width: u32,
+    pub screen_height: u32,
+    /// How much of the previous position to keep each update, from 0.0 (no
+    /// smoothing) to just under 1.0, or `None` to not smooth at all.
+    pub smoothing: Option<f32>,
+    last_dots: Option<[(f32, f32); 2]>,
+    last_angle: f32,
+    position: Option<(f32, f32)>,
+}
+
+#[cfg(feature = "std")]
+impl IrPointer {
+    pub fn new(screen_width: u32, screen_height: u32) -> Self {
+        Self {
+            screen_width,
+            screen_height,
+            smoothing: None,
+            last_dots: None,
+            last_angle: 0.0,
+            position: None,
+        }
+    }
+
+    /// Update the pointer with the dots from the latest report, returning the
+    /// new position on the screen in pixels.
+    ///
+    /// Returns `None` if the position can't be worked out, e.g. because no
+    /// dots are visible. The position isn't clamped to the screen.
+    pub fn update(&mut self, dots: &[IrDot]) -> Option<(f32, f32)> {
+        let mut visible = dots.iter().map(|dot| (dot.x as f32, dot.y as f32));
+
+        let (midpoint, angle) = match (visible.next(), visible.next()) {
+            (Some(first), Some(second)) => {
+                // Order the dots from left to right, since the camera doesn't
+                // keep track of which dot is which
+                let (left, right) = if first.0 <= second.0 {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+
+                let angle = (right.1 - left.1).atan2(right.0 - left.0);
+                self.last_dots = Some([left, right]);
+                self.last_angle = angle;
+
+                (((left.0 + right.0) / 2.0, (left.1 + right.1) / 2.0), angle)
+            }
+            (Some(dot), None) => match self.last_dots {
+                Some(last_dots) => {
+                    // Assume the visible dot is whichever one was closest
+                    let distance = |(x, y): (f32, f32)| (x - dot.0).powi(2) + (y - dot.1).powi(2);
+                    let closest = if distance(last_dots[0]) <= distance(last_dots[1]) {
+                        last_dots[0]
+                    } else {
+                        last_dots[1]
+                    };
+
+                    // Move the last pair of dots along with the visible one
+                    let offset = (dot.0 - closest.0, dot.1 - closest.1);
+                    let moved = last_dots.map(|(x, y)| (x + offset.0, y + offset.1));
+                    self.last_dots = Some(moved);
+
+                    (
+                        (
+                            (moved[0].0 + moved[1].0) / 2.0,
+                            (moved[0].1 + moved[1].1) / 2.0,
+                        ),
+                        self.last_angle,
+                    )
+                }
+                None => (dot, 0.0),
+            },
+            _ => {
+                self.position = None;
+                return None;
+            }
+        };
+
+        // Undo the roll of the Wiimote by rotating around the center of the
+        // camera's view
+        let center = (CAMERA_WIDTH as f32 / 2.0, CAMERA_HEIGHT as f32 / 2.0);
+        let (sin, cos) = (-angle).sin_cos();
+        let (dx, dy) = (midpoint.0 - center.0, midpoint.1 - center.1);
+        let x = dx * cos - dy * sin + center.0;
+        let y = dx * sin + dy * cos + center.1;
+
+        // The camera sees the sensor bar move the opposite way to the Wiimote
+        let target = (
+            (1.0 - x / CAMERA_WIDTH as f32) * self.screen_width as f32,
+            y / CAMERA_HEIGHT as f32 * self.screen_height as f32,
+        );
+
+        let position = match (self.smoothing, self.position) {
+            (Some(smoothing), Some(last)) => (
+                last.0 * smoothing + target.0 * (1.0 - smoothing),
+                last.1 * smoothing + target.1 * (1.0 - smoothing),
+            ),
+            _ => target,
+        };
+        self.position = Some(position);
+
+        Some(position)
+    }
+
+    /// The last position returned by [`IrPointer::update`].
+    pub fn position(&self) -> Option<(f32, f32)> {
+        self.position
+    }
+
+    /// Forget the previous dots and position.
+    pub fn reset(&mut self) {
+        self.last_dots = None;
+        self.last_angle = 0.0;
+        self.position = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    fn assert_near(got: Option<(f32, f32)>, expected: (f32, f32)) {
+        let (x, y) = got.unwrap();
+        assert!(
+            (x - expected.0).abs() < 0.5 && (y - expected.1).abs() < 0.5,
+            "{got:?} isn't near {expected:?}"
+        );
+    }
+
+    // A dot in the extended format
+    fn extended(x: u16, y: u16, size: u8) -> [u8; 3] {
+        [x as u8, y as u8, (y >> 8 << 6 | x >> 8 << 4) as u8 | size]
+    }
+
+    fn info(x: u16, y: u16, size: u8) -> IrDotInfo {
+        IrDotInfo {
+            dot: IrDot { x, y },
+            size,
+            bounding_box: None,
+            intensity: None,
+        }
+    }
+
+    #[test]
+    fn four_extended_dots() {
+        let dots = [(100, 200, 1), (300, 400, 2), (700, 600, 3), (1000, 767, 15)];
+        let mut bytes = [0; 12];
+        for (chunk, (x, y, size)) in bytes.chunks_exact_mut(3).zip(dots) {
+            chunk.copy_from_slice(&extended(x, y, size));
+        }
+
+        assert_eq!(
+            IrDotInfo::from_extended(&bytes),
+            dots.map(|(x, y, size)| Some(info(x, y, size)))
+        );
+    }
+
+    #[test]
+    fn absent_extended_dots_are_none() {
+        let mut bytes = [0xff; 12];
+        bytes[3..6].copy_from_slice(&extended(512, 384, 4));
+        // A dot at the very edge isn't mistaken for an absent one
+        bytes[9..].copy_from_slice(&extended(0x3ff, 0, 1));
+
+        assert_eq!(
+            IrDotInfo::from_extended(&bytes),
+            [None, Some(info(512, 384, 4)), None, Some(info(0x3ff, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn full_dots_have_a_bounding_box_and_intensity() {
+        let mut bytes = [0xff; 36];
+        bytes[..3].copy_from_slice(&extended(512, 384, 4));
+        bytes[3..9].copy_from_slice(&[10, 20, 30, 40, 0, 200]);
+
+        let dots = IrDotInfo::from_full(&bytes);
+        assert_eq!(
+            dots[0],
+            Some(IrDotInfo {
+                bounding_box: Some(IrBoundingBox {
+                    x_min: 10,
+                    y_min: 20,
+                    x_max: 30,
+                    y_max: 40,
+                }),
+                intensity: Some(200),
+                ..info(512, 384, 4)
+            })
+        );
+        assert_eq!(dots[1..], [None; 3]);
+    }
+
+    #[test]
+    fn basic_dots_in_pairs() {
+        // (512, 384) and an absent dot
+        let bytes = [0x00, 0x80, 0x6f, 0xff, 0xff];
+        assert_eq!(
+            IrDot::from_basic(&bytes),
+            [Some(IrDot { x: 512, y: 384 }), None]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn pointer_at_the_screen_center() {
+        let mut pointer = IrPointer::new(1920, 1080);
+        let left = IrDot { x: 412, y: 384 };
+        let right = IrDot { x: 612, y: 384 };
+
+        assert_near(pointer.update(&[left, right]), (960.0, 540.0));
+        // The camera doesn't keep the dots in order
+        assert_near(pointer.update(&[right, left]), (960.0, 540.0));
+        // Rolling the Wiimote rotates the dots around the center
+        let rolled = [IrDot { x: 426, y: 334 }, IrDot { x: 598, y: 434 }];
+        assert_near(pointer.update(&rolled), (960.0, 540.0));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn pointer_follows_one_dot_after_a_pair() {
+        let mut pointer = IrPointer::new(1024, 768);
+        assert_eq!(pointer.update(&[]), None);
+
+        pointer.update(&[IrDot { x: 412, y: 384 }, IrDot { x: 612, y: 384 }]);
+        // The right dot moves out of view as the left one moves 100 to the
+        // right, so the pointer moves 100 to the left
+        assert_near(pointer.update(&[IrDot { x: 512, y: 384 }]), (412.0, 384.0));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn pointer_smoothing_keeps_some_of_the_last_position() {
+        let mut pointer = IrPointer::new(1024, 768);
+        pointer.smoothing = Some(0.5);
+
+        pointer.update(&[IrDot { x: 412, y: 384 }, IrDot { x: 612, y: 384 }]);
+        let moved = pointer.update(&[IrDot { x: 312, y: 384 }, IrDot { x: 512, y: 384 }]);
+        assert_near(moved, (562.0, 384.0));
+        assert_eq!(pointer.position(), moved);
+    }
+}
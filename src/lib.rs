@@ -0,0 +1,34 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod bluetooth;
+pub mod buttons;
+pub mod calibration;
+#[cfg(feature = "std")]
+pub mod event;
+#[cfg(feature = "serde")]
+pub mod event_log;
+pub mod extension;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+#[cfg(feature = "std")]
+pub mod gesture;
+#[cfg(feature = "std")]
+pub mod hid;
+pub mod ir;
+#[cfg(feature = "std")]
+pub mod manager;
+pub mod motion;
+#[cfg(feature = "std")]
+pub mod prelude;
+pub mod protocol;
+#[cfg(feature = "std")]
+pub mod record;
+pub mod report;
+#[cfg(feature = "std")]
+pub mod scanner;
+pub mod status;
+#[cfg(feature = "std")]
+pub mod util;
+#[cfg(feature = "std")]
+pub mod wiimote;
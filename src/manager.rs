@@ -0,0 +1,791 @@
+use crossbeam_channel::{bounded, unbounded, Receiver};
+use thiserror::Error;
+
+use std::io;
+use std::iter;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bluetooth::Address;
+use crate::event::{EventDecoder, WiimoteEvent};
+use crate::hid;
+use crate::scanner::{KnownPath, WiimoteScanner};
+use crate::util::Flag;
+use crate::wiimote::{PlayerNumber, ReconnectPolicy, WiimoteConfig, WiimoteHandle};
+
+pub const MAX_PLAYERS: usize = 8;
+
+// How long the dispatcher thread waits between updates
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(5);
+// How many times to try opening a Wiimote's HID device, and how long to wait
+// in between. Freshly paired Wiimotes often can't be opened for a moment.
+const OPEN_ATTEMPTS: u32 = 3;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+type ConnectCallback = Box<dyn FnMut(PlayerNumber, &WiimoteHandle) + Send>;
+type DisconnectCallback = Box<dyn FnMut(PlayerNumber) + Send>;
+type EventCallback = Box<dyn FnMut(PlayerNumber, WiimoteEvent) + Send>;
+// Opens a Wiimote as a player, which is swapped out for mock Wiimotes in tests
+type Opener =
+    Box<dyn FnMut(hid::DeviceInfo, usize, WiimoteConfig) -> io::Result<WiimoteHandle> + Send>;
+
+#[derive(Default)]
+struct Callbacks {
+    on_connect: Option<ConnectCallback>,
+    on_disconnect: Option<DisconnectCallback>,
+    on_event: Option<EventCallback>,
+    observer: Option<Box<dyn WiimoteObserver>>,
+}
+
+/// Gets told about the lifecycle of the Wiimotes in a [`WiimoteManager`],
+/// e.g. to show it in a UI or record telemetry.
+///
+/// Unlike [`WiimoteManager::on_event`], this doesn't get the input from the
+/// Wiimotes. Every method does nothing by default.
+///
+/// The methods are called from [`WiimoteManager::update`], so on the
+/// dispatcher thread if the manager was spawned. They shouldn't block, since
+/// that holds up everything else the manager does.
+pub trait WiimoteObserver: Send {
+    /// The scanner started looking for Wiimotes.
+    ///
+    /// If the scanner is already running when the observer is set, this is
+    /// called straight away.
+    fn on_scan_started(&mut self) {}
+    /// The scanner found a Wiimote, which is about to be opened.
+    fn on_device_found(&mut self, _device_info: &hid::DeviceInfo) {}
+    /// A Wiimote connected as `player`, with the given Bluetooth address if it
+    /// is known.
+    fn on_connected(&mut self, _player: PlayerNumber, _address: Option<Address>) {}
+    /// The Wiimote for `player` disconnected.
+    fn on_disconnected(&mut self, _player: PlayerNumber) {}
+    /// A Wiimote was found, but couldn't be opened.
+    fn on_error(&mut self, _device_info: &hid::DeviceInfo, _error: &io::Error) {}
+}
+
+/// Something that happened to one of the slots of a [`WiimoteManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagerEvent {
+    /// A Wiimote was connected as this player.
+    Connected(PlayerNumber),
+    /// The Wiimote for this player disconnected.
+    Disconnected(PlayerNumber),
+    /// A Wiimote was found, but every slot is taken.
+    ///
+    /// The Wiimote is left unopened, and it is found again by the scanner
+    /// until a slot frees up. This is only emitted when the slots first turn
+    /// out to be full, not for every Wiimote that is found while they stay
+    /// full.
+    SlotsFull(hid::DeviceInfo),
+}
+
+/// A connected Wiimote, which the scanner won't find again until it is
+/// dropped.
+struct Slot {
+    wiimote: WiimoteHandle,
+    known_path: KnownPath,
+}
+
+/// A Wiimote that failed to open in a way that might be temporary, which keeps
+/// its slot until it is tried again.
+struct PendingOpen {
+    device_info: hid::DeviceInfo,
+    known_path: KnownPath,
+    player: PlayerNumber,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+/// A slot that is being kept for a Wiimote that disconnected.
+struct Reservation {
+    address: Address,
+    known_path: KnownPath,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+/// Keeps track of the Wiimotes found by a [`WiimoteScanner`], giving each one
+/// a player number.
+///
+/// The manager doesn't do anything on its own: [`WiimoteManager::update`] has
+/// to be called regularly to add newly found Wiimotes and remove disconnected
+/// ones. Alternatively, register callbacks with [`WiimoteManager::on_connect`]
+/// and friends, and let [`WiimoteManager::spawn`] call `update` on a
+/// dispatcher thread.
+pub struct WiimoteManager {
+    scanner: WiimoteScanner,
+    device_rx: Receiver<hid::DeviceInfo>,
+    config: WiimoteConfig,
+    reconnect_policy: ReconnectPolicy,
+    slots: [Option<Slot>; MAX_PLAYERS],
+    reservations: [Option<Reservation>; MAX_PLAYERS],
+    pending_opens: Vec<PendingOpen>,
+    opener: Opener,
+    // Whether `SlotsFull` has been emitted since a slot was last free
+    slots_full: bool,
+    // Only used when there is an `on_event` callback
+    decoders: [Option<EventDecoder>; MAX_PLAYERS],
+    callbacks: Callbacks,
+}
+
+impl WiimoteManager {
+    /// Start the scanner's thread, and manage the Wiimotes that it finds.
+    ///
+    /// Each Wiimote is initialized with `config`.
+    pub fn new(mut scanner: WiimoteScanner, config: WiimoteConfig) -> Self {
+        let (device_tx, device_rx) = unbounded();
+        scanner.start_thread(device_tx);
+
+        Self {
+            scanner,
+            device_rx,
+            config,
+            reconnect_policy: ReconnectPolicy::default(),
+            slots: Default::default(),
+            reservations: Default::default(),
+            pending_opens: Vec::new(),
+            opener: Box::new(WiimoteHandle::open),
+            slots_full: false,
+            decoders: Default::default(),
+            callbacks: Callbacks::default(),
+        }
+    }
+
+    pub fn set_reconnect_policy(&mut self, reconnect_policy: ReconnectPolicy) {
+        self.reconnect_policy = reconnect_policy;
+    }
+
+    /// Call `f` whenever a Wiimote connects.
+    pub fn on_connect(&mut self, f: impl FnMut(PlayerNumber, &WiimoteHandle) + Send + 'static) {
+        self.callbacks.on_connect = Some(Box::new(f));
+    }
+
+    /// Call `f` whenever a Wiimote disconnects.
+    pub fn on_disconnect(&mut self, f: impl FnMut(PlayerNumber) + Send + 'static) {
+        self.callbacks.on_disconnect = Some(Box::new(f));
+    }
+
+    /// Call `f` with the events decoded from every Wiimote.
+    ///
+    /// This takes over the reports read from the Wiimotes, so
+    /// [`WiimoteManager::try_recv_reports`] won't return anything once this
+    /// has been called.
+    pub fn on_event(&mut self, f: impl FnMut(PlayerNumber, WiimoteEvent) + Send + 'static) {
+        self.callbacks.on_event = Some(Box::new(f));
+        for (i, slot) in self.slots.iter().enumerate() {
+            if let (Some(slot), None) = (slot, &self.decoders[i]) {
+                self.decoders[i] = Some(slot.wiimote.event_decoder(&self.config));
+            }
+        }
+    }
+
+    /// Tell `observer` about the Wiimotes connecting and disconnecting,
+    /// replacing any previous observer.
+    pub fn set_observer(&mut self, observer: Box<dyn WiimoteObserver>) {
+        let observer = self.callbacks.observer.insert(observer);
+        if self.scanner.is_running() {
+            observer.on_scan_started();
+        }
+    }
+
+    /// Remove disconnected Wiimotes and add newly found ones, returning what
+    /// changed.
+    ///
+    /// Any callbacks are called from here, on the calling thread.
+    pub fn update(&mut self) -> Vec<ManagerEvent> {
+        let mut events = Vec::new();
+
+        self.remove_disconnected(&mut events);
+        self.retry_reservations(&mut events);
+        self.retry_opens(&mut events);
+        self.add_new_devices(&mut events);
+        self.dispatch(&events);
+
+        events
+    }
+
+    /// Move the manager to a dispatcher thread, which calls
+    /// [`WiimoteManager::update`] in a loop until it is stopped.
+    ///
+    /// Every callback is called from this one thread, one at a time, so a
+    /// callback that blocks holds up the events of every Wiimote. The poll
+    /// threads of the Wiimotes keep reading in the meantime, so no reports
+    /// are lost.
+    pub fn spawn(mut self) -> ManagerThread {
+        let is_running = Flag::new(true);
+        let thread_is_running = is_running.cloned_handle();
+
+        let thread_handle = thread::spawn(move || {
+            while thread_is_running.get() {
+                self.update();
+                thread::sleep(DISPATCH_INTERVAL);
+            }
+
+            self
+        });
+
+        ManagerThread {
+            is_running,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    fn dispatch(&mut self, events: &[ManagerEvent]) {
+        for event in events {
+            match event {
+                ManagerEvent::Connected(player) => {
+                    if let Some(slot) = &self.slots[player.index()] {
+                        if let Some(observer) = &mut self.callbacks.observer {
+                            observer.on_connected(*player, slot.wiimote.address());
+                        }
+                        if let Some(on_connect) = &mut self.callbacks.on_connect {
+                            on_connect(*player, &slot.wiimote);
+                        }
+                    }
+                }
+                ManagerEvent::Disconnected(player) => {
+                    if let Some(observer) = &mut self.callbacks.observer {
+                        observer.on_disconnected(*player);
+                    }
+                    if let Some(on_disconnect) = &mut self.callbacks.on_disconnect {
+                        on_disconnect(*player);
+                    }
+                }
+                ManagerEvent::SlotsFull(_) => {}
+            }
+        }
+
+        if let Some(on_event) = &mut self.callbacks.on_event {
+            for (i, slot) in self.slots.iter().enumerate() {
+                if let (Some(slot), Some(decoder)) = (slot, &mut self.decoders[i]) {
+                    Self::dispatch_reports(PlayerNumber(i), &slot.wiimote, decoder, on_event);
+                }
+            }
+        }
+    }
+
+    fn dispatch_reports(
+        player: PlayerNumber,
+        wiimote: &WiimoteHandle,
+        decoder: &mut EventDecoder,
+        on_event: &mut EventCallback,
+    ) {
+        for report in wiimote.read_rx().try_iter() {
+            if let Some(event) = decoder.decode(&report) {
+                on_event(player, event);
+            }
+            while let Some(event) = decoder.pending() {
+                on_event(player, event);
+            }
+        }
+    }
+
+    fn remove_disconnected(&mut self, events: &mut Vec<ManagerEvent>) {
+        // FIXME: drain_filter()?
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            let Slot {
+                wiimote,
+                known_path,
+            } = match slot.take() {
+                Some(slot) if !slot.wiimote.is_connected() => slot,
+                other => {
+                    *slot = other;
+                    continue;
+                }
+            };
+
+            // Finish off the events of the wiimote, releasing its buttons
+            if let (Some(on_event), Some(mut decoder)) =
+                (&mut self.callbacks.on_event, self.decoders[i].take())
+            {
+                Self::dispatch_reports(PlayerNumber(i), &wiimote, &mut decoder, on_event);
+                loop {
+                    let event = decoder.disconnect();
+                    on_event(PlayerNumber(i), event);
+                    if event == WiimoteEvent::Disconnected {
+                        break;
+                    }
+                }
+            }
+
+            // Keep the slot for the wiimote while we try to reconnect it.
+            // Otherwise, dropping the path lets the scanner find it again.
+            match wiimote.address() {
+                Some(address) if self.reconnect_policy.max_attempts > 0 => {
+                    println!("[WiimoteManager] Reserving slot {i} for wiimote {address}");
+                    self.reservations[i] = Some(Reservation {
+                        address,
+                        known_path,
+                        attempts: 0,
+                        next_attempt: Instant::now() + self.reconnect_policy.delay(0),
+                    });
+                }
+                _ => drop(known_path),
+            }
+
+            println!("[WiimoteManager] Removed wiimote from slot {i}");
+            events.push(ManagerEvent::Disconnected(PlayerNumber(i)));
+        }
+    }
+
+    fn retry_reservations(&mut self, events: &mut Vec<ManagerEvent>) {
+        for i in 0..MAX_PLAYERS {
+            let reservation = match &mut self.reservations[i] {
+                Some(reservation) if Instant::now() >= reservation.next_attempt => reservation,
+                _ => continue,
+            };
+
+            if let Some(device_info) = find_wiimote(reservation.address, self.scanner.identifiers())
+            {
+                println!("[WiimoteManager] Reconnecting wiimote to slot {i}");
+                let reservation = self.reservations[i].take();
+                let known_path = self.claim_path(reservation, &device_info);
+                self.open(device_info, known_path, PlayerNumber(i), events);
+                continue;
+            }
+
+            reservation.attempts += 1;
+            if reservation.attempts >= self.reconnect_policy.max_attempts {
+                // Give up, so the scanner can find the wiimote as a new one
+                self.reservations[i] = None;
+                println!("[WiimoteManager] Released slot {i}");
+            } else {
+                reservation.next_attempt =
+                    Instant::now() + self.reconnect_policy.delay(reservation.attempts);
+            }
+        }
+    }
+
+    fn retry_opens(&mut self, events: &mut Vec<ManagerEvent>) {
+        let now = Instant::now();
+        let (due, waiting) = self
+            .pending_opens
+            .drain(..)
+            .partition(|pending| now >= pending.next_attempt);
+        self.pending_opens = waiting;
+
+        for pending in due {
+            println!(
+                "[WiimoteManager] Retrying opening wiimote in slot {}",
+                pending.player.index()
+            );
+            let PendingOpen {
+                device_info,
+                known_path,
+                player,
+                attempts,
+                ..
+            } = pending;
+            self.open_attempt(device_info, known_path, player, attempts + 1, events);
+        }
+    }
+
+    /// Find the slot to put a newly found wiimote in, or `None` if they are
+    /// all taken.
+    fn free_slot(&self, device_info: &hid::DeviceInfo) -> Option<PlayerNumber> {
+        // Put a reconnected wiimote back in its old slot
+        let reserved_slot = device_info.address().and_then(|address| {
+            self.reservations
+                .iter()
+                .position(|r| r.as_ref().map(|r| r.address) == Some(address))
+        });
+
+        // Otherwise, use the first available slot
+        reserved_slot
+            .or_else(|| self.first_free_slot())
+            .map(PlayerNumber)
+    }
+
+    // The first slot that isn't in use or being kept for a Wiimote
+    fn first_free_slot(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .zip(&self.reservations)
+            .enumerate()
+            .position(|(i, (wm, r))| {
+                wm.is_none()
+                    && r.is_none()
+                    && !self.pending_opens.iter().any(|p| p.player.index() == i)
+            })
+    }
+
+    fn add_new_devices(&mut self, events: &mut Vec<ManagerEvent>) {
+        if self.first_free_slot().is_some() {
+            self.slots_full = false;
+        }
+
+        while let Ok(device_info) = self.device_rx.try_recv() {
+            if let Some(observer) = &mut self.callbacks.observer {
+                observer.on_device_found(&device_info);
+            }
+
+            let player = match self.free_slot(&device_info) {
+                Some(player) => player,
+                None => {
+                    // Let the scanner find the wiimote again later
+                    self.scanner.forget_device_path(&device_info.path);
+                    if !self.slots_full {
+                        println!("[WiimoteManager] All {MAX_PLAYERS} slots are full");
+                        self.slots_full = true;
+                        events.push(ManagerEvent::SlotsFull(device_info));
+                    }
+                    continue;
+                }
+            };
+
+            let reservation = self.reservations[player.index()].take();
+            let known_path = self.claim_path(reservation, &device_info);
+            self.open(device_info, known_path, player, events);
+        }
+    }
+
+    /// Keep the scanner from finding the wiimote again while it is in a slot.
+    ///
+    /// If the wiimote is reconnecting with the same path, the reservation's
+    /// path is reused so that the scanner doesn't forget it in between.
+    fn claim_path(
+        &self,
+        reservation: Option<Reservation>,
+        device_info: &hid::DeviceInfo,
+    ) -> KnownPath {
+        match reservation {
+            Some(reservation) if reservation.known_path.path() == device_info.path => {
+                reservation.known_path
+            }
+            _ => self.scanner.known_path(device_info.path.clone()),
+        }
+    }
+
+    fn open(
+        &mut self,
+        device_info: hid::DeviceInfo,
+        known_path: KnownPath,
+        player: PlayerNumber,
+        events: &mut Vec<ManagerEvent>,
+    ) {
+        self.open_attempt(device_info, known_path, player, 1, events);
+    }
+
+    // Errors that might go away by themselves are retried on a later update,
+    // rather than blocking this one
+    fn open_attempt(
+        &mut self,
+        device_info: hid::DeviceInfo,
+        known_path: KnownPath,
+        player: PlayerNumber,
+        attempts: u32,
+        events: &mut Vec<ManagerEvent>,
+    ) {
+        println!(
+            "[WiimoteManager] Opening HID Device with path {:?}",
+            device_info.path
+        );
+
+        match (self.opener)(device_info.clone(), player.index(), self.config.clone()) {
+            Ok(wiimote) => {
+                if self.callbacks.on_event.is_some() {
+                    self.decoders[player.index()] = Some(wiimote.event_decoder(&self.config));
+                }
+                self.slots[player.index()] = Some(Slot {
+                    wiimote,
+                    known_path,
+                });
+                events.push(ManagerEvent::Connected(player));
+            }
+            Err(e) if attempts < OPEN_ATTEMPTS && hid::Device::is_recoverable_open_error(&e) => {
+                println!(
+                    "[WiimoteManager] Failed to open wiimote (attempt {attempts}/{OPEN_ATTEMPTS}): {e}"
+                );
+                self.pending_opens.push(PendingOpen {
+                    device_info,
+                    known_path,
+                    player,
+                    attempts,
+                    next_attempt: Instant::now() + OPEN_RETRY_DELAY,
+                });
+            }
+            Err(e) => {
+                // Let the scanner find the wiimote again so we can retry
+                eprintln!("[WiimoteManager] Error opening wiimote: {e}");
+                if let Some(observer) = &mut self.callbacks.observer {
+                    observer.on_error(&device_info, &e);
+                }
+                drop(known_path);
+            }
+        }
+    }
+
+    /// How many Wiimotes are currently connected.
+    pub fn connected_count(&self) -> usize {
+        self.iter()
+            .filter(|(_, wiimote)| wiimote.is_connected())
+            .count()
+    }
+
+    /// Iterate over the Wiimotes in the manager, in order of player number.
+    pub fn iter(&self) -> impl Iterator<Item = (PlayerNumber, &WiimoteHandle)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .flat_map(|(i, slot)| Some(PlayerNumber(i)).zip(slot.as_ref()))
+            .map(|(player, slot)| (player, &slot.wiimote))
+    }
+
+    /// Get the Wiimote for the given player, if there is one.
+    pub fn get(&self, player: PlayerNumber) -> Option<&WiimoteHandle> {
+        self.slots
+            .get(player.index())?
+            .as_ref()
+            .map(|slot| &slot.wiimote)
+    }
+
+    /// Receive the reports that have been read from every Wiimote, without
+    /// blocking.
+    pub fn try_recv_reports(&self) -> impl Iterator<Item = (PlayerNumber, hid::Report)> + '_ {
+        self.iter()
+            .flat_map(|(player, wiimote)| iter::repeat(player).zip(wiimote.read_rx().try_iter()))
+    }
+
+    /// Start the scanner's thread again after
+    /// [`WiimoteManager::stop_scanning`].
+    ///
+    /// This does nothing if the scanner is already running.
+    pub fn start_scanning(&mut self) {
+        if self.scanner.is_running() {
+            return;
+        }
+
+        let (device_tx, device_rx) = unbounded();
+        self.scanner.start_thread(device_tx);
+        self.device_rx = device_rx;
+        if let Some(observer) = &mut self.callbacks.observer {
+            observer.on_scan_started();
+        }
+    }
+
+    /// Disconnect every Wiimote and stop the scanner, waiting at most
+    /// `timeout` for all of their threads to finish.
+    ///
+    /// Any reads and writes that the Wiimotes are blocked on are cancelled.
+    /// The scanner can't be interrupted in the middle of a Bluetooth inquiry,
+    /// so if it is in one this will probably time out. On a timeout, the
+    /// threads that haven't finished are left to finish in the background.
+    pub fn shutdown(self, timeout: Duration) -> Result<(), ShutdownTimeout> {
+        // Tell everything to stop first, so that they all stop at once
+        self.scanner.signal_stop();
+        for (_, wiimote) in self.iter() {
+            wiimote.signal_stop();
+        }
+
+        let (done_tx, done_rx) = bounded(1);
+        thread::spawn(move || {
+            // This joins every thread
+            drop(self);
+            let _ = done_tx.send(());
+        });
+
+        done_rx
+            .recv_timeout(timeout)
+            .map_err(|_| ShutdownTimeout(timeout))
+    }
+
+    /// Stop the scanner's thread.
+    ///
+    /// The connected Wiimotes stay connected until the manager is dropped.
+    pub fn stop_scanning(&mut self) {
+        self.scanner.stop_thread();
+    }
+}
+
+/// The threads of a [`WiimoteManager`] didn't stop in time when it was shut
+/// down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("The Wiimote manager didn't shut down within {0:?}")]
+pub struct ShutdownTimeout(pub Duration);
+
+/// A [`WiimoteManager`] running on its own dispatcher thread, created with
+/// [`WiimoteManager::spawn`].
+///
+/// The thread is stopped when this is dropped.
+pub struct ManagerThread {
+    is_running: Flag,
+    thread_handle: Option<thread::JoinHandle<WiimoteManager>>,
+}
+
+impl ManagerThread {
+    /// Stop the dispatcher thread and get the manager back.
+    pub fn stop(mut self) -> WiimoteManager {
+        self.join().expect("The dispatcher thread panicked")
+    }
+
+    fn join(&mut self) -> Option<WiimoteManager> {
+        self.is_running.set(false);
+        let thread_handle = self.thread_handle.take()?;
+
+        match thread_handle.join() {
+            Ok(manager) => Some(manager),
+            Err(_) => {
+                println!("[WiimoteManager] Dispatcher thread panicked");
+                None
+            }
+        }
+    }
+}
+
+impl Drop for ManagerThread {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+fn find_wiimote(
+    address: Address,
+    identifiers: &[hid::WiimoteIdentifier],
+) -> Option<hid::DeviceInfo> {
+    let device_enumerator = hid::DeviceEnumerator::new().ok()?;
+    let device_info = device_enumerator
+        .devices()
+        .find(|d| d.is_wiimote_in(identifiers) && d.address() == Some(address));
+
+    device_info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hid::MockDevice;
+
+    // The device info of the `n`th mock Wiimote
+    fn mock_device_info(n: usize) -> hid::DeviceInfo {
+        hid::DeviceInfo {
+            path: format!("mock-wiimote-{n}"),
+            vendor_id: 0x057e,
+            product_id: 0x0306,
+            product_string: None,
+            serial_number: None,
+            usage_page: 0,
+            usage: 0,
+        }
+    }
+
+    fn mock_wiimote(player_num: usize) -> (WiimoteHandle, String) {
+        let device_info = mock_device_info(player_num);
+        let path = device_info.path.clone();
+        let config = WiimoteConfig {
+            rumble_on_connect: false,
+            ..WiimoteConfig::default()
+        };
+        let device = MockDevice::default().timing_out();
+
+        let wiimote = WiimoteHandle::from_device(device, None, device_info, player_num, config);
+        (wiimote, path)
+    }
+
+    // A manager with mock Wiimotes in the first `count` slots, and a scanner
+    // that was never started
+    fn mock_manager(count: usize) -> WiimoteManager {
+        let (_, device_rx) = unbounded();
+        let mut manager = WiimoteManager {
+            scanner: WiimoteScanner::new(),
+            device_rx,
+            config: WiimoteConfig::default(),
+            reconnect_policy: ReconnectPolicy::default(),
+            slots: Default::default(),
+            reservations: Default::default(),
+            pending_opens: Vec::new(),
+            slots_full: false,
+            opener: Box::new(|device_info, _, _| {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{:?} isn't a mock", device_info.path),
+                ))
+            }),
+            decoders: Default::default(),
+            callbacks: Callbacks::default(),
+        };
+
+        for i in 0..count {
+            let (wiimote, path) = mock_wiimote(i);
+            let known_path = manager.scanner.known_path(path);
+            manager.slots[i] = Some(Slot {
+                wiimote,
+                known_path,
+            });
+        }
+
+        manager
+    }
+
+    #[test]
+    fn shutdown_finishes_within_the_deadline() {
+        const TIMEOUT: Duration = Duration::from_secs(2);
+
+        let manager = mock_manager(4);
+        assert_eq!(manager.connected_count(), 4);
+
+        let start = Instant::now();
+        assert_eq!(manager.shutdown(TIMEOUT), Ok(()));
+        assert!(start.elapsed() < TIMEOUT);
+    }
+
+    #[test]
+    fn open_is_retried_on_a_later_update() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+
+        let mut manager = mock_manager(0);
+        let (device_tx, device_rx) = unbounded();
+        manager.device_rx = device_rx;
+
+        // Fail the first attempt the way a freshly paired Wiimote does
+        let attempts = Arc::new(AtomicU32::new(0));
+        let opener_attempts = Arc::clone(&attempts);
+        manager.opener = Box::new(move |_, player_num, _| {
+            match opener_attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(io::Error::from(io::ErrorKind::PermissionDenied)),
+                _ => Ok(mock_wiimote(player_num).0),
+            }
+        });
+
+        device_tx.send(mock_device_info(0)).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(manager.update(), []);
+        assert_eq!(manager.update(), []);
+        assert!(start.elapsed() < OPEN_RETRY_DELAY);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        thread::sleep(OPEN_RETRY_DELAY);
+        assert_eq!(manager.update(), [ManagerEvent::Connected(PlayerNumber(0))]);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        manager.shutdown(Duration::from_secs(2)).unwrap();
+    }
+
+    #[test]
+    fn slots_full_is_emitted_once_they_become_full() {
+        let mut manager = mock_manager(MAX_PLAYERS);
+        let (device_tx, device_rx) = unbounded();
+        manager.device_rx = device_rx;
+
+        let device_info = mock_device_info(MAX_PLAYERS);
+
+        // The scanner finds the extra Wiimote again on every pass
+        device_tx.send(device_info.clone()).unwrap();
+        assert_eq!(
+            manager.update(),
+            [ManagerEvent::SlotsFull(device_info.clone())]
+        );
+        device_tx.send(device_info.clone()).unwrap();
+        assert_eq!(manager.update(), []);
+
+        // Once a slot has been free, filling it up again is a new transition
+        let slot = manager.slots[0].take();
+        assert_eq!(manager.update(), []);
+        manager.slots[0] = slot;
+        device_tx.send(device_info.clone()).unwrap();
+        assert_eq!(manager.update(), [ManagerEvent::SlotsFull(device_info)]);
+
+        manager.shutdown(Duration::from_secs(2)).unwrap();
+    }
+}
@@ -0,0 +1,857 @@
+use core::fmt;
+use core::ops::{Add, Mul, Sub};
+use core::time::Duration;
+
+use crate::protocol::{Report, ReportMode};
+use crate::report::{self, DecodeError};
+
+/// A 3D vector, e.g. an acceleration or an angular velocity.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn length(self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+/// The raw 10-bit accelerometer values from the Wiimote.
+///
+/// The Wiimote reports the acceleration it feels, so when lying flat and still
+/// it reads +1G on the Z axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccelData {
+    pub x: u16,
+    pub y: u16,
+    pub z: u16,
+}
+
+impl AccelData {
+    /// Decode the accelerometer data from the two button bytes (which hold the
+    /// least significant bits) and the three accelerometer bytes of a report.
+    pub fn from_bytes(buttons: [u8; 2], accel: [u8; 3]) -> Self {
+        // X has two extra bits, but Y and Z only have one each
+        Self {
+            x: (accel[0] as u16) << 2 | (buttons[0] >> 5 & 0b11) as u16,
+            y: (accel[1] as u16) << 2 | (buttons[1] >> 4 & 0b10) as u16,
+            z: (accel[2] as u16) << 2 | (buttons[1] >> 5 & 0b10) as u16,
+        }
+    }
+
+    /// The acceleration in units of G.
+    pub fn calibrated(&self, cal: &AccelCalibration) -> Vec3 {
+        let axis = |raw: u16, i: usize| {
+            let zero = cal.zero_g[i] as f32;
+            let one = cal.one_g[i] as f32;
+            (raw as f32 - zero) / (one - zero)
+        };
+
+        Vec3::new(axis(self.x, 0), axis(self.y, 1), axis(self.z, 2))
+    }
+
+    /// The acceleration in units of G with gravity removed, i.e. only the
+    /// acceleration caused by moving the Wiimote.
+    ///
+    /// `orientation` is the orientation of the Wiimote, e.g. from an
+    /// [`OrientationFilter`], which is used to find where gravity is pulling.
+    /// A Wiimote that is lying still has a linear acceleration of about zero.
+    pub fn linear(&self, cal: &AccelCalibration, orientation: &Quaternion) -> Vec3 {
+        self.calibrated(cal) - orientation.gravity()
+    }
+
+    /// Decode the accelerometer data from a report sent in `mode`, or `None`
+    /// if the mode doesn't include it.
+    ///
+    /// The report must be long enough to hold the accelerometer data.
+    pub fn from_mode(report: &Report, mode: ReportMode) -> Option<Self> {
+        let range = mode.accel_range()?;
+        let accel = &report[range];
+
+        Some(Self::from_bytes(
+            [report[2], report[3]],
+            [accel[0], accel[1], accel[2]],
+        ))
+    }
+}
+
+impl TryFrom<&Report> for AccelData {
+    type Error = DecodeError;
+
+    /// Decode the accelerometer data from a report in any of the report modes
+    /// that include it, e.g. alongside the IR data in
+    /// [`ReportMode::CoreAccelIr12`].
+    fn try_from(report: &Report) -> Result<Self, Self::Error> {
+        let id = report::input_report_id(report, 2)?;
+        let mode = ReportMode::try_from(id).map_err(|_| DecodeError::UnexpectedId {
+            expected_id: None,
+            got_id: id,
+        })?;
+        let range = mode.accel_range().ok_or(DecodeError::UnexpectedId {
+            expected_id: None,
+            got_id: id,
+        })?;
+        report::input_report_id(report, range.end)?;
+
+        Ok(Self::from_mode(report, mode).unwrap())
+    }
+}
+
+impl fmt::Display for AccelData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "X: {:4}, Y: {:4}, Z: {:4}", self.x, self.y, self.z)
+    }
+}
+
+/// The accelerometer calibration stored in the Wiimote's EEPROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccelCalibration {
+    /// The raw values at 0G, in X, Y, Z order.
+    pub zero_g: [u16; 3],
+    /// The raw values at 1G, in X, Y, Z order.
+    pub one_g: [u16; 3],
+}
+
+impl AccelCalibration {
+    /// The address of the calibration data in the Wiimote's EEPROM.
+    pub const ADDRESS: u32 = 0x0016;
+    /// The length of the calibration data, including the checksum.
+    pub const LENGTH: usize = 10;
+
+    /// Decode the calibration data read from [`AccelCalibration::ADDRESS`].
+    ///
+    /// Returns `None` if the checksum doesn't match.
+    pub fn from_bytes(bytes: &[u8; Self::LENGTH]) -> Option<Self> {
+        let sum = bytes[..9]
+            .iter()
+            .fold(0u8, |acc, &byte| acc.wrapping_add(byte));
+        if bytes[9] != sum.wrapping_add(0x55) {
+            return None;
+        }
+
+        let values = |offset: usize| {
+            let lsb = bytes[offset + 3];
+            [
+                (bytes[offset] as u16) << 2 | (lsb >> 4 & 0b11) as u16,
+                (bytes[offset + 1] as u16) << 2 | (lsb >> 2 & 0b11) as u16,
+                (bytes[offset + 2] as u16) << 2 | (lsb & 0b11) as u16,
+            ]
+        };
+
+        Some(Self {
+            zero_g: values(0),
+            one_g: values(4),
+        })
+    }
+}
+
+impl Default for AccelCalibration {
+    /// Typical calibration values, for when the Wiimote's own calibration
+    /// can't be read.
+    fn default() -> Self {
+        Self {
+            zero_g: [512, 512, 512],
+            one_g: [616, 616, 616],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestCalibrationError {
+    NoSamples,
+    Moved { noise: f32 },
+    NotConnected,
+}
+
+impl fmt::Display for RestCalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSamples => write!(f, "No accelerometer data was received"),
+            Self::Moved { noise } => write!(
+                f,
+                "The Wiimote moved while it was being calibrated (noise {noise:.1})"
+            ),
+            Self::NotConnected => write!(f, "The Wiimote disconnected before it was calibrated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RestCalibrationError {}
+
+/// The offset and noise of a particular Wiimote's accelerometer, measured
+/// while it lies still.
+///
+/// This is on top of the [`AccelCalibration`] stored in the Wiimote, which is
+/// the same for every Wiimote of a model, give or take.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestCalibration {
+    /// The average raw reading on each axis.
+    pub mean: Vec3,
+    /// The standard deviation of the raw readings on each axis, i.e. how much
+    /// they jitter when the Wiimote isn't moving.
+    pub noise: Vec3,
+}
+
+impl RestCalibration {
+    /// How much the raw readings can jitter (as a standard deviation) before
+    /// the Wiimote is assumed to have moved.
+    pub const MAX_NOISE: f32 = 4.0;
+
+    /// Work out the calibration from raw samples taken while the Wiimote was
+    /// lying still.
+    #[cfg(feature = "std")]
+    pub fn from_samples(samples: &[AccelData]) -> Result<Self, RestCalibrationError> {
+        if samples.is_empty() {
+            return Err(RestCalibrationError::NoSamples);
+        }
+
+        let n = samples.len() as f32;
+        let to_vec3 =
+            |sample: &AccelData| Vec3::new(sample.x as f32, sample.y as f32, sample.z as f32);
+
+        let mean = samples
+            .iter()
+            .map(to_vec3)
+            .fold(Vec3::ZERO, |sum, v| sum + v)
+            * (1.0 / n);
+        let variance = samples
+            .iter()
+            .map(|sample| {
+                let d = to_vec3(sample) - mean;
+                Vec3::new(d.x * d.x, d.y * d.y, d.z * d.z)
+            })
+            .fold(Vec3::ZERO, |sum, v| sum + v)
+            * (1.0 / n);
+        let noise = Vec3::new(variance.x.sqrt(), variance.y.sqrt(), variance.z.sqrt());
+
+        let max_noise = noise.x.max(noise.y).max(noise.z);
+        if max_noise > Self::MAX_NOISE {
+            return Err(RestCalibrationError::Moved { noise: max_noise });
+        }
+
+        Ok(Self { mean, noise })
+    }
+
+    /// The noise on each axis in units of G, e.g. to use as a deadzone.
+    pub fn noise_g(&self, cal: &AccelCalibration) -> Vec3 {
+        let axis = |noise: f32, i: usize| noise / (cal.one_g[i] as f32 - cal.zero_g[i] as f32);
+
+        Vec3::new(
+            axis(self.noise.x, 0),
+            axis(self.noise.y, 1),
+            axis(self.noise.z, 2),
+        )
+    }
+}
+
+/// A rotation, represented as a unit quaternion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    #[cfg(feature = "std")]
+    fn normalized(self) -> Self {
+        let norm = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if norm == 0.0 {
+            return Self::IDENTITY;
+        }
+
+        Self {
+            w: self.w / norm,
+            x: self.x / norm,
+            y: self.y / norm,
+            z: self.z / norm,
+        }
+    }
+
+    /// The acceleration that gravity causes the Wiimote to read in G,
+    /// assuming this is the orientation of the Wiimote.
+    pub fn gravity(&self) -> Vec3 {
+        let Self { w, x, y, z } = *self;
+        Vec3::new(
+            2.0 * (x * z - w * y),
+            2.0 * (w * x + y * z),
+            w * w - x * x - y * y + z * z,
+        )
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Roll, pitch, and yaw angles in radians.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EulerAngles {
+    /// The rotation about the Wiimote's long (Y) axis.
+    pub roll: f32,
+    /// The rotation about the Wiimote's X axis, where pointing up is positive.
+    pub pitch: f32,
+    /// The rotation about the vertical axis, relative to where the Wiimote was
+    /// pointing when the filter started.
+    pub yaw: f32,
+}
+
+/// Estimates the orientation of a Wiimote using Madgwick's IMU filter.
+///
+/// The accelerometer alone can only measure roll and pitch, so the yaw will
+/// only be tracked if gyroscope data from a MotionPlus is also provided.
+///
+/// See <https://x-io.co.uk/open-source-imu-and-ahrs-algorithms/>
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct OrientationFilter {
+    /// How strongly the accelerometer corrects the gyroscope's drift.
+    beta: f32,
+    orientation: Quaternion,
+}
+
+#[cfg(feature = "std")]
+impl OrientationFilter {
+    pub const DEFAULT_BETA: f32 = 0.1;
+
+    pub fn new(beta: f32) -> Self {
+        Self {
+            beta,
+            orientation: Quaternion::IDENTITY,
+        }
+    }
+
+    /// Update the orientation with a new sample.
+    ///
+    /// `accel` is the calibrated acceleration in G, and `gyro` is the angular
+    /// velocity about the X, Y, and Z axes in radians per second. `dt` is the
+    /// time since the previous sample.
+    pub fn update(&mut self, accel: Vec3, gyro: Option<Vec3>, dt: Duration) {
+        let Vec3 {
+            x: gx,
+            y: gy,
+            z: gz,
+        } = gyro.unwrap_or_default();
+        let Quaternion {
+            w: q0,
+            x: q1,
+            y: q2,
+            z: q3,
+        } = self.orientation;
+
+        // Rate of change of the quaternion from the gyroscope
+        let mut q_dot = [
+            0.5 * (-q1 * gx - q2 * gy - q3 * gz),
+            0.5 * (q0 * gx + q2 * gz - q3 * gy),
+            0.5 * (q0 * gy - q1 * gz + q3 * gx),
+            0.5 * (q0 * gz + q1 * gy - q2 * gx),
+        ];
+
+        // Correct using the accelerometer, unless there is no reading
+        let accel_norm = accel.length();
+        if accel_norm > 0.0 {
+            let Vec3 {
+                x: ax,
+                y: ay,
+                z: az,
+            } = accel * (1.0 / accel_norm);
+
+            // Gradient descent step
+            let s = [
+                4.0 * q0 * q2 * q2 + 2.0 * q2 * ax + 4.0 * q0 * q1 * q1 - 2.0 * q1 * ay,
+                4.0 * q1 * q3 * q3 - 2.0 * q3 * ax + 4.0 * q0 * q0 * q1 - 2.0 * q0 * ay - 4.0 * q1
+                    + 8.0 * q1 * q1 * q1
+                    + 8.0 * q1 * q2 * q2
+                    + 4.0 * q1 * az,
+                4.0 * q0 * q0 * q2 + 2.0 * q0 * ax + 4.0 * q2 * q3 * q3 - 2.0 * q3 * ay - 4.0 * q2
+                    + 8.0 * q2 * q1 * q1
+                    + 8.0 * q2 * q2 * q2
+                    + 4.0 * q2 * az,
+                4.0 * q1 * q1 * q3 - 2.0 * q1 * ax + 4.0 * q2 * q2 * q3 - 2.0 * q2 * ay,
+            ];
+            let s_norm = s.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if s_norm > 0.0 {
+                for (q, s) in q_dot.iter_mut().zip(s) {
+                    *q -= self.beta * s / s_norm;
+                }
+            }
+        }
+
+        let dt = dt.as_secs_f32();
+        self.orientation = Quaternion {
+            w: q0 + q_dot[0] * dt,
+            x: q1 + q_dot[1] * dt,
+            y: q2 + q_dot[2] * dt,
+            z: q3 + q_dot[3] * dt,
+        }
+        .normalized();
+    }
+
+    /// The current estimate of the orientation.
+    pub fn orientation(&self) -> Quaternion {
+        self.orientation
+    }
+
+    /// The current estimate of the orientation as roll, pitch, and yaw.
+    pub fn euler_angles(&self) -> EulerAngles {
+        let Vec3 {
+            x: gx,
+            y: gy,
+            z: gz,
+        } = self.orientation.gravity();
+        let Quaternion { w, x, y, z } = self.orientation;
+
+        EulerAngles {
+            roll: gx.atan2(gz),
+            pitch: gy.atan2(gx.hypot(gz)),
+            yaw: (2.0 * (x * y + w * z)).atan2(w * w + x * x - y * y - z * z),
+        }
+    }
+
+    /// Reset the orientation back to flat, facing forwards.
+    pub fn reset(&mut self) {
+        self.orientation = Quaternion::IDENTITY;
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for OrientationFilter {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BETA)
+    }
+}
+
+/// Smooths out noisy samples with an exponential moving average.
+#[derive(Debug, Clone)]
+pub struct LowPassFilter {
+    /// How much of each new sample is used, from 0.0 (ignore new samples) to
+    /// 1.0 (no smoothing).
+    pub alpha: f32,
+    value: Option<Vec3>,
+}
+
+impl LowPassFilter {
+    pub fn new(alpha: f32) -> Self {
+        Self { alpha, value: None }
+    }
+
+    /// Add a sample, returning the smoothed value.
+    pub fn update(&mut self, sample: Vec3) -> Vec3 {
+        let value = match self.value {
+            Some(value) => value + (sample - value) * self.alpha,
+            None => sample,
+        };
+        self.value = Some(value);
+
+        value
+    }
+
+    /// The current smoothed value, if there have been any samples.
+    pub fn value(&self) -> Option<Vec3> {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Settings for a [`TiltSmoother`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TiltConfig {
+    /// How much of each new sample is used, like [`LowPassFilter::alpha`].
+    pub alpha: f32,
+    /// How far the Wiimote can be tilted (in radians) before the tilt stops
+    /// being zero.
+    ///
+    /// The tilt doesn't jump when it leaves the deadzone, since the deadzone
+    /// is subtracted from it.
+    pub deadzone: f32,
+    /// How far the magnitude of the acceleration can be from 1 G (in G)
+    /// before the sample is ignored, or `None` to use every sample.
+    ///
+    /// When the Wiimote is being moved quickly, e.g. shaken, the acceleration
+    /// no longer points towards the ground, so the tilt can't be measured.
+    pub max_deviation: Option<f32>,
+    /// How often [`crate::event::WiimoteEvent::Tilt`] is emitted at most.
+    pub interval: Duration,
+}
+
+impl Default for TiltConfig {
+    fn default() -> Self {
+        Self {
+            alpha: 0.2,
+            deadzone: 0.05,
+            max_deviation: Some(0.3),
+            interval: Duration::from_millis(16),
+        }
+    }
+}
+
+/// The tilt of the Wiimote, measured from gravity alone.
+///
+/// The angles are kept to the nearest milliradian, so that tilts can be
+/// compared exactly (which keeps [`crate::event::WiimoteEvent`] `Eq`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tilt {
+    roll_mrad: i16,
+    pitch_mrad: i16,
+}
+
+impl Tilt {
+    pub fn from_radians(roll: f32, pitch: f32) -> Self {
+        // `f32::round` isn't in `core`
+        let to_mrad = |radians: f32| {
+            let mrad = radians * 1000.0;
+            (if mrad >= 0.0 { mrad + 0.5 } else { mrad - 0.5 }) as i16
+        };
+
+        Self {
+            roll_mrad: to_mrad(roll),
+            pitch_mrad: to_mrad(pitch),
+        }
+    }
+
+    /// The rotation about the Wiimote's long (Y) axis, in radians.
+    pub fn roll(&self) -> f32 {
+        self.roll_mrad as f32 / 1000.0
+    }
+
+    /// The rotation about the Wiimote's X axis, in radians, where pointing up
+    /// is positive.
+    pub fn pitch(&self) -> f32 {
+        self.pitch_mrad as f32 / 1000.0
+    }
+}
+
+/// Turns calibrated accelerometer samples into a smooth [`Tilt`], e.g. for
+/// steering.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct TiltSmoother {
+    config: TiltConfig,
+    filter: LowPassFilter,
+}
+
+#[cfg(feature = "std")]
+impl TiltSmoother {
+    pub fn new(config: TiltConfig) -> Self {
+        Self {
+            config,
+            filter: LowPassFilter::new(config.alpha),
+        }
+    }
+
+    pub fn config(&self) -> &TiltConfig {
+        &self.config
+    }
+
+    /// Feed a calibrated acceleration (in G), returning the smoothed tilt.
+    ///
+    /// Returns `None` if the sample was ignored because the Wiimote is moving
+    /// too much.
+    pub fn update(&mut self, accel: Vec3) -> Option<Tilt> {
+        if let Some(max_deviation) = self.config.max_deviation {
+            if (accel.length() - 1.0).abs() > max_deviation {
+                return None;
+            }
+        }
+
+        let Vec3 { x, y, z } = self.filter.update(accel);
+        let deadzone = |angle: f32| {
+            let magnitude = (angle.abs() - self.config.deadzone).max(0.0);
+            magnitude.copysign(angle)
+        };
+
+        Some(Tilt::from_radians(
+            deadzone(x.atan2(z)),
+            deadzone(y.atan2(x.hypot(z))),
+        ))
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accel(x: u16, y: u16, z: u16) -> AccelData {
+        AccelData { x, y, z }
+    }
+
+    #[test]
+    fn accel_is_decoded_in_every_mode_that_has_it() {
+        for mode in ReportMode::ALL {
+            // Fill everything after the accelerometer data so that decoding
+            // from the wrong offset would be noticed
+            let mut report: Report = [0xa1, mode as u8, 0x00, 0x00, 0x80, 0x81, 0x82]
+                .into_iter()
+                .collect();
+            while report.len() < mode.report_length() {
+                report.push(0xee);
+            }
+
+            match mode {
+                ReportMode::CoreAccel
+                | ReportMode::CoreAccelIr12
+                | ReportMode::CoreAccelExt16
+                | ReportMode::CoreAccelIr10Ext6 => {
+                    assert_eq!(
+                        AccelData::try_from(&report),
+                        Ok(accel(0x200, 0x204, 0x208)),
+                        "{:?}",
+                        mode
+                    );
+                }
+                _ => assert_eq!(
+                    AccelData::try_from(&report),
+                    Err(DecodeError::UnexpectedId {
+                        expected_id: None,
+                        got_id: mode as u8,
+                    }),
+                    "{:?}",
+                    mode
+                ),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn rest_calibration_from_still_samples() {
+        let samples = [
+            accel(510, 500, 600),
+            accel(512, 502, 600),
+            accel(510, 500, 602),
+            accel(512, 502, 602),
+        ];
+        let cal = RestCalibration::from_samples(&samples).unwrap();
+
+        assert_eq!(cal.mean, Vec3::new(511.0, 501.0, 601.0));
+        assert_eq!(cal.noise, Vec3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn rest_calibration_rejects_movement() {
+        let samples = [accel(512, 512, 600), accel(540, 512, 600)];
+        assert!(matches!(
+            RestCalibration::from_samples(&samples),
+            Err(RestCalibrationError::Moved { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn rest_calibration_needs_samples() {
+        assert!(matches!(
+            RestCalibration::from_samples(&[]),
+            Err(RestCalibrationError::NoSamples)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    fn variance(values: &[f32]) -> f32 {
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn tilt_smoother_reduces_noise() {
+        let mut smoother = TiltSmoother::new(TiltConfig {
+            deadzone: 0.0,
+            max_deviation: None,
+            ..TiltConfig::default()
+        });
+
+        // Gravity with the Wiimote rolled slightly, plus jitter from a simple
+        // deterministic generator
+        let mut seed = 1u32;
+        let mut noise = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 16) as f32 / u16::MAX as f32 - 0.5) * 0.1
+        };
+        let mut raw = Vec::new();
+        let mut smoothed = Vec::new();
+        for _ in 0..200 {
+            let accel = Vec3::new(0.2 + noise(), noise(), 0.98 + noise());
+            raw.push(accel.x.atan2(accel.z));
+            smoothed.push(smoother.update(accel).unwrap().roll());
+        }
+
+        // Skip the filter settling at the start
+        let raw_variance = variance(&raw[50..]);
+        let smoothed_variance = variance(&smoothed[50..]);
+        assert!(smoothed_variance < raw_variance / 4.0);
+    }
+
+    #[test]
+    fn tilt_keeps_milliradians() {
+        let tilt = Tilt::from_radians(0.1234, -0.5);
+        assert_eq!(tilt.roll(), 0.123);
+        assert_eq!(tilt.pitch(), -0.5);
+        assert_eq!(tilt, Tilt::from_radians(0.1231, -0.5002));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn orientation_filter_stays_flat_at_rest() {
+        let mut filter = OrientationFilter::new(OrientationFilter::DEFAULT_BETA);
+        for _ in 0..100 {
+            filter.update(Vec3::new(0.0, 0.0, 1.0), None, Duration::from_millis(10));
+        }
+
+        let angles = filter.euler_angles();
+        assert!(angles.roll.abs() < 1e-3, "{angles:?}");
+        assert!(angles.pitch.abs() < 1e-3, "{angles:?}");
+        assert!(angles.yaw.abs() < 1e-3, "{angles:?}");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn orientation_filter_converges_on_the_tilt() {
+        let roll = 0.5f32;
+        let mut filter = OrientationFilter::new(OrientationFilter::DEFAULT_BETA);
+        for _ in 0..2000 {
+            let accel = Vec3::new(roll.sin(), 0.0, roll.cos());
+            filter.update(accel, None, Duration::from_millis(10));
+        }
+
+        let angles = filter.euler_angles();
+        assert!((angles.roll - roll).abs() < 0.01, "{angles:?}");
+        assert!(angles.pitch.abs() < 0.01, "{angles:?}");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn orientation_filter_tracks_yaw_with_a_gyroscope() {
+        let mut filter = OrientationFilter::new(OrientationFilter::DEFAULT_BETA);
+        // Turning at 1 rad/s for half a second
+        let gyro = Vec3::new(0.0, 0.0, 1.0);
+        for _ in 0..50 {
+            filter.update(
+                Vec3::new(0.0, 0.0, 1.0),
+                Some(gyro),
+                Duration::from_millis(10),
+            );
+        }
+
+        let angles = filter.euler_angles();
+        assert!((angles.yaw - 0.5).abs() < 0.01, "{angles:?}");
+        assert!(angles.roll.abs() < 0.01, "{angles:?}");
+    }
+
+    #[test]
+    fn linear_acceleration_of_a_still_wiimote() {
+        let cal = AccelCalibration::default();
+
+        // Lying flat, so gravity reads as +1G on Z
+        let flat = accel(512, 512, 616);
+        assert_eq!(flat.calibrated(&cal), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(flat.linear(&cal, &Quaternion::IDENTITY), Vec3::ZERO);
+
+        // Moving along X while lying flat
+        let moving = accel(564, 512, 616);
+        assert_eq!(
+            moving.linear(&cal, &Quaternion::IDENTITY),
+            Vec3::new(0.5, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn linear_acceleration_while_tilted() {
+        let cal = AccelCalibration::default();
+        // Rolled onto its side, so gravity reads as +1G on X
+        let on_side = accel(616, 512, 512);
+
+        let mut filter = OrientationFilter::default();
+        for _ in 0..2000 {
+            filter.update(on_side.calibrated(&cal), None, Duration::from_millis(10));
+        }
+
+        let linear = on_side.linear(&cal, &filter.orientation());
+        assert!(linear.length() < 0.01, "{linear:?}");
+    }
+
+    #[test]
+    fn low_pass_filter_smooths_towards_new_samples() {
+        let mut filter = LowPassFilter::new(0.25);
+        assert_eq!(filter.value(), None);
+
+        assert_eq!(filter.update(Vec3::ZERO), Vec3::ZERO);
+        assert_eq!(
+            filter.update(Vec3::new(4.0, 0.0, 0.0)),
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            filter.update(Vec3::new(4.0, 0.0, 0.0)),
+            Vec3::new(1.75, 0.0, 0.0)
+        );
+
+        filter.reset();
+        assert_eq!(filter.value(), None);
+    }
+}
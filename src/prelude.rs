@@ -0,0 +1,10 @@
+// The types that almost every program using the crate needs, so that they can
+// be imported with `use wiimote_rs::prelude::*`. Keep this list short: adding
+// to it is easy, but removing anything breaks every glob import.
+
+pub use crate::buttons::{Button, CoreButtons, DPad};
+pub use crate::event::WiimoteEvent;
+pub use crate::hid::DeviceInfo;
+pub use crate::manager::{ManagerEvent, WiimoteManager};
+pub use crate::protocol::{Led, ReportMode};
+pub use crate::wiimote::{Feedback, PlayerNumber, Wiimote, WiimoteConfig, WiimoteHandle};
@@ -0,0 +1,333 @@
+// The wire format of the Wiimote's reports, which doesn't depend on Windows
+// or on how the reports are sent. This and the decoders built on it
+// (`buttons`, `report`, `status`, `extension`, `calibration`, `motion` and
+// `ir`) only use `core`, so they can be reused with a different transport by
+// building without the `std` feature. Only their error types' implementations
+// of `std::error::Error` and the float maths (e.g. orientation and tilt) need
+// `std`.
+
+use arrayvec::ArrayVec;
+use bitflags::bitflags;
+
+use core::fmt;
+use core::ops::Range;
+
+pub const INPUT_REPORT: u8 = 0xa1;
+pub const OUTPUT_REPORT: u8 = 0xa2;
+
+// NOTE: This size includes the HID header
+pub const MAX_REPORT_LENGTH: usize = 23;
+
+pub type Report = ArrayVec<u8, MAX_REPORT_LENGTH>;
+
+#[repr(u8)]
+pub enum OutputReportID {
+    Rumble = 0x10,
+    Led = 0x11,
+    ReportMode = 0x12,
+    RequestStatus = 0x15,
+    WriteMemory = 0x16,
+    ReadMemory = 0x17,
+}
+
+impl From<OutputReportID> for u8 {
+    fn from(val: OutputReportID) -> Self {
+        val as u8
+    }
+}
+
+#[repr(u8)]
+pub enum InputReportID {
+    // Status = 0x20,
+    ReadMemoryData = 0x21,
+    Ack = 0x22,
+    CoreButtons = 0x30,
+}
+
+impl From<InputReportID> for u8 {
+    fn from(val: InputReportID) -> Self {
+        val as u8
+    }
+}
+
+/// The data reporting modes of the Wiimote, named after the data that is sent
+/// in each input report.
+///
+/// The mode is also the ID of the input reports that are sent in that mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ReportMode {
+    CoreButtons = 0x30,
+    CoreAccel = 0x31,
+    CoreExt8 = 0x32,
+    CoreAccelIr12 = 0x33,
+    CoreExt19 = 0x34,
+    CoreAccelExt16 = 0x35,
+    CoreIr10Ext9 = 0x36,
+    CoreAccelIr10Ext6 = 0x37,
+    ExtOnly21 = 0x3d,
+    /// The buttons, the accelerometer and the IR camera in the full format,
+    /// split across two reports (0x3e and 0x3f) that the Wiimote sends
+    /// alternately.
+    ///
+    /// Each half on its own only has the buttons, so the halves have to be
+    /// put back together with a [`crate::report::InterleavedDecoder`].
+    Interleaved = 0x3e,
+}
+
+impl ReportMode {
+    /// Every report mode, in order of report ID.
+    pub const ALL: [ReportMode; 10] = [
+        ReportMode::CoreButtons,
+        ReportMode::CoreAccel,
+        ReportMode::CoreExt8,
+        ReportMode::CoreAccelIr12,
+        ReportMode::CoreExt19,
+        ReportMode::CoreAccelExt16,
+        ReportMode::CoreIr10Ext9,
+        ReportMode::CoreAccelIr10Ext6,
+        ReportMode::ExtOnly21,
+        ReportMode::Interleaved,
+    ];
+
+    /// The ID of the second half of a report sent in
+    /// [`ReportMode::Interleaved`].
+    pub const INTERLEAVED_SECOND_ID: u8 = 0x3f;
+
+    /// The mode that an input report was sent in, or `None` if it isn't a
+    /// data report.
+    pub fn of_report(report: &Report) -> Option<Self> {
+        match report.as_slice() {
+            [INPUT_REPORT, Self::INTERLEAVED_SECOND_ID, ..] => Some(Self::Interleaved),
+            [INPUT_REPORT, id, ..] => Self::try_from(*id).ok(),
+            _ => None,
+        }
+    }
+
+    /// The length of the input reports sent in this mode, including the HID
+    /// header and the report ID.
+    pub fn report_length(self) -> usize {
+        match self {
+            Self::CoreButtons => 4,
+            Self::CoreAccel => 7,
+            Self::CoreExt8 => 12,
+            Self::CoreAccelIr12 => 19,
+            _ => MAX_REPORT_LENGTH,
+        }
+    }
+
+    // NOTE: The ranges below index into the whole report, including the HID
+    // header and the report ID.
+
+    /// Where the core buttons are in the report, if it has them.
+    pub fn buttons_range(self) -> Option<Range<usize>> {
+        match self {
+            Self::ExtOnly21 => None,
+            _ => Some(2..4),
+        }
+    }
+
+    /// Where the accelerometer data is in the report, if it has any.
+    ///
+    /// This is straight after the buttons in every mode that has it, whatever
+    /// else the mode includes. Note that the least significant bits of the
+    /// accelerometer data are packed into the button bytes.
+    pub fn accel_range(self) -> Option<Range<usize>> {
+        match self {
+            Self::CoreAccel
+            | Self::CoreAccelIr12
+            | Self::CoreAccelExt16
+            | Self::CoreAccelIr10Ext6 => Some(4..7),
+            _ => None,
+        }
+    }
+
+    /// Where the IR camera data is in the report, if it has any.
+    pub fn ir_range(self) -> Option<Range<usize>> {
+        match self {
+            Self::CoreAccelIr12 => Some(7..19),
+            Self::CoreIr10Ext9 => Some(4..14),
+            Self::CoreAccelIr10Ext6 => Some(7..17),
+            _ => None,
+        }
+    }
+
+    /// Where the extension data is in the report, if it has any.
+    pub fn ext_range(self) -> Option<Range<usize>> {
+        match self {
+            Self::CoreExt8 => Some(4..12),
+            Self::CoreExt19 => Some(4..23),
+            Self::CoreAccelExt16 => Some(7..23),
+            Self::CoreIr10Ext9 => Some(14..23),
+            Self::CoreAccelIr10Ext6 => Some(17..23),
+            Self::ExtOnly21 => Some(2..23),
+            _ => None,
+        }
+    }
+}
+
+impl From<ReportMode> for u8 {
+    fn from(val: ReportMode) -> Self {
+        val as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownReportMode(pub u8);
+
+impl fmt::Display for UnknownReportMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown report mode {:#04x}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownReportMode {}
+
+impl TryFrom<u8> for ReportMode {
+    type Error = UnknownReportMode;
+
+    fn try_from(val: u8) -> Result<Self, Self::Error> {
+        ReportMode::ALL
+            .into_iter()
+            .find(|&mode| mode as u8 == val)
+            .ok_or(UnknownReportMode(val))
+    }
+}
+
+bitflags! {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Led: u8 {
+        const LED_1 = 0x10;
+        const LED_2 = 0x20;
+        const LED_3 = 0x40;
+        const LED_4 = 0x80;
+    }
+}
+
+impl Led {
+    // NOTE: This is zero indexed
+    pub fn player(p: usize) -> Self {
+        match p {
+            0 => Led::LED_1,
+            1 => Led::LED_2,
+            2 => Led::LED_3,
+            3 => Led::LED_4,
+            4 => !Led::LED_1,
+            5 => !Led::LED_2,
+            6 => !Led::LED_3,
+            7 => !Led::LED_4,
+            _ => Led::all(),
+        }
+    }
+
+    /// The LEDs in a byte laid out like the LED report or the flags of a
+    /// status report, ignoring the other bits.
+    pub fn from_value(byte: u8) -> Self {
+        Led::from_bits_truncate(byte)
+    }
+
+    /// The (zero indexed) player that these LEDs show, i.e. the inverse of
+    /// [`Led::player`].
+    ///
+    /// Returns `None` if the LEDs don't show any player, e.g. when two LEDs
+    /// are lit, or every LED is.
+    pub fn player_of(&self) -> Option<usize> {
+        (0..8).find(|&p| Led::player(p) == *self)
+    }
+
+    /// Light `level` LEDs from left to right, e.g. to show the battery level.
+    ///
+    /// Levels above 4 light every LED.
+    pub fn bar(level: u8) -> Self {
+        let bits = match level {
+            0 => 0x00,
+            1 => 0x10,
+            2 => 0x30,
+            3 => 0x70,
+            _ => 0xf0,
+        };
+
+        Led::from_bits_truncate(bits)
+    }
+
+    /// Light as many LEDs as there are bits set in `value`, from left to
+    /// right like [`Led::bar`], e.g. to show how many flags are set.
+    pub fn from_bits_count(value: u8) -> Self {
+        Led::bar(value.count_ones() as u8)
+    }
+
+    /// Show the low 4 bits of `n` in binary, with the most significant bit on
+    /// the left (LED 1).
+    pub fn binary(n: u8) -> Self {
+        // LED 1 is the lowest bit of the LEDs, so reverse the nibble
+        let reversed = (n & 0x0f).reverse_bits();
+        Led::from_bits_truncate(reversed)
+    }
+}
+
+impl fmt::Display for Led {
+    /// Shows the LEDs from left to right, e.g. `1--4`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let leds = [Led::LED_1, Led::LED_2, Led::LED_3, Led::LED_4];
+        for (i, led) in leds.into_iter().enumerate() {
+            if self.contains(led) {
+                write!(f, "{}", i + 1)?;
+            } else {
+                write!(f, "-")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_round_trips() {
+        for p in 0..8 {
+            let leds = Led::player(p);
+            assert_eq!(leds.player_of(), Some(p));
+            assert_eq!(Led::from_value(leds.bits() | 0x0f), leds);
+        }
+
+        // The fallback for other players doesn't show any one player
+        assert_eq!(Led::player(8).player_of(), None);
+        assert_eq!((Led::LED_1 | Led::LED_2).player_of(), None);
+        assert_eq!(Led::empty().player_of(), None);
+    }
+
+    #[test]
+    fn bar_lights_leds_from_the_left() {
+        assert_eq!(Led::bar(0), Led::empty());
+        assert_eq!(Led::bar(1), Led::LED_1);
+        assert_eq!(Led::bar(2), Led::LED_1 | Led::LED_2);
+        assert_eq!(Led::bar(3), Led::LED_1 | Led::LED_2 | Led::LED_3);
+        assert_eq!(Led::bar(4), Led::all());
+        assert_eq!(Led::bar(5), Led::all());
+    }
+
+    #[test]
+    fn from_bits_count_lights_one_led_per_bit() {
+        assert_eq!(Led::from_bits_count(0), Led::empty());
+        assert_eq!(Led::from_bits_count(0b1000_0000), Led::LED_1);
+        assert_eq!(Led::from_bits_count(0b0101), Led::LED_1 | Led::LED_2);
+        assert_eq!(Led::from_bits_count(0b0111), Led::bar(3));
+        assert_eq!(Led::from_bits_count(0xff), Led::all());
+    }
+
+    #[test]
+    fn binary_shows_the_low_nibble() {
+        let patterns = [
+            0x00, 0x80, 0x40, 0xc0, 0x20, 0xa0, 0x60, 0xe0, 0x10, 0x90, 0x50, 0xd0, 0x30, 0xb0,
+            0x70, 0xf0,
+        ];
+        for (n, bits) in patterns.into_iter().enumerate() {
+            assert_eq!(Led::binary(n as u8), Led::from_bits_truncate(bits), "{n}");
+        }
+        assert_eq!(Led::binary(0x15), Led::binary(0x05));
+    }
+}
@@ -0,0 +1,257 @@
+// The recording format is a header, followed by one entry per report. Each
+// entry is the time since the recording started in microseconds (u64, little
+// endian), the length of the report (u8), and then the report itself.
+
+use crossbeam_channel::{unbounded, Receiver};
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::hid::{self, HidDevice, MockDevice};
+
+const HEADER: &[u8; 5] = b"WREC\x01";
+
+/// Records the reports read from a Wiimote, along with when they were read,
+/// so they can be replayed later by a [`ReportPlayer`].
+pub struct ReportRecorder<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl ReportRecorder<BufWriter<File>> {
+    /// Start a new recording in the file at `path`, replacing it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> ReportRecorder<W> {
+    /// Start a new recording, writing it to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(HEADER)?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// Add a report to the recording, timestamped with the current time.
+    pub fn record(&mut self, report: &hid::Report) -> io::Result<()> {
+        let micros = self.start.elapsed().as_micros() as u64;
+        self.writer.write_all(&micros.to_le_bytes())?;
+        self.writer.write_all(&[report.len() as u8])?;
+        self.writer.write_all(report)?;
+
+        Ok(())
+    }
+
+    /// Finish the recording, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write + Send + 'static> ReportRecorder<W> {
+    /// Record every report sent to `read_rx` on another thread, passing them
+    /// on to the returned receiver.
+    ///
+    /// The recording finishes once `read_rx` disconnects, i.e. when the
+    /// Wiimote disconnects, and the thread then returns the writer.
+    pub fn tap(
+        mut self,
+        read_rx: Receiver<hid::Report>,
+    ) -> (Receiver<hid::Report>, thread::JoinHandle<io::Result<W>>) {
+        let (tapped_tx, tapped_rx) = unbounded();
+
+        let thread_handle = thread::spawn(move || {
+            for report in read_rx.iter() {
+                self.record(&report)?;
+                // Keep recording even if nobody is listening
+                let _ = tapped_tx.send(report);
+            }
+
+            self.finish()
+        });
+
+        (tapped_rx, thread_handle)
+    }
+}
+
+/// Replays a recording made by a [`ReportRecorder`] as a [`HidDevice`].
+///
+/// The reports are fed through a [`MockDevice`] at the time they were
+/// recorded, so the writes can be inspected with [`ReportPlayer::mock`]. Once
+/// the recording runs out, reads fail as if the Wiimote had disconnected.
+pub struct ReportPlayer {
+    reports: Vec<(Duration, hid::Report)>,
+    next: usize,
+    speed: f32,
+    mock: MockDevice,
+    start: Option<Instant>,
+}
+
+impl ReportPlayer {
+    /// Load the recording in the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::load(BufReader::new(File::open(path)?))
+    }
+
+    /// Load a recording from `reader`.
+    pub fn load(mut reader: impl Read) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut header = [0; HEADER.len()];
+        reader.read_exact(&mut header)?;
+        if &header != HEADER {
+            return Err(invalid("Not a report recording"));
+        }
+
+        let mut reports = Vec::new();
+        loop {
+            let mut micros = [0; 8];
+            match reader.read_exact(&mut micros) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+
+            let mut len = [0];
+            reader.read_exact(&mut len)?;
+            let len = len[0] as usize;
+            if len > hid::MAX_REPORT_LENGTH {
+                return Err(invalid("Recorded report is too long"));
+            }
+
+            let mut buf = [0; hid::MAX_REPORT_LENGTH];
+            reader.read_exact(&mut buf[..len])?;
+            let report = hid::Report::try_from(&buf[..len]).unwrap();
+
+            let time = Duration::from_micros(u64::from_le_bytes(micros));
+            reports.push((time, report));
+        }
+
+        Ok(Self {
+            reports,
+            next: 0,
+            speed: 1.0,
+            mock: MockDevice::default(),
+            start: None,
+        })
+    }
+
+    /// Play the recording `speed` times faster than it was recorded, or as
+    /// fast as possible if `speed` is 0.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// The number of reports in the recording.
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    /// The device that the reports are fed through, which records any writes.
+    pub fn mock(&self) -> &MockDevice {
+        &self.mock
+    }
+}
+
+impl HidDevice for ReportPlayer {
+    /// Wait until the next report is due and return it.
+    ///
+    /// Like a real device, this returns an empty report if the next report
+    /// isn't due within the read timeout.
+    fn read(&mut self) -> hid::Result<hid::Report> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+
+        if let Some((time, report)) = self.reports.get(self.next) {
+            if self.speed > 0.0 {
+                let due = start + time.div_f32(self.speed);
+                let wait = due.saturating_duration_since(Instant::now());
+                if wait > hid::WIIMOTE_READ_TIMEOUT {
+                    thread::sleep(hid::WIIMOTE_READ_TIMEOUT);
+                    return Ok(hid::Report::new());
+                }
+                thread::sleep(wait);
+            }
+
+            self.mock.push_read(report.clone());
+            self.next += 1;
+        }
+
+        self.mock.read()
+    }
+
+    fn write(&mut self, buf: &[u8]) -> hid::Result<usize> {
+        self.mock.write(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hid::INPUT_REPORT;
+
+    fn report(bytes: &[u8]) -> hid::Report {
+        bytes.iter().copied().collect()
+    }
+
+    #[test]
+    fn reports_round_trip_through_a_recording() {
+        let reports = [
+            report(&[INPUT_REPORT, 0x30, 0x00, 0x08]),
+            report(&[INPUT_REPORT, 0x31, 0x00, 0x00, 0x80, 0x81, 0x82]),
+            report(&[INPUT_REPORT, 0x20, 0, 0, 0, 0, 0, 0xc8]),
+        ];
+
+        let mut recorder = ReportRecorder::new(Vec::new()).unwrap();
+        for report in &reports {
+            recorder.record(report).unwrap();
+        }
+        let recording = recorder.finish().unwrap();
+        assert!(recording.starts_with(HEADER));
+
+        let mut player = ReportPlayer::load(&recording[..]).unwrap().with_speed(0.0);
+        assert_eq!(player.len(), reports.len());
+        for report in &reports {
+            assert_eq!(&player.read().unwrap(), report);
+        }
+        // Then the Wiimote "disconnects"
+        assert_eq!(player.read(), Err(hid::Error::NotConnected));
+    }
+
+    #[test]
+    fn bad_recordings_are_invalid_data() {
+        let err = ReportPlayer::load(&b"WREC\x02"[..]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // One entry that claims to be longer than any report
+        let mut recording = HEADER.to_vec();
+        recording.extend(0u64.to_le_bytes());
+        recording.push(hid::MAX_REPORT_LENGTH as u8 + 1);
+        recording.extend([0; hid::MAX_REPORT_LENGTH + 1]);
+        let err = ReportPlayer::load(&recording[..]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn writes_show_up_in_the_mock() {
+        let recording = ReportRecorder::new(Vec::new()).unwrap().finish().unwrap();
+        let mut player = ReportPlayer::load(&recording[..]).unwrap();
+        assert!(player.is_empty());
+
+        let led = crate::report::led(crate::protocol::Led::LED_1);
+        player.write(&led).unwrap();
+        assert_eq!(player.mock().writes(), [led]);
+    }
+}
@@ -0,0 +1,879 @@
+// Every output report starts with OUTPUT_REPORT and the report ID, and bit 0
+// of the first byte after the ID is the rumble bit.
+
+use arrayvec::ArrayVec;
+
+use core::fmt;
+
+use crate::buttons::CoreButtons;
+use crate::ir::{IrDot, IrDotInfo};
+use crate::motion::AccelData;
+use crate::protocol::{
+    Led, OutputReportID, Report, ReportMode, INPUT_REPORT, MAX_REPORT_LENGTH, OUTPUT_REPORT,
+};
+
+/// The largest amount of data that fits into one register write.
+pub const MAX_WRITE_LENGTH: usize = 16;
+
+const RUMBLE_BIT: u8 = 0x01;
+const ACK_BIT: u8 = 0x02;
+const CONTINUOUS_BIT: u8 = 0x04;
+const EEPROM_SPACE: u8 = 0x00;
+const REGISTER_SPACE: u8 = 0x04;
+// The part of the EEPROM that can be read and written freely
+const EEPROM_LENGTH: u32 = 0x1700;
+// The high byte of each block of control registers: the speaker, the
+// extension, the MotionPlus, and the IR camera
+const REGISTER_BLOCKS: [u8; 4] = [0xa2, 0xa4, 0xa6, 0xb0];
+
+/// A memory access that the Wiimote wouldn't respond to properly, so it is
+/// rejected before anything is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressError {
+    Empty,
+    TooLong(usize),
+    OutOfEeprom { address: u32, size: usize },
+    UnknownRegister { address: u32, size: usize },
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Can't read or write zero bytes"),
+            Self::TooLong(size) => write!(
+                f,
+                "Can only write {MAX_WRITE_LENGTH} bytes at a time, not {size}"
+            ),
+            Self::OutOfEeprom { address, size } => write!(
+                f,
+                "{size} bytes at {address:#08x} aren't all in the EEPROM (0x0000-0x16ff)"
+            ),
+            Self::UnknownRegister { address, size } => write!(
+                f,
+                "{size} bytes at {address:#08x} aren't all in a known register block"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AddressError {}
+
+/// The ways that reading from or writing to the Wiimote's memory can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryError {
+    InvalidAddress(AddressError),
+    NotConnected,
+    TimedOut,
+    Wiimote { address: u32, error: u8 },
+}
+
+impl From<AddressError> for MemoryError {
+    fn from(e: AddressError) -> Self {
+        Self::InvalidAddress(e)
+    }
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidAddress(e) => e.fmt(f),
+            Self::NotConnected => {
+                write!(f, "The Wiimote disconnected before its memory was accessed")
+            }
+            Self::TimedOut => write!(f, "Timed out waiting for the Wiimote to respond"),
+            Self::Wiimote { address, error } => write!(
+                f,
+                "The Wiimote failed to access {address:#08x} (error {error:#x})"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MemoryError {}
+
+/// The ways that waiting for the Wiimote to acknowledge an output report can
+/// fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckError {
+    NotConnected,
+    TimedOut,
+    Wiimote { report_id: u8, error: u8 },
+}
+
+impl fmt::Display for AckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConnected => write!(
+                f,
+                "The Wiimote disconnected before the report was acknowledged"
+            ),
+            Self::TimedOut => write!(
+                f,
+                "Timed out waiting for the Wiimote to acknowledge the report"
+            ),
+            Self::Wiimote { report_id, error } => write!(
+                f,
+                "The Wiimote rejected output report {report_id:#04x} (error {error:#x})"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AckError {}
+
+fn check_eeprom(address: u32, size: usize) -> Result<(), AddressError> {
+    if size == 0 {
+        return Err(AddressError::Empty);
+    }
+
+    match address.checked_add(size as u32) {
+        Some(end) if end <= EEPROM_LENGTH => Ok(()),
+        _ => Err(AddressError::OutOfEeprom { address, size }),
+    }
+}
+
+fn check_register(address: u32, size: usize) -> Result<(), AddressError> {
+    if size == 0 {
+        return Err(AddressError::Empty);
+    }
+
+    // The whole range has to be inside one block
+    let [_, block, ..] = address.to_be_bytes();
+    let offset = address & 0xffff;
+    if address > 0xffffff || !REGISTER_BLOCKS.contains(&block) || offset + size as u32 > 0x10000 {
+        return Err(AddressError::UnknownRegister { address, size });
+    }
+
+    Ok(())
+}
+
+fn new_report(id: OutputReportID, data: &[u8]) -> Report {
+    let mut report = Report::new();
+    report.push(OUTPUT_REPORT);
+    report.push(id.into());
+    report.try_extend_from_slice(data).unwrap();
+
+    report
+}
+
+/// Turn the rumble motor of the Wiimote on or off, without changing anything
+/// else.
+pub fn rumble(rumble: bool) -> Report {
+    let flags = if rumble { RUMBLE_BIT } else { 0x00 };
+    new_report(OutputReportID::Rumble, &[flags])
+}
+
+/// Set the LEDs of the Wiimote.
+pub fn led(leds: Led) -> Report {
+    new_report(OutputReportID::Led, &[leds.bits()])
+}
+
+/// Set the reporting mode of the Wiimote.
+///
+/// If `continuous` is false, the Wiimote will only send a report when its data
+/// changes.
+pub fn report_mode(continuous: bool, mode: ReportMode) -> Report {
+    let flags = if continuous { CONTINUOUS_BIT } else { 0x00 };
+    new_report(OutputReportID::ReportMode, &[flags, mode.into()])
+}
+
+/// Request a status report from the Wiimote.
+pub fn request_status() -> Report {
+    new_report(OutputReportID::RequestStatus, &[0x00])
+}
+
+/// Write `data` to the Wiimote's control registers, starting at `address`.
+///
+/// Returns an error if `data` is longer than [`MAX_WRITE_LENGTH`] bytes, or
+/// if it wouldn't all be written to a known block of registers.
+pub fn register_write(address: u32, data: &[u8]) -> Result<Report, AddressError> {
+    if data.len() > MAX_WRITE_LENGTH {
+        return Err(AddressError::TooLong(data.len()));
+    }
+    check_register(address, data.len())?;
+
+    let [_, addr_hi, addr_mid, addr_lo] = address.to_be_bytes();
+    let mut payload = [0; MAX_WRITE_LENGTH];
+    payload[..data.len()].copy_from_slice(data);
+
+    let mut report = new_report(
+        OutputReportID::WriteMemory,
+        &[REGISTER_SPACE, addr_hi, addr_mid, addr_lo, data.len() as u8],
+    );
+    report.try_extend_from_slice(&payload).unwrap();
+
+    Ok(report)
+}
+
+fn memory_read(space: u8, address: u32, size: u16) -> Report {
+    let [_, addr_hi, addr_mid, addr_lo] = address.to_be_bytes();
+    let [size_hi, size_lo] = size.to_be_bytes();
+
+    new_report(
+        OutputReportID::ReadMemory,
+        &[space, addr_hi, addr_mid, addr_lo, size_hi, size_lo],
+    )
+}
+
+/// Read `size` bytes from the Wiimote's EEPROM, starting at `address`.
+///
+/// The data is sent back in 0x21 input reports, 16 bytes at a time. Returns
+/// an error if the data isn't all in the readable part of the EEPROM, as the
+/// Wiimote would never send it.
+pub fn eeprom_read(address: u32, size: u16) -> Result<Report, AddressError> {
+    check_eeprom(address, size as usize)?;
+    Ok(memory_read(EEPROM_SPACE, address, size))
+}
+
+/// Read `size` bytes from the Wiimote's control registers, starting at
+/// `address`.
+///
+/// The data is sent back in 0x21 input reports, 16 bytes at a time. Returns
+/// an error if the data isn't all in a known block of registers, as the
+/// Wiimote would never send it.
+pub fn register_read(address: u32, size: u16) -> Result<Report, AddressError> {
+    check_register(address, size as usize)?;
+    Ok(memory_read(REGISTER_SPACE, address, size))
+}
+
+/// Ask the Wiimote to acknowledge an output report with an 0x22 input report,
+/// which most reports don't get by default.
+pub fn request_ack(report: &mut Report) {
+    if let Some(flags) = report.get_mut(2) {
+        *flags |= ACK_BIT;
+    }
+}
+
+/// Set or clear the rumble bit of an output report.
+pub fn set_rumble(report: &mut Report, rumble: bool) {
+    if let Some(flags) = report.get_mut(2) {
+        if rumble {
+            *flags |= RUMBLE_BIT;
+        } else {
+            *flags &= !RUMBLE_BIT;
+        }
+    }
+}
+
+/// The ways that decoding an input report can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    Empty,
+    NotInputReport(u8),
+    /// The report has a different ID than expected.
+    ///
+    /// `expected_id` is `None` if several IDs would have been accepted, e.g.
+    /// every report mode that includes the accelerometer data.
+    UnexpectedId {
+        expected_id: Option<u8>,
+        got_id: u8,
+    },
+    TooShort {
+        expected: usize,
+        got: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The report is empty"),
+            Self::NotInputReport(header) => {
+                write!(f, "The report isn't an input report (header {header:#04x})")
+            }
+            Self::UnexpectedId {
+                expected_id: Some(expected_id),
+                got_id,
+            } => write!(
+                f,
+                "Expected report ID {expected_id:#04x}, but got {got_id:#04x}"
+            ),
+            Self::UnexpectedId {
+                expected_id: None,
+                got_id,
+            } => write!(f, "Unexpected report ID {got_id:#04x}"),
+            Self::TooShort { expected, got } => write!(
+                f,
+                "The report is {got} bytes long, but should be at least {expected}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Get the ID of an input report, checking that it is at least `min_length`
+/// bytes long (including the HID header and the ID).
+pub(crate) fn input_report_id(report: &Report, min_length: usize) -> Result<u8, DecodeError> {
+    let id = match report.as_slice() {
+        [] => return Err(DecodeError::Empty),
+        [INPUT_REPORT, id, ..] => *id,
+        [INPUT_REPORT] => {
+            return Err(DecodeError::TooShort {
+                expected: 2,
+                got: 1,
+            })
+        }
+        [header, ..] => return Err(DecodeError::NotInputReport(*header)),
+    };
+
+    if report.len() < min_length {
+        return Err(DecodeError::TooShort {
+            expected: min_length,
+            got: report.len(),
+        });
+    }
+
+    Ok(id)
+}
+
+/// Check that `report` is an input report with the ID `expected_id`, and that
+/// it is at least `min_length` bytes long (including the HID header and the
+/// ID).
+pub(crate) fn expect_input_report(
+    report: &Report,
+    expected_id: u8,
+    min_length: usize,
+) -> Result<(), DecodeError> {
+    // Check the ID before the length, since the length depends on the ID
+    let got_id = input_report_id(report, 2)?;
+    if got_id != expected_id {
+        return Err(DecodeError::UnexpectedId {
+            expected_id: Some(expected_id),
+            got_id,
+        });
+    }
+
+    input_report_id(report, min_length).map(|_| ())
+}
+
+/// A data read report (0x21), which carries up to 16 bytes of the memory that
+/// was requested with [`eeprom_read`] or [`register_read`].
+///
+/// Larger reads are split across several of these reports, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataReadReport {
+    pub buttons: CoreButtons,
+    /// The number of bytes of `data` that were read, from 1 to 16.
+    pub size: u8,
+    /// The error code, which is 0 if the read succeeded.
+    ///
+    /// 7 means the memory is write-only, and 8 means it doesn't exist.
+    pub error: u8,
+    /// The low 16 bits of the address of the first byte of `data`.
+    pub address_low: u16,
+    /// The bytes that were read, which is empty if there was an error.
+    pub data: ArrayVec<u8, 16>,
+}
+
+impl DataReadReport {
+    pub const ID: u8 = 0x21;
+    /// The length of a data read report, including the HID header and the ID.
+    pub const LENGTH: usize = 23;
+
+    /// Decode a data read report, returning `None` if the report isn't one.
+    pub fn from_report(report: &Report) -> Option<Self> {
+        Self::try_from(report).ok()
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.error != 0
+    }
+}
+
+impl TryFrom<&Report> for DataReadReport {
+    type Error = DecodeError;
+
+    fn try_from(report: &Report) -> Result<Self, Self::Error> {
+        expect_input_report(report, Self::ID, Self::LENGTH)?;
+
+        // The high nibble is the number of bytes minus one, and the low nibble
+        // is the error code
+        let size_error = report[4];
+        let size = (size_error >> 4) + 1;
+        let error = size_error & 0x0f;
+
+        let mut data = ArrayVec::new();
+        if error == 0 {
+            data.try_extend_from_slice(&report[7..7 + size as usize])
+                .unwrap();
+        }
+
+        Ok(Self {
+            buttons: CoreButtons::from_bytes([report[2], report[3]]),
+            size,
+            error,
+            address_low: u16::from_be_bytes([report[5], report[6]]),
+            data,
+        })
+    }
+}
+
+/// An input report sent in one of the reporting modes, split into the data
+/// that the mode includes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedReport {
+    pub mode: ReportMode,
+    /// The core buttons, which every mode has apart from
+    /// [`ReportMode::ExtOnly21`].
+    pub buttons: Option<CoreButtons>,
+    pub accel: Option<AccelData>,
+    /// The raw IR camera data, which is 10 or 12 bytes depending on the mode.
+    pub ir: Option<ArrayVec<u8, 12>>,
+    /// The raw extension data, which is between 6 and 21 bytes depending on
+    /// the mode.
+    pub extension: Option<ArrayVec<u8, 21>>,
+}
+
+impl DecodedReport {
+    /// Decode the four IR dots, in whichever format the mode sends them.
+    ///
+    /// Returns `None` if the mode doesn't include the IR data. Dots that the
+    /// camera can't see are `None`.
+    pub fn ir_dots(&self) -> Option<[Option<IrDot>; 4]> {
+        let ir = self.ir.as_ref()?;
+
+        match ir.len() {
+            // The basic format packs two dots into every 5 bytes
+            10 => {
+                let [dot_1, dot_2] = IrDot::from_basic(ir[..5].try_into().unwrap());
+                let [dot_3, dot_4] = IrDot::from_basic(ir[5..].try_into().unwrap());
+                Some([dot_1, dot_2, dot_3, dot_4])
+            }
+            12 => Some(
+                IrDotInfo::from_extended(ir[..].try_into().unwrap())
+                    .map(|info| info.map(|i| i.dot)),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&Report> for DecodedReport {
+    type Error = DecodeError;
+
+    fn try_from(report: &Report) -> Result<Self, Self::Error> {
+        let id = input_report_id(report, 2)?;
+        let mode = ReportMode::of_report(report).ok_or(DecodeError::UnexpectedId {
+            expected_id: None,
+            got_id: id,
+        })?;
+        input_report_id(report, mode.report_length())?;
+
+        let buttons = mode
+            .buttons_range()
+            .map(|range| CoreButtons::from_bytes([report[range.start], report[range.start + 1]]));
+        let accel = AccelData::from_mode(report, mode);
+        let ir = mode
+            .ir_range()
+            .map(|range| report[range].try_into().unwrap());
+        let extension = mode
+            .ext_range()
+            .map(|range| report[range].try_into().unwrap());
+
+        Ok(Self {
+            mode,
+            buttons,
+            accel,
+            ir,
+            extension,
+        })
+    }
+}
+
+/// The two halves of a report sent in [`ReportMode::Interleaved`], put back
+/// together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterleavedSample {
+    /// The core buttons, from the second half.
+    pub buttons: CoreButtons,
+    /// The accelerometer data, which only has 8 bits of precision in this
+    /// mode, so the two lowest bits are always zero.
+    pub accel: AccelData,
+    /// The raw IR camera data in the full format, which is 9 bytes per dot.
+    pub ir: [u8; 36],
+}
+
+/// Puts the halves of the reports sent in [`ReportMode::Interleaved`] back
+/// together.
+///
+/// The first half (0x3e) is held on to until the second half (0x3f) arrives.
+/// If a report is dropped, the halves are matched up again by their IDs, so
+/// a half without the other one is thrown away.
+#[derive(Debug, Clone, Default)]
+pub struct InterleavedDecoder {
+    first_half: Option<Report>,
+}
+
+impl InterleavedDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next report from the Wiimote, returning a sample if this was
+    /// the second half of one.
+    ///
+    /// Any other reports are ignored.
+    pub fn push(&mut self, report: &Report) -> Option<InterleavedSample> {
+        let id = input_report_id(report, MAX_REPORT_LENGTH).ok()?;
+
+        if id == ReportMode::Interleaved as u8 {
+            self.first_half = Some(report.clone());
+            None
+        } else if id == ReportMode::INTERLEAVED_SECOND_ID {
+            let first_half = self.first_half.take()?;
+            Some(Self::combine(&first_half, report))
+        } else {
+            None
+        }
+    }
+
+    /// Forget the first half that is waiting for its second half, if there
+    /// is one.
+    pub fn reset(&mut self) {
+        self.first_half = None;
+    }
+
+    fn combine(first: &Report, second: &Report) -> InterleavedSample {
+        // Z is split into bit pairs in the unused bits of the button bytes,
+        // with the first half holding the most significant four bits
+        let z_bits = |report: &Report| (report[3] >> 5 & 0b11) << 2 | (report[2] >> 5 & 0b11);
+        let z = z_bits(first) << 4 | z_bits(second);
+
+        let mut ir = [0; 36];
+        ir[..18].copy_from_slice(&first[5..23]);
+        ir[18..].copy_from_slice(&second[5..23]);
+
+        InterleavedSample {
+            buttons: CoreButtons::from_bytes([second[2], second[3]]),
+            accel: AccelData {
+                x: (first[4] as u16) << 2,
+                y: (second[4] as u16) << 2,
+                z: (z as u16) << 2,
+            },
+            ir,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StatusReport;
+
+    fn report(bytes: &[u8]) -> Report {
+        bytes.iter().copied().collect()
+    }
+
+    fn interleaved_half(id: u8, buttons: [u8; 2], accel: u8, ir: u8) -> Report {
+        let mut half = report(&[INPUT_REPORT, id, buttons[0], buttons[1], accel]);
+        half.extend([ir; 18]);
+        half
+    }
+
+    #[test]
+    fn interleaved_halves_make_one_sample() {
+        // Z is 0xab, split across the unused bits of the button bytes
+        let first = interleaved_half(0x3e, [0x40, 0x40], 0x10, 1);
+        let second = interleaved_half(0x3f, [0x60, 0x48], 0x20, 2);
+
+        let mut decoder = InterleavedDecoder::new();
+        assert_eq!(decoder.push(&first), None);
+        let sample = decoder.push(&second).unwrap();
+        assert_eq!(sample.buttons, CoreButtons::A);
+        assert_eq!(
+            sample.accel,
+            AccelData {
+                x: 0x40,
+                y: 0x80,
+                z: 0xab << 2
+            }
+        );
+        assert_eq!(sample.ir[..18], [1; 18]);
+        assert_eq!(sample.ir[18..], [2; 18]);
+
+        // The second half on its own is thrown away
+        assert_eq!(decoder.push(&second), None);
+    }
+
+    #[test]
+    fn interleaved_decoder_resyncs_after_a_dropped_half() {
+        let stale = interleaved_half(0x3e, [0, 0], 0x10, 1);
+        let first = interleaved_half(0x3e, [0, 0], 0x30, 3);
+        let second = interleaved_half(0x3f, [0, 0], 0x20, 2);
+
+        // The second half of `stale` was dropped
+        let mut decoder = InterleavedDecoder::new();
+        assert_eq!(decoder.push(&stale), None);
+        assert_eq!(decoder.push(&first), None);
+        let sample = decoder.push(&second).unwrap();
+        assert_eq!(sample.accel.x, 0x30 << 2);
+        assert_eq!(sample.ir[..18], [3; 18]);
+
+        // Other reports are ignored, and reset forgets a waiting half
+        assert_eq!(decoder.push(&first), None);
+        let buttons = report(&[INPUT_REPORT, 0x30, 0, 0]);
+        assert_eq!(decoder.push(&buttons), None);
+        decoder.reset();
+        assert_eq!(decoder.push(&second), None);
+    }
+    #[test]
+    fn output_report_bytes() {
+        assert_eq!(rumble(true).as_slice(), [OUTPUT_REPORT, 0x10, 0x01]);
+        assert_eq!(
+            led(Led::LED_1 | Led::LED_4).as_slice(),
+            [OUTPUT_REPORT, 0x11, 0x90]
+        );
+        assert_eq!(
+            report_mode(true, ReportMode::CoreAccel).as_slice(),
+            [OUTPUT_REPORT, 0x12, 0x04, 0x31]
+        );
+        assert_eq!(
+            report_mode(false, ReportMode::CoreButtons).as_slice(),
+            [OUTPUT_REPORT, 0x12, 0x00, 0x30]
+        );
+        assert_eq!(request_status().as_slice(), [OUTPUT_REPORT, 0x15, 0x00]);
+
+        let write = register_write(0xa400f0, &[0x55]).unwrap();
+        assert_eq!(write.len(), 23);
+        assert_eq!(
+            write[..8],
+            [OUTPUT_REPORT, 0x16, 0x04, 0xa4, 0x00, 0xf0, 0x01, 0x55]
+        );
+        assert!(write[8..].iter().all(|&b| b == 0));
+
+        assert_eq!(
+            eeprom_read(0x0016, 7).unwrap().as_slice(),
+            [OUTPUT_REPORT, 0x17, 0x00, 0x00, 0x00, 0x16, 0x00, 0x07]
+        );
+        assert_eq!(
+            register_read(0xa400fa, 6).unwrap().as_slice(),
+            [OUTPUT_REPORT, 0x17, 0x04, 0xa4, 0x00, 0xfa, 0x00, 0x06]
+        );
+    }
+
+    #[test]
+    fn memory_accesses_are_checked_at_the_boundaries() {
+        assert!(eeprom_read(0x0000, 0x1700).is_ok());
+        assert!(eeprom_read(0x16ff, 1).is_ok());
+        assert_eq!(
+            eeprom_read(0x16ff, 2),
+            Err(AddressError::OutOfEeprom {
+                address: 0x16ff,
+                size: 2,
+            })
+        );
+        assert_eq!(eeprom_read(0x0000, 0), Err(AddressError::Empty));
+
+        assert!(register_read(0xa4fff0, 16).is_ok());
+        assert_eq!(
+            register_read(0xa4fff0, 17),
+            Err(AddressError::UnknownRegister {
+                address: 0xa4fff0,
+                size: 17,
+            })
+        );
+        assert_eq!(
+            register_read(0xa30000, 1),
+            Err(AddressError::UnknownRegister {
+                address: 0xa30000,
+                size: 1,
+            })
+        );
+        assert_eq!(
+            register_read(0x01a40000, 1),
+            Err(AddressError::UnknownRegister {
+                address: 0x01a40000,
+                size: 1,
+            })
+        );
+
+        assert!(register_write(0xb00000, &[0; MAX_WRITE_LENGTH]).is_ok());
+        assert_eq!(
+            register_write(0xb00000, &[0; MAX_WRITE_LENGTH + 1]),
+            Err(AddressError::TooLong(MAX_WRITE_LENGTH + 1))
+        );
+        assert_eq!(register_write(0xb00000, &[]), Err(AddressError::Empty));
+    }
+
+    #[test]
+    fn flags_are_set_on_any_output_report() {
+        let mut report = led(Led::LED_1);
+        set_rumble(&mut report, true);
+        request_ack(&mut report);
+        assert_eq!(report.as_slice(), [OUTPUT_REPORT, 0x11, 0x13]);
+
+        set_rumble(&mut report, false);
+        assert_eq!(report.as_slice(), [OUTPUT_REPORT, 0x11, 0x12]);
+    }
+
+    #[test]
+    fn decodes_each_report_type() {
+        let status = report(&[INPUT_REPORT, 0x20, 0x00, 0x08, 0x10, 0, 0, 0x64]);
+        let status = StatusReport::try_from(&status).unwrap();
+        assert_eq!(status.buttons, CoreButtons::A);
+        assert_eq!(status.battery_level, 0x64);
+
+        let accel = report(&[INPUT_REPORT, 0x31, 0x00, 0x00, 0x80, 0x81, 0x82]);
+        let accel = AccelData::try_from(&accel).unwrap();
+        assert_eq!((accel.x, accel.y, accel.z), (0x200, 0x204, 0x208));
+
+        let buttons = report(&[INPUT_REPORT, 0x30, 0x00, 0x08]);
+        assert_eq!(CoreButtons::try_from(&buttons), Ok(CoreButtons::A));
+    }
+
+    #[test]
+    fn rejects_the_wrong_id() {
+        let buttons = report(&[INPUT_REPORT, 0x30, 0x00, 0x08, 0, 0, 0, 0]);
+        assert_eq!(
+            StatusReport::try_from(&buttons),
+            Err(DecodeError::UnexpectedId {
+                expected_id: Some(StatusReport::ID),
+                got_id: 0x30,
+            })
+        );
+        assert_eq!(
+            AccelData::try_from(&buttons),
+            Err(DecodeError::UnexpectedId {
+                expected_id: None,
+                got_id: 0x30,
+            })
+        );
+
+        let extension_only = report(&[INPUT_REPORT, 0x3d, 0, 0]);
+        assert_eq!(
+            CoreButtons::try_from(&extension_only),
+            Err(DecodeError::UnexpectedId {
+                expected_id: None,
+                got_id: 0x3d,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_short_and_malformed_reports() {
+        let short_status = report(&[INPUT_REPORT, 0x20, 0x00, 0x00]);
+        assert_eq!(
+            StatusReport::try_from(&short_status),
+            Err(DecodeError::TooShort {
+                expected: StatusReport::LENGTH,
+                got: 4,
+            })
+        );
+
+        let short_accel = report(&[INPUT_REPORT, 0x31, 0x00, 0x00, 0x80]);
+        assert_eq!(
+            AccelData::try_from(&short_accel),
+            Err(DecodeError::TooShort {
+                expected: 7,
+                got: 5,
+            })
+        );
+
+        assert_eq!(
+            CoreButtons::try_from(&report(&[INPUT_REPORT])),
+            Err(DecodeError::TooShort {
+                expected: 2,
+                got: 1,
+            })
+        );
+        assert_eq!(CoreButtons::try_from(&report(&[])), Err(DecodeError::Empty));
+        assert_eq!(
+            CoreButtons::try_from(&report(&[OUTPUT_REPORT, 0x30, 0, 0])),
+            Err(DecodeError::NotInputReport(OUTPUT_REPORT))
+        );
+    }
+
+    #[test]
+    fn data_read_report_success() {
+        // 6 bytes of the extension identifier read from 0xa400fa
+        let mut bytes = [0u8; DataReadReport::LENGTH];
+        bytes[..13].copy_from_slice(&[
+            INPUT_REPORT,
+            0x21,
+            0x00,
+            0x08,
+            0x50,
+            0x00,
+            0xfa,
+            0x00,
+            0x00,
+            0xa4,
+            0x20,
+            0x00,
+            0x00,
+        ]);
+        let read = DataReadReport::try_from(&report(&bytes)).unwrap();
+
+        assert_eq!(read.buttons, CoreButtons::A);
+        assert_eq!((read.size, read.error), (6, 0));
+        assert!(!read.is_error());
+        assert_eq!(read.address_low, 0x00fa);
+        assert_eq!(read.data.as_slice(), [0x00, 0x00, 0xa4, 0x20, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn data_read_report_error() {
+        // Reading write-only memory
+        let mut bytes = [0u8; DataReadReport::LENGTH];
+        bytes[..7].copy_from_slice(&[INPUT_REPORT, 0x21, 0x00, 0x00, 0xf7, 0x00, 0xf0]);
+        let read = DataReadReport::try_from(&report(&bytes)).unwrap();
+
+        assert!(read.is_error());
+        assert_eq!((read.size, read.error), (16, 7));
+        assert_eq!(read.address_low, 0x00f0);
+        assert!(read.data.is_empty());
+
+        assert_eq!(
+            DataReadReport::try_from(&report(&bytes[..10])),
+            Err(DecodeError::TooShort {
+                expected: DataReadReport::LENGTH,
+                got: 10,
+            })
+        );
+    }
+
+    #[test]
+    fn extension_only_report_has_no_buttons() {
+        let mut bytes = report(&[INPUT_REPORT, 0x3d]);
+        bytes.extend(1..=21);
+        let decoded = DecodedReport::try_from(&bytes).unwrap();
+
+        assert_eq!(decoded.mode, ReportMode::ExtOnly21);
+        assert_eq!(decoded.buttons, None);
+        assert_eq!(decoded.accel, None);
+        assert_eq!(decoded.ir, None);
+        assert!(decoded.extension.unwrap().into_iter().eq(1..=21));
+
+        assert_eq!(
+            DecodedReport::try_from(&report(&bytes[..22])),
+            Err(DecodeError::TooShort {
+                expected: 23,
+                got: 22,
+            })
+        );
+    }
+
+    #[test]
+    fn decoded_report_offsets() {
+        // Buttons, accelerometer, then 16 bytes of extension data
+        let mut bytes = report(&[INPUT_REPORT, 0x35, 0x00, 0x08, 0x80, 0x81, 0x82]);
+        bytes.extend(1..=16);
+        let decoded = DecodedReport::try_from(&bytes).unwrap();
+
+        assert_eq!(decoded.buttons, Some(CoreButtons::A));
+        assert_eq!(
+            decoded.accel,
+            Some(AccelData {
+                x: 0x200,
+                y: 0x204,
+                z: 0x208,
+            })
+        );
+        assert!(decoded.extension.unwrap().into_iter().eq(1..=16));
+    }
+}
@@ -1,71 +1,339 @@
-use std::collections::HashSet;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex, Weak};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use crossbeam_channel::Sender;
 
-use crate::bluetooth;
+use crate::bluetooth::{self, Address};
 use crate::hid;
-use crate::util;
+use crate::util::{self, Flag};
+
+// How long to wait before trying again if the Bluetooth or HID APIs fail
+const SCAN_RETRY_DELAY: Duration = Duration::from_millis(500);
+// How long to give an enabled Wiimote to show up as connected before enabling
+// it again. Enabling a Wiimote that is still connecting makes it fail.
+const ENABLE_COOLDOWN: Duration = Duration::from_secs(10);
+// How many passes in a row a remembered Wiimote has to be seen not connected
+// before it is removed. A Wiimote that is connecting looks the same for a
+// moment, and removing it then stops it from connecting.
+const REMOVE_AFTER_PASSES: u32 = 2;
 
 // XXX: use a thread::Builder
 // TODO: Start and stop wiimote scanning on demand
 
-pub struct WiimoteScanner {
+/// What the scanning thread is doing, so that problems connecting Wiimotes can
+/// be shown to the user.
+#[derive(Debug, Clone)]
+pub enum ScannerEvent {
+    /// A Wiimote was found over Bluetooth.
+    WiimoteFound(Address),
+    /// A Wiimote was enabled, so it should show up as a HID device soon.
+    Enabled(Address),
+    /// A Wiimote couldn't be enabled, e.g. because it is remembered by another
+    /// radio.
+    EnableFailed(Address, windows::core::Error),
+    /// A remembered Wiimote that wasn't connected was removed, so that it can
+    /// be paired again.
+    Removed(Address),
+    /// The Bluetooth radio couldn't be used, e.g. because it is turned off.
+    RadioUnavailable(windows::core::Error),
+}
+
+#[derive(Debug, Default)]
+struct ScanState {
     // Remember device paths so we don't try to connect to the same device twice
-    known_paths: Arc<Mutex<HashSet<String>>>,
-    thread_running: Arc<AtomicBool>,
+    known_paths: HashSet<String>,
+    // The wiimotes found by the last HID enumeration
+    current_devices: Vec<hid::DeviceInfo>,
+}
+
+/// A device path that the scanner won't send again until this is dropped,
+/// created with [`WiimoteScanner::known_path`].
+///
+/// Holding one of these for as long as a Wiimote is in use means the scanner
+/// can always find it again afterwards, however it stopped being used.
+#[derive(Debug)]
+pub struct KnownPath {
+    path: String,
+    state: Weak<Mutex<ScanState>>,
+}
+
+impl KnownPath {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Drop for KnownPath {
+    fn drop(&mut self) {
+        // There's nothing to forget if the scanner is gone
+        if let Some(state) = self.state.upgrade() {
+            let mut state = state.lock().unwrap();
+            state.known_paths.remove(&self.path);
+        }
+    }
+}
+
+// The settings that the scanning thread is started with
+struct ScanOptions {
+    issue_inquiry: bool,
+    radio_address: Option<Address>,
+    forget_after: Option<Duration>,
+    identifiers: Arc<[hid::WiimoteIdentifier]>,
+}
+
+// How many passes in a row each remembered Wiimote has been seen not connected
+#[derive(Debug, Default)]
+struct UnconnectedPasses(HashMap<Address, u32>);
+
+impl UnconnectedPasses {
+    // Count another pass that `address` wasn't connected in, returning whether
+    // it has been long enough to remove it
+    fn should_remove(&mut self, address: Address) -> bool {
+        let passes = self.0.entry(address).or_insert(0);
+        *passes += 1;
+        if *passes < REMOVE_AFTER_PASSES {
+            return false;
+        }
+
+        self.0.remove(&address);
+        true
+    }
+
+    fn reset(&mut self, address: Address) {
+        self.0.remove(&address);
+    }
+
+    // A pass that didn't see a Wiimote breaks its streak
+    fn end_pass(&mut self, seen: &HashSet<Address>) {
+        self.0.retain(|address, _| seen.contains(address));
+    }
+}
+
+pub struct WiimoteScanner {
+    state: Arc<Mutex<ScanState>>,
+    // Whether to search for new Bluetooth devices, or only look at the ones
+    // Windows already knows about
+    issue_inquiry: bool,
+    // The address of the radio to connect Wiimotes through, or `None` for the
+    // first one
+    radio_address: Option<Address>,
+    // Remove remembered Wiimotes that haven't been seen or used for this long,
+    // even when not issuing inquiries
+    forget_after: Option<Duration>,
+    event_tx: Option<Sender<ScannerEvent>>,
+    // What a Wiimote looks like, both over Bluetooth and as a HID device
+    identifiers: Arc<[hid::WiimoteIdentifier]>,
+    thread_running: Flag,
     thread_handle: Option<thread::JoinHandle<()>>,
 }
 
 impl WiimoteScanner {
     pub fn new() -> Self {
         Self {
-            known_paths: Arc::new(Mutex::new(HashSet::new())),
-            thread_running: Arc::new(AtomicBool::new(false)),
+            state: Arc::new(Mutex::new(ScanState::default())),
+            issue_inquiry: true,
+            radio_address: None,
+            forget_after: None,
+            event_tx: None,
+            identifiers: hid::KNOWN_WIIMOTES.into(),
+            thread_running: Flag::new(false),
             thread_handle: None,
         }
     }
 
-    pub fn start_thread(&mut self, device_tx: Sender<String>) {
-        if self.thread_running.load(Ordering::SeqCst) {
+    /// Create a scanner that never issues a Bluetooth inquiry, and so only
+    /// connects Wiimotes that have already been paired.
+    ///
+    /// Inquiries can interfere with other Bluetooth devices, e.g. by making
+    /// audio stutter. Paired Wiimotes are also never removed in this mode.
+    pub fn new_passive() -> Self {
+        let mut scanner = Self::new();
+        scanner.issue_inquiry = false;
+
+        scanner
+    }
+
+    /// Only connect Wiimotes through the Bluetooth radio with the given
+    /// address, instead of the first radio that is found.
+    pub fn with_radio(mut self, address: Address) -> Self {
+        self.radio_address = Some(address);
+        self
+    }
+
+    /// Remove remembered Wiimotes that aren't connected and haven't been seen
+    /// or used for `age`, so that they can be paired again.
+    ///
+    /// This is mostly useful with [`WiimoteScanner::new_passive`], which
+    /// otherwise never removes paired Wiimotes.
+    pub fn with_forget_after(mut self, age: Duration) -> Self {
+        self.forget_after = Some(age);
+        self
+    }
+
+    /// Send what the scanning thread is doing to `event_tx`, in addition to
+    /// sending the Wiimotes it finds to `device_tx`.
+    pub fn with_events(mut self, event_tx: Sender<ScannerEvent>) -> Self {
+        self.event_tx = Some(event_tx);
+        self
+    }
+
+    /// Only connect devices that match one of `identifiers`, instead of
+    /// [`hid::KNOWN_WIIMOTES`], e.g. to support third-party Wiimotes with an
+    /// unusual name, or to leave out the Wii U Pro Controller.
+    ///
+    /// Bluetooth devices are enabled if their name is the product string of
+    /// one of the identifiers.
+    pub fn with_identifiers(mut self, identifiers: Vec<hid::WiimoteIdentifier>) -> Self {
+        self.identifiers = identifiers.into();
+        self
+    }
+
+    /// The identifiers that devices are matched against.
+    pub fn identifiers(&self) -> &[hid::WiimoteIdentifier] {
+        &self.identifiers
+    }
+
+    pub fn start_thread(&mut self, device_tx: Sender<hid::DeviceInfo>) {
+        if self.thread_running.get() {
             return;
         }
-        self.thread_running.store(true, Ordering::SeqCst);
+        // A thread that was only told to stop may still be finishing
+        self.join_thread();
+        self.thread_running.set(true);
 
-        let known_paths_mutex = Arc::clone(&self.known_paths);
-        let is_running = Arc::clone(&self.thread_running);
-        let func = move || Self::scanning_thread(&is_running, &known_paths_mutex, device_tx);
+        let state_mutex = Arc::clone(&self.state);
+        let is_running = self.thread_running.cloned_handle();
+        let options = ScanOptions {
+            issue_inquiry: self.issue_inquiry,
+            radio_address: self.radio_address,
+            forget_after: self.forget_after,
+            identifiers: Arc::clone(&self.identifiers),
+        };
+        let event_tx = self.event_tx.clone();
+        let func =
+            move || Self::scanning_thread(&is_running, options, &state_mutex, device_tx, event_tx);
 
         self.thread_handle = Some(thread::spawn(func));
     }
 
+    /// Find the Wiimotes that are currently available as HID devices, without
+    /// starting the scanning thread.
+    ///
+    /// If `do_inquiry` is true, a Bluetooth inquiry is done first, and any
+    /// Wiimotes that aren't connected are enabled. This blocks for the whole
+    /// inquiry, and Wiimotes that were just enabled usually take a few seconds
+    /// to show up as HID devices, so they may only be found by a later scan.
+    /// Errors are logged, and the Wiimotes found so far are returned.
+    pub fn scan_once(&self, do_inquiry: bool) -> Vec<hid::DeviceInfo> {
+        if do_inquiry {
+            let bt_res = bluetooth::iter_devices(self.radio_address, true, |bt_device| {
+                if util::is_valid_device_name(bt_device.name(), &self.identifiers)
+                    && !bt_device.is_connected()
+                {
+                    match bt_device.enable() {
+                        Ok(_) => println!("[Bluetooth] Enabled Wiimote {}", bt_device.address()),
+                        Err(e) => eprintln!("[Bluetooth] Error enabling Wiimote: {e:?}"),
+                    }
+                }
+
+                ControlFlow::Continue(())
+            });
+
+            if let Err(e) = bt_res {
+                eprintln!("[Bluetooth] Error finding devices: {e:?}");
+            }
+        }
+
+        match hid::DeviceEnumerator::new() {
+            Ok(device_enumerator) => device_enumerator
+                .devices()
+                .filter(|d| d.is_wiimote_in(&self.identifiers))
+                .collect(),
+            Err(e) => {
+                eprintln!("[WiimoteScanner] Error enumerating HID devices: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Whether the scanning thread is running.
+    pub fn is_running(&self) -> bool {
+        self.thread_running.get()
+    }
+
+    /// Tell the scanning thread to stop, without waiting for it to finish.
+    pub(crate) fn signal_stop(&self) {
+        self.thread_running.set(false);
+    }
+
+    /// Stop the scanning thread and wait for it to finish.
+    ///
+    /// The scanning thread checks whether it should stop between each device,
+    /// so this will block for at most one Bluetooth inquiry.
     pub fn stop_thread(&mut self) {
-        if self.thread_running.load(Ordering::SeqCst) {
-            self.thread_running.store(false, Ordering::SeqCst);
+        self.thread_running.set(false);
+        self.join_thread();
+    }
 
-            self.thread_handle.take().unwrap().join().unwrap();
+    // `signal_stop` may have already cleared the flag, so this joins whenever
+    // there is a thread, running or not
+    fn join_thread(&mut self) {
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                println!("[WiimoteScanner] Thread panicked while stopping");
+            }
         }
     }
 
     fn scanning_thread(
-        is_running: &Arc<AtomicBool>,
-        known_paths_mutex: &Arc<Mutex<HashSet<String>>>,
-        device_tx: Sender<String>,
+        is_running: &Flag,
+        options: ScanOptions,
+        state_mutex: &Arc<Mutex<ScanState>>,
+        device_tx: Sender<hid::DeviceInfo>,
+        event_tx: Option<Sender<ScannerEvent>>,
     ) {
-        while is_running.load(Ordering::SeqCst) {
+        let ScanOptions {
+            issue_inquiry,
+            radio_address,
+            forget_after,
+            identifiers,
+        } = options;
+        let send_event = |event| {
+            if let Some(event_tx) = &event_tx {
+                // Nobody may be listening anymore, which is fine
+                let _ = event_tx.send(event);
+            }
+        };
+
+        // When each Wiimote was last enabled, since the Bluetooth API still
+        // reports Wiimotes that are connecting as not connected
+        let mut enabled_at: HashMap<Address, Instant> = HashMap::new();
+        let mut unconnected_passes = UnconnectedPasses::default();
+
+        while is_running.get() {
+            let mut seen = HashSet::new();
             println!("[WiimoteScanner] Updating bluetooth devices...");
             // Scan for bluetooth devices, then enable new wiimotes and remove disconnected wiimotes
-            bluetooth::iter_devices(true, |bt_device| {
+            let bt_res = bluetooth::iter_devices(radio_address, issue_inquiry, |bt_device| {
+                // The inquiry can't be interrupted, but we can avoid
+                // processing the rest of the devices
+                if !is_running.get() {
+                    return ControlFlow::Break(());
+                }
+
                 println!(
                     "[Bluetooth] Found \"{}\" ({})",
                     bt_device.name(),
                     bt_device.address(),
                 );
 
-                if util::is_valid_device_name(bt_device.name()) {
+                if util::is_valid_device_name(bt_device.name(), &identifiers) {
                     let wiimote = bt_device;
+                    seen.insert(wiimote.address());
+                    send_event(ScannerEvent::WiimoteFound(wiimote.address()));
 
                     println!(
                         "[Bluetooth] Wiimote detected - Authenticated: {}, Connected: {}, Remembered: {}",
@@ -74,8 +342,31 @@ impl WiimoteScanner {
                         wiimote.is_remembered()
                     );
 
-                    // Disable and remove any remembered devices that aren't connected
-                    if wiimote.is_remembered() && !wiimote.is_connected() {
+                    // Disable and remove any remembered devices that aren't connected,
+                    // unless we're relying on them being remembered and they
+                    // aren't stale
+                    let age = wiimote
+                        .last_seen()
+                        .max(wiimote.last_used())
+                        .and_then(|time| SystemTime::now().duration_since(time).ok());
+                    let is_stale = matches!(
+                        (forget_after, age),
+                        (Some(forget_after), Some(age)) if age >= forget_after
+                    );
+                    let recently_enabled = matches!(
+                        enabled_at.get(&wiimote.address()),
+                        Some(time) if time.elapsed() < ENABLE_COOLDOWN
+                    );
+                    if (issue_inquiry || is_stale)
+                        && wiimote.is_remembered()
+                        && !wiimote.is_connected()
+                        && !recently_enabled
+                    {
+                        if !unconnected_passes.should_remove(wiimote.address()) {
+                            // It might be connecting, so check it again next time
+                            return ControlFlow::Continue(());
+                        }
+
                         // XXX: This probably isn't needed
                         // match wiimote.disable_device() {
                         //     Ok(_) => println!("[Bluetooth] Disabled Wiimote {}", wiimote.address()),
@@ -84,50 +375,140 @@ impl WiimoteScanner {
 
                         wiimote.remove();
                         println!("[Bluetooth] Removed Wiimote {}", wiimote.address());
+                        send_event(ScannerEvent::Removed(wiimote.address()));
 
-                        return;
+                        return ControlFlow::Continue(());
                     }
 
+                    unconnected_passes.reset(wiimote.address());
+
                     // Ignore any currently connected wiimotes
                     if wiimote.is_connected() {
-                        return;
+                        enabled_at.remove(&wiimote.address());
+                        return ControlFlow::Continue(());
+                    }
+
+                    // Give recently enabled wiimotes time to connect
+                    if let Some(enabled_at) = enabled_at.get(&wiimote.address()) {
+                        if enabled_at.elapsed() < ENABLE_COOLDOWN {
+                            return ControlFlow::Continue(());
+                        }
                     }
 
-                    // Wiimotes at this point are not remembered or connected - so enable them
+                    // Wiimotes at this point are not connected - so enable them
                     match wiimote.enable() {
-                        Ok(_) => println!("[Bluetooth] Enabled Wiimote {}", wiimote.address()),
-                        Err(e) => eprintln!("[Bluetooth] Error enabling Wiimote: {e:?}"),
+                        Ok(_) => {
+                            println!("[Bluetooth] Enabled Wiimote {}", wiimote.address());
+                            enabled_at.insert(wiimote.address(), Instant::now());
+                            send_event(ScannerEvent::Enabled(wiimote.address()));
+                        }
+                        Err(e) => {
+                            eprintln!("[Bluetooth] Error enabling Wiimote: {e:?}");
+                            send_event(ScannerEvent::EnableFailed(wiimote.address(), e));
+                        }
                     }
                 }
+
+                ControlFlow::Continue(())
             });
 
+            unconnected_passes.end_pass(&seen);
+
+            if let Err(e) = bt_res {
+                // The bluetooth stack may come back, e.g. after the radio is
+                // turned back on, so keep trying
+                eprintln!("[Bluetooth] Error finding devices: {e:?}");
+                send_event(ScannerEvent::RadioUnavailable(e));
+                thread::sleep(SCAN_RETRY_DELAY);
+            }
+
+            if !is_running.get() {
+                break;
+            }
+
             println!("[WiimoteScanner] Finding HID devices...");
             {
-                let mut known_paths = known_paths_mutex.lock().unwrap();
-                let device_enumerator = hid::DeviceEnumerator::new();
+                let device_enumerator = match hid::DeviceEnumerator::new() {
+                    Ok(device_enumerator) => device_enumerator,
+                    Err(e) => {
+                        eprintln!("[WiimoteScanner] Error enumerating HID devices: {e}");
+                        thread::sleep(SCAN_RETRY_DELAY);
+                        continue;
+                    }
+                };
+                let mut state = state_mutex.lock().unwrap();
 
-                for device_info in device_enumerator.devices().filter(|d| d.is_wiimote()) {
-                    let device_path = device_info.path;
+                state.current_devices = device_enumerator
+                    .devices()
+                    .filter(|d| d.is_wiimote_in(&identifiers))
+                    .collect();
+
+                let ScanState {
+                    known_paths,
+                    current_devices,
+                } = &mut *state;
+
+                for device_info in current_devices.iter() {
                     // Ignore any currently connected (known) wiimotes
-                    if known_paths.contains(&device_path) {
+                    if known_paths.contains(&device_info.path) {
                         continue;
                     }
 
-                    // Send the device path and remember this wiimote
-                    device_tx.send(device_path.clone());
-                    known_paths.insert(device_path);
+                    // Remember this wiimote and send its info
+                    known_paths.insert(device_info.path.clone());
+                    if device_tx.send(device_info.clone()).is_err() {
+                        // Nobody is listening for Wiimotes anymore
+                        println!("[WiimoteScanner] Receiver dropped, stopping");
+                        is_running.set(false);
+                        break;
+                    }
                     // println!("[WiimoteScanner] known_paths: {known_paths:?}");
                 }
             }
+
+            // Without an inquiry to wait for, a pass finishes straight away
+            if !issue_inquiry && is_running.get() {
+                thread::sleep(SCAN_RETRY_DELAY);
+            }
         }
 
         // TODO: Disconnect/Power off wiimotes here (could be done on drop?)
         println!("[WiimoteScanner] Thread stopped");
     }
 
+    /// Stop the scanner from sending `path` until the returned [`KnownPath`]
+    /// is dropped.
+    ///
+    /// Paths are already known once they have been sent to `device_tx`, so
+    /// this is only needed to control when they are forgotten.
+    pub fn known_path(&self, path: String) -> KnownPath {
+        let mut state = self.state.lock().unwrap();
+        state.known_paths.insert(path.clone());
+
+        KnownPath {
+            path,
+            state: Arc::downgrade(&self.state),
+        }
+    }
+
     pub fn forget_device_path(&self, path: &str) {
-        let mut known_ids = self.known_paths.lock().unwrap();
-        known_ids.remove(path);
+        let mut state = self.state.lock().unwrap();
+        state.known_paths.remove(path);
+    }
+
+    /// The Wiimotes that were found the last time the scanning thread looked
+    /// for HID devices.
+    ///
+    /// This includes Wiimotes that have already been sent to `device_tx`.
+    pub fn current_devices(&self) -> Vec<hid::DeviceInfo> {
+        let state = self.state.lock().unwrap();
+        state.current_devices.clone()
+    }
+}
+
+impl Default for WiimoteScanner {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -136,3 +517,53 @@ impl Drop for WiimoteScanner {
         self.stop_thread();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_known(scanner: &WiimoteScanner, path: &str) -> bool {
+        scanner.state.lock().unwrap().known_paths.contains(path)
+    }
+
+    #[test]
+    fn dropping_a_known_path_forgets_it() {
+        let scanner = WiimoteScanner::new();
+        let first = scanner.known_path("first".to_string());
+        let second = scanner.known_path("second".to_string());
+        assert!(is_known(&scanner, "first") && is_known(&scanner, "second"));
+
+        // Tearing down a slot, however that happens, lets the scanner find its
+        // Wiimote again
+        drop(first);
+        assert!(!is_known(&scanner, "first"));
+        assert!(is_known(&scanner, "second"));
+
+        // The scanner may be gone by the time the last slot is torn down
+        drop(scanner);
+        drop(second);
+    }
+
+    #[test]
+    fn transiently_unconnected_wiimotes_are_not_removed() {
+        let connecting = Address::from_bytes([1, 0, 0, 0, 0, 0]);
+        let gone = Address::from_bytes([2, 0, 0, 0, 0, 0]);
+        let mut passes = UnconnectedPasses::default();
+
+        // Seen not connected for one pass while connecting, then connected
+        assert!(!passes.should_remove(connecting));
+        passes.reset(connecting);
+        assert!(!passes.should_remove(connecting));
+
+        // Not seen at all for a pass in between
+        assert!(!passes.should_remove(gone));
+        passes.end_pass(&HashSet::from([connecting]));
+        assert!(!passes.should_remove(gone));
+
+        // Only removed once it stays not connected
+        assert!(passes.should_remove(gone));
+        assert!(passes.should_remove(connecting));
+        // The streak starts over after being removed
+        assert!(!passes.should_remove(gone));
+    }
+}
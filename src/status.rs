@@ -0,0 +1,86 @@
+use core::fmt;
+
+use crate::buttons::CoreButtons;
+use crate::protocol::{Led, Report, INPUT_REPORT};
+use crate::report::{self, DecodeError};
+
+// The battery level reported by a Wiimote with fresh batteries
+const BATTERY_LEVEL_FULL: u8 = 0xc8;
+
+/// The status report (0x20) sent by the Wiimote in response to a status
+/// request, or when an extension is connected or disconnected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusReport {
+    pub buttons: CoreButtons,
+    pub battery_low: bool,
+    pub extension_connected: bool,
+    pub speaker_enabled: bool,
+    pub ir_enabled: bool,
+    pub leds: Led,
+    /// The raw battery level, which is around 0xc8 when the batteries are
+    /// full.
+    pub battery_level: u8,
+}
+
+impl StatusReport {
+    pub const ID: u8 = 0x20;
+    /// The length of a status report, including the HID header and the ID.
+    pub const LENGTH: usize = 8;
+
+    /// Decode a status report, returning `None` if the report isn't one.
+    pub fn from_report(report: &Report) -> Option<Self> {
+        match report.as_slice() {
+            [INPUT_REPORT, Self::ID, b1, b2, flags, _, _, battery_level, ..] => Some(Self {
+                buttons: CoreButtons::from_bytes([*b1, *b2]),
+                battery_low: flags & 0x01 != 0,
+                extension_connected: flags & 0x02 != 0,
+                speaker_enabled: flags & 0x04 != 0,
+                ir_enabled: flags & 0x08 != 0,
+                leds: Led::from_bits_truncate(*flags),
+                battery_level: *battery_level,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The battery level as a rough percentage.
+    pub fn battery_percent(&self) -> u8 {
+        let percent = self.battery_level as u32 * 100 / BATTERY_LEVEL_FULL as u32;
+        percent.min(100) as u8
+    }
+}
+
+impl TryFrom<&Report> for StatusReport {
+    type Error = DecodeError;
+
+    fn try_from(report: &Report) -> Result<Self, Self::Error> {
+        report::expect_input_report(report, Self::ID, Self::LENGTH)?;
+
+        Ok(Self::from_report(report).unwrap())
+    }
+}
+
+impl fmt::Display for StatusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on_off = |enabled| if enabled { "on" } else { "off" };
+
+        write!(f, "Battery {}%", self.battery_percent())?;
+        if self.battery_low {
+            write!(f, " (low)")?;
+        }
+        write!(
+            f,
+            ", LEDs {}, extension {}, speaker {}, IR {}, buttons [{}]",
+            self.leds,
+            if self.extension_connected {
+                "connected"
+            } else {
+                "disconnected"
+            },
+            on_off(self.speaker_enabled),
+            on_off(self.ir_enabled),
+            self.buttons
+        )
+    }
+}
@@ -1,9 +1,18 @@
 use widestring::U16CString;
 
-// TODO: Thread-safe Flag?
+use crate::hid::WiimoteIdentifier;
 
-pub fn is_valid_device_name(name: &str) -> bool {
-    name == "Nintendo RVL-CNT-01" || name == "Nintendo RVL-CNT-01-TR"
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+pub const WIIMOTE_NAME: &str = "Nintendo RVL-CNT-01";
+pub const WIIMOTE_TR_NAME: &str = "Nintendo RVL-CNT-01-TR";
+pub const PRO_CONTROLLER_NAME: &str = "Nintendo RVL-CNT-01-UC";
+
+pub fn is_valid_device_name(name: &str, identifiers: &[WiimoteIdentifier]) -> bool {
+    identifiers
+        .iter()
+        .any(|identifier| identifier.matches_name(name))
 }
 
 /// Lossily converts a nul-terminated UTF-16 String buffer into a [`String`].
@@ -15,3 +24,81 @@ pub unsafe fn wstring_to_utf8(buf: &[u16]) -> String {
     // XXX: Check if nul-terminated and return a Result
     U16CString::from_ptr_str(buf.as_ptr()).to_string_lossy()
 }
+
+/// A boolean flag that can be shared between threads.
+#[derive(Debug, Default)]
+pub struct Flag(Arc<AtomicBool>);
+
+impl Flag {
+    pub fn new(value: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(value)))
+    }
+
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, value: bool) {
+        self.0.store(value, Ordering::SeqCst);
+    }
+
+    /// Create another handle to the same flag, e.g. to move into a thread.
+    pub fn cloned_handle(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+/// An `f32` that can be shared between threads.
+#[derive(Debug, Default)]
+pub struct SharedF32(Arc<AtomicU32>);
+
+impl SharedF32 {
+    pub fn new(value: f32) -> Self {
+        Self(Arc::new(AtomicU32::new(value.to_bits())))
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::SeqCst))
+    }
+
+    pub fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::SeqCst);
+    }
+
+    /// Create another handle to the same value, e.g. to move into a thread.
+    pub fn cloned_handle(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn flag_handles_share_the_value() {
+        let flag = Flag::new(false);
+        let handle = flag.cloned_handle();
+
+        handle.set(true);
+        assert!(flag.get());
+        flag.set(false);
+        assert!(!handle.get());
+    }
+
+    #[test]
+    fn flag_stops_another_thread() {
+        let is_running = Flag::new(true);
+        let thread_is_running = is_running.cloned_handle();
+        let thread = thread::spawn(move || {
+            while thread_is_running.get() {
+                thread::yield_now();
+            }
+        });
+
+        is_running.set(false);
+        thread.join().unwrap();
+    }
+}
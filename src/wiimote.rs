@@ -1,209 +1,2611 @@
-use bitflags::bitflags;
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::hid::{self, OUTPUT_REPORT};
+use crate::bluetooth::{self, Address};
+use crate::buttons::CoreButtons;
+use crate::calibration::Calibration;
+use crate::event::{BatteryWarning, ButtonRemap, EventDecoder, WiimoteEvent};
+use crate::extension::{MotionPlusMode, EXTENSION_INIT_WRITES};
+use crate::gesture::GestureConfig;
+use crate::hid::{self, HidDevice, WiimoteKind, INPUT_REPORT};
+use crate::motion::{
+    AccelCalibration, AccelData, RestCalibration, RestCalibrationError, TiltConfig,
+};
+use crate::report::{self, AckError, DecodedReport, MemoryError};
+use crate::status::StatusReport;
+use crate::util::{Flag, SharedF32};
+
+pub use crate::protocol::{InputReportID, Led, OutputReportID, ReportMode, UnknownReportMode};
 
 const RUMBLE_ON_CONNECT: bool = true;
 const RUMBLE_DURATION: Duration = Duration::from_millis(250);
+// How often the LEDs are updated while a connection animation is playing
+const LED_FRAME_INTERVAL: Duration = Duration::from_millis(50);
+// How long to wait for the Wiimote to acknowledge a report in a sequence
+const ACK_TIMEOUT: Duration = Duration::from_millis(1000);
+// How often the report rate is recalculated
+const REPORT_RATE_WINDOW: Duration = Duration::from_secs(1);
+// How long a paused thread waits for a command before checking whether it
+// should resume or stop, in case it misses being woken
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+// The most reports written between two reads, so that a flood of writes can't
+// stop reports from being read
+const MAX_WRITES_PER_READ: usize = 8;
+// How long after initializing an extension to ignore it being connected
+// again, since activating the MotionPlus makes it look like it was reconnected
+const EXTENSION_REINIT_COOLDOWN: Duration = Duration::from_secs(1);
+// How often the watchdog checks whether the thread is still making progress
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(250);
+// How many times `Wiimote::connect_by_path` tries opening the HID device, and
+// how long it waits in between
+const CONNECT_ATTEMPTS: u32 = 3;
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(250);
 
 // TODO: Error enum for read/write/prepare errors
 
-#[repr(u8)]
-pub enum OutputReportID {
-    // Rumble = 0x10,
-    Led = 0x11,
-    ReportMode = 0x12,
-    RequestStatus = 0x15,
+/// A zero indexed player number, so `PlayerNumber(0)` is player 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlayerNumber(pub usize);
+
+impl PlayerNumber {
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    /// The LEDs that show this player number, see [`Led::player`].
+    pub fn leds(self) -> Led {
+        Led::player(self.0)
+    }
+}
+
+impl fmt::Display for PlayerNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "P{}", self.0 + 1)
+    }
+}
+
+/// An LED pattern that is played while a Wiimote is connecting.
+#[derive(Debug, Clone, Copy)]
+pub enum LedAnimation {
+    /// A single LED sweeping back and forth.
+    KnightRider,
+    /// All of the LEDs blinking together.
+    Blink,
+    /// The LEDs filling up from left to right, then emptying.
+    Fill,
+    /// A custom animation, given the time since it started.
+    Custom(fn(Duration) -> Led),
+}
+
+impl LedAnimation {
+    /// The LEDs to show at `elapsed` into the animation.
+    pub fn frame(&self, elapsed: Duration) -> Led {
+        let step = |length: u128| elapsed.as_millis() / length;
+
+        match self {
+            Self::KnightRider => match step(100) % 6 {
+                0 => Led::LED_1,
+                1 | 5 => Led::LED_2,
+                2 | 4 => Led::LED_3,
+                _ => Led::LED_4,
+            },
+            Self::Blink => {
+                if step(250) % 2 == 0 {
+                    Led::all()
+                } else {
+                    Led::empty()
+                }
+            }
+            Self::Fill => match step(150) % 5 {
+                0 => Led::empty(),
+                1 => Led::LED_1,
+                2 => Led::LED_1 | Led::LED_2,
+                3 => Led::LED_1 | Led::LED_2 | Led::LED_3,
+                _ => Led::all(),
+            },
+            Self::Custom(f) => f(elapsed),
+        }
+    }
+}
+
+/// Settings for how a Wiimote is initialized when it connects.
+#[derive(Debug, Clone)]
+pub struct WiimoteConfig {
+    /// Whether the Wiimote should rumble when it connects.
+    pub rumble_on_connect: bool,
+    /// How long the Wiimote should rumble for when it connects.
+    pub rumble_duration: Duration,
+    /// The reporting mode to set when the Wiimote connects.
+    pub initial_report_mode: ReportMode,
+    /// The LEDs to turn on when the Wiimote connects, or `None` to show the
+    /// player number.
+    pub initial_leds: Option<Led>,
+    /// How to recover a Wiimote that stops sending reports, or `None` to never
+    /// try.
+    pub stall_recovery: Option<StallRecovery>,
+    /// Whether to drop [`WiimoteEvent::ButtonsResent`] events, so that only
+    /// actual changes to the buttons are emitted.
+    ///
+    /// The Wiimote can resend the same buttons many times, even when it isn't
+    /// in continuous reporting mode. The raw reports are never filtered.
+    pub dedup_button_reports: bool,
+    /// The mode to activate the MotionPlus in when the Wiimote connects, or
+    /// `None` to leave it inactive.
+    pub motion_plus: Option<MotionPlusMode>,
+    /// Whether to activate the built-in MotionPlus of an RVL-CNT-01-TR in
+    /// [`MotionPlusMode::NunchukPassthrough`] when `motion_plus` is `None`, so
+    /// that a Nunchuk and the MotionPlus can be used at the same time.
+    ///
+    /// This is off by default, since the passthrough mode changes the layout
+    /// of the extension data and halves how often each device is reported.
+    pub auto_motion_plus: bool,
+    /// The animation to play on the LEDs while the Wiimote is connecting, or
+    /// `None` to go straight to the final LEDs.
+    pub connect_animation: Option<LedAnimation>,
+    /// How long to play `connect_animation` for.
+    pub connect_animation_duration: Duration,
+    /// How long the Wiimote's thread can stop making progress for, on top of
+    /// a read timeout, before it is disconnected, or `None` to never check.
+    ///
+    /// This makes sure that a read which never completes can't stop the
+    /// Wiimote from being dropped. Connecting and reading memory block the
+    /// thread for a while, so this shouldn't be too short.
+    pub watchdog_grace: Option<Duration>,
+    /// How to detect gestures from the accelerometer, or `None` to never emit
+    /// [`WiimoteEvent::Gesture`].
+    ///
+    /// Gestures are only detected in report modes that include the
+    /// accelerometer data.
+    pub gestures: Option<GestureConfig>,
+    /// How to smooth the tilt of the Wiimote, or `None` to never emit
+    /// [`WiimoteEvent::Tilt`].
+    pub tilt: Option<TiltConfig>,
+    /// How to map the physical buttons onto the buttons in the
+    /// [`WiimoteEvent`]s, which is the identity by default.
+    ///
+    /// The raw reports are never remapped.
+    pub button_remap: ButtonRemap,
+    /// The shortest time between two output reports written by the Wiimote's
+    /// thread, so that the Wiimote doesn't drop any of them.
+    pub write_interval: Duration,
+    /// When to emit [`WiimoteEvent::BatteryLow`] and
+    /// [`WiimoteEvent::BatteryOk`], or `None` to never emit them.
+    pub battery_warning: Option<BatteryWarning>,
+    /// How often to request a status report, or `None` to only request one
+    /// when the Wiimote connects or resumes.
+    ///
+    /// The Wiimote only sends its battery level in status reports, so without
+    /// this the battery level is never updated.
+    pub status_interval: Option<Duration>,
+    /// How many of the most recent reports read from the Wiimote to keep for
+    /// [`WiimotePollThread::recent_reports`], or 0 to keep none.
+    ///
+    /// They are also dumped if the Wiimote disconnects due to an error.
+    pub recent_reports: usize,
+}
+
+impl Default for WiimoteConfig {
+    fn default() -> Self {
+        Self {
+            rumble_on_connect: RUMBLE_ON_CONNECT,
+            rumble_duration: RUMBLE_DURATION,
+            initial_report_mode: ReportMode::CoreButtons,
+            initial_leds: None,
+            stall_recovery: None,
+            dedup_button_reports: true,
+            motion_plus: None,
+            auto_motion_plus: false,
+            connect_animation: None,
+            connect_animation_duration: Duration::from_secs(1),
+            watchdog_grace: Some(Duration::from_secs(5)),
+            gestures: None,
+            tilt: None,
+            button_remap: ButtonRemap::identity(),
+            write_interval: Duration::from_millis(5),
+            battery_warning: Some(BatteryWarning::default()),
+            status_interval: Some(Duration::from_secs(60)),
+            recent_reports: 0,
+        }
+    }
+}
+
+/// Settings for recovering a Wiimote that has stalled.
+///
+/// Sometimes a Wiimote will only send zeroed reports (or nothing at all) after
+/// it connects, until the reporting mode is set again. Once the poll thread
+/// sees enough of these reads in a row, it sets the reporting mode and requests
+/// the status again to kick the Wiimote back into reporting.
+#[derive(Debug, Clone, Copy)]
+pub struct StallRecovery {
+    /// How many stalled reads in a row it takes for the Wiimote to be
+    /// considered stalled. 0 disables stall recovery, like `None` does.
+    pub max_stalled_reads: u32,
+    /// Whether reads that time out count as stalled.
+    ///
+    /// In non-continuous reporting mode the Wiimote only sends a report when
+    /// something changes, so timeouts are normal while it's idle.
+    pub count_timeouts: bool,
 }
 
-impl From<OutputReportID> for u8 {
-    fn from(val: OutputReportID) -> Self {
-        val as u8
+impl StallRecovery {
+    fn is_stalled(&self, report: &hid::Report) -> bool {
+        if report.is_empty() {
+            // The read timed out
+            return self.count_timeouts;
+        }
+
+        // Skip the input report byte and the report ID, which are never zero
+        report.iter().skip(2).all(|&byte| byte == 0)
     }
 }
 
-#[repr(u8)]
-pub enum InputReportID {
-    // Status = 0x20,
-    // Ack = 0x22,
-    CoreButtons = 0x30,
+/// How to reconnect a Wiimote that has disconnected to the same player slot.
+///
+/// The slot is kept free for the Wiimote while it is searched for, waiting
+/// `backoff_base * 2^n` before the nth attempt. If the Wiimote still hasn't
+/// been found after `max_attempts`, the slot is given up.
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    pub backoff_base: Duration,
 }
 
-impl From<InputReportID> for u8 {
-    fn from(val: InputReportID) -> Self {
-        val as u8
+impl ReconnectPolicy {
+    /// How long to wait before the given attempt, starting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff_base
+            .saturating_mul(2u32.saturating_pow(attempt))
     }
 }
 
-bitflags! {
-    struct Led: u8 {
-        const LED_1 = 0x10;
-        const LED_2 = 0x20;
-        const LED_3 = 0x40;
-        const LED_4 = 0x80;
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff_base: Duration::from_millis(500),
+        }
     }
 }
 
-impl Led {
-    // NOTE: This is zero indexed
-    pub fn player(p: usize) -> Self {
-        match p {
-            0 => Led::LED_1,
-            1 => Led::LED_2,
-            2 => Led::LED_3,
-            3 => Led::LED_4,
-            4 => !Led::LED_1,
-            5 => !Led::LED_2,
-            6 => !Led::LED_3,
-            7 => !Led::LED_4,
-            _ => Led::all(),
+// The state shared between a `WiimotePollThread` and its thread
+#[derive(Debug, Default)]
+struct PollShared {
+    is_connected: Flag,
+    is_paused: Flag,
+    is_rumbling: Flag,
+    // Incremented every time the thread goes around its loop, so the watchdog
+    // can tell when it is stuck
+    progress: Arc<AtomicUsize>,
+    report_hz: SharedF32,
+    player_num: Arc<AtomicUsize>,
+    last_status: Arc<Mutex<Option<StatusReport>>>,
+    // The ID of the current report mode. Only the setters store to it, so
+    // that it can be read back straight after being set
+    report_mode: Arc<AtomicU8>,
+    recent_reports: Arc<Mutex<hid::ReportRing>>,
+    // How many times the thread has initialized a newly connected extension,
+    // for decoders to emit `WiimoteEvent::ExtensionReinitialized`
+    extension_inits: Arc<AtomicUsize>,
+}
+
+impl PollShared {
+    fn cloned_handle(&self) -> Self {
+        Self {
+            is_connected: self.is_connected.cloned_handle(),
+            is_paused: self.is_paused.cloned_handle(),
+            is_rumbling: self.is_rumbling.cloned_handle(),
+            progress: Arc::clone(&self.progress),
+            report_hz: self.report_hz.cloned_handle(),
+            player_num: Arc::clone(&self.player_num),
+            last_status: Arc::clone(&self.last_status),
+            report_mode: Arc::clone(&self.report_mode),
+            recent_reports: Arc::clone(&self.recent_reports),
+            extension_inits: Arc::clone(&self.extension_inits),
         }
     }
+
+    fn player_num(&self) -> usize {
+        self.player_num.load(Ordering::SeqCst)
+    }
+
+    fn report_mode(&self) -> ReportMode {
+        // Only valid modes are ever stored
+        ReportMode::try_from(self.report_mode.load(Ordering::SeqCst)).unwrap()
+    }
+
+    fn set_report_mode(&self, mode: ReportMode) {
+        self.report_mode.store(mode.into(), Ordering::SeqCst);
+    }
+}
+
+// Commands sent to the thread of a `WiimotePollThread`
+enum PollCommand {
+    Write(hid::Report),
+    WriteRaw {
+        report: hid::Report,
+        keep_rumble: bool,
+    },
+    SetReportMode(ReportMode),
+    // Write `report` and wait for it to be acknowledged, switching to
+    // `report_mode` if it is one that sets it
+    WriteAcked {
+        report: hid::Report,
+        report_mode: Option<ReportMode>,
+        reply_tx: Sender<Result<(), AckError>>,
+    },
+    Sequence {
+        reports: Vec<hid::Report>,
+        wait_for_acks: bool,
+    },
+    ReadCalibration(Sender<Result<Calibration, MemoryError>>),
+    CalibrateAtRest {
+        samples: usize,
+        reply_tx: Sender<Result<RestCalibration, RestCalibrationError>>,
+    },
+    ReadMemory {
+        request: hid::Report,
+        address: u32,
+        size: usize,
+        reply_tx: Sender<Result<Vec<u8>, MemoryError>>,
+    },
+    WriteMemory {
+        report: hid::Report,
+        address: u32,
+        reply_tx: Sender<Result<(), MemoryError>>,
+    },
+    // Does nothing, but wakes a paused thread up
+    Wake,
+}
+
+// The channels used by the thread of a `WiimotePollThread`
+struct PollChannels {
+    read_tx: Sender<hid::Report>,
+    write_rx: Receiver<hid::Report>,
+    // Commands from the `WiimotePollThread` itself, which take priority over
+    // `write_rx`
+    control_rx: Receiver<PollCommand>,
+}
+
+// The state of the thread of a `WiimotePollThread` that commands can change
+struct PollState<'a> {
+    kind: Option<WiimoteKind>,
+    config: &'a WiimoteConfig,
+    // The mode to restore after pausing, stalling or connecting an extension
+    report_mode: ReportMode,
+    extension_connected: bool,
+    last_extension_init: Option<Instant>,
+}
+
+/// Something that can give feedback on a Wiimote, so that feedback logic (e.g.
+/// showing the battery level on the LEDs) can be written without depending on
+/// a real Wiimote.
+pub trait Feedback {
+    fn set_leds(&self, leds: Led);
+    fn set_rumble(&self, rumble: bool);
+}
+
+/// A call made to a [`RecordingFeedback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackCall {
+    SetLeds(Led),
+    SetRumble(bool),
+}
+
+/// A fake [`Feedback`] that records every call made to it.
+#[derive(Debug, Default)]
+pub struct RecordingFeedback {
+    calls: Mutex<Vec<FeedbackCall>>,
+}
+
+impl RecordingFeedback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every call that has been made, in order.
+    pub fn calls(&self) -> Vec<FeedbackCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The LEDs that were set last, if any were.
+    pub fn leds(&self) -> Option<Led> {
+        self.calls().into_iter().rev().find_map(|call| match call {
+            FeedbackCall::SetLeds(leds) => Some(leds),
+            _ => None,
+        })
+    }
+
+    /// Whether the rumble was last turned on.
+    pub fn is_rumbling(&self) -> bool {
+        self.calls()
+            .into_iter()
+            .rev()
+            .find_map(|call| match call {
+                FeedbackCall::SetRumble(rumble) => Some(rumble),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
 }
 
+impl Feedback for RecordingFeedback {
+    fn set_leds(&self, leds: Led) {
+        self.calls.lock().unwrap().push(FeedbackCall::SetLeds(leds));
+    }
+
+    fn set_rumble(&self, rumble: bool) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(FeedbackCall::SetRumble(rumble));
+    }
+}
+
+// Each Wiimote gets its own poll thread, blocking on its own overlapped reads.
+// A single dispatcher thread waiting on every device's overlapped event with
+// `WaitForMultipleObjects` would bound the thread count, but acks, pausing,
+// status requests and stall recovery all assume a thread per Wiimote, so it
+// would have to be benchmarked with real Wiimotes before it's worth the
+// rewrite. Until then there is one thread per Wiimote.
 pub struct WiimotePollThread {
-    is_connected: Arc<AtomicBool>,
+    shared: PollShared,
+    control_tx: Sender<PollCommand>,
+    kind: Option<WiimoteKind>,
+    calibration: Mutex<Option<Calibration>>,
+    // Taken by the thread before it drops the device
+    canceller: Arc<Mutex<Option<hid::IoCanceller>>>,
     thread_handle: Option<thread::JoinHandle<()>>,
 }
 
 // XXX: Rename to WiimotePollThread or something?
 impl WiimotePollThread {
     // TODO: Take in a device path and return a result if isn't a valid wiimote?
-    pub fn new(
-        hid_device: hid::Device,
+    pub fn new<D: HidDevice + Send + 'static>(
+        hid_device: D,
+        read_tx: Sender<hid::Report>,
+        write_rx: Receiver<hid::Report>,
+        player_num: usize,
+        kind: Option<WiimoteKind>,
+        config: WiimoteConfig,
+    ) -> Self {
+        Self::with_canceller(
+            hid_device, None, read_tx, write_rx, player_num, kind, config,
+        )
+    }
+
+    /// The same as [`WiimotePollThread::new`], but the thread's IO can be
+    /// cancelled with `canceller` when it is stopped, so that a stuck read
+    /// can't stop it from being joined.
+    pub(crate) fn with_canceller<D: HidDevice + Send + 'static>(
+        hid_device: D,
+        canceller: Option<hid::IoCanceller>,
         read_tx: Sender<hid::Report>,
         write_rx: Receiver<hid::Report>,
         player_num: usize,
+        kind: Option<WiimoteKind>,
+        config: WiimoteConfig,
     ) -> Self {
+        let (control_tx, control_rx) = unbounded();
         let mut wiimote_thread = Self {
-            is_connected: Arc::new(AtomicBool::new(false)),
+            shared: PollShared::default(),
+            control_tx,
+            kind,
+            calibration: Mutex::new(None),
+            canceller: Arc::new(Mutex::new(canceller)),
             thread_handle: None,
         };
 
-        wiimote_thread.start_thread(hid_device, read_tx, write_rx, player_num);
+        let channels = PollChannels {
+            read_tx,
+            write_rx,
+            control_rx,
+        };
+        wiimote_thread
+            .shared
+            .player_num
+            .store(player_num, Ordering::SeqCst);
+        wiimote_thread
+            .shared
+            .set_report_mode(config.initial_report_mode);
+        *wiimote_thread.shared.recent_reports.lock().unwrap() =
+            hid::ReportRing::new(config.recent_reports);
+        wiimote_thread.start_thread(hid_device, channels, kind, config);
 
         wiimote_thread
     }
 
-    fn start_thread(
+    fn start_thread<D: HidDevice + Send + 'static>(
         &mut self,
-        hid_device: hid::Device,
-        read_tx: Sender<hid::Report>,
-        write_rx: Receiver<hid::Report>,
-        player_num: usize,
+        hid_device: D,
+        channels: PollChannels,
+        kind: Option<WiimoteKind>,
+        config: WiimoteConfig,
     ) {
-        if self.is_connected.load(Ordering::SeqCst) {
+        if self.shared.is_connected.get() {
             return;
         }
-        self.is_connected.store(true, Ordering::SeqCst);
+        self.shared.is_connected.set(true);
+
+        // The canceller is taken before the device is dropped, so that the
+        // watchdog can never cancel IO on a closed handle
+        let canceller = Arc::clone(&self.canceller);
+        if let Some(grace) = config.watchdog_grace {
+            let shared = self.shared.cloned_handle();
+            let canceller = Arc::clone(&canceller);
+            // The watchdog stops on its own once the thread has stopped
+            thread::spawn(move || Self::watchdog_thread(&shared, &canceller, grace));
+        }
 
-        let is_connected = Arc::clone(&self.is_connected);
+        let shared = self.shared.cloned_handle();
         let func = move || {
-            if let Err(e) =
-                Self::io_thread(&is_connected, hid_device, &read_tx, &write_rx, player_num)
-            {
-                println!("[Wiimote] Disconnecting Wiimote due to error: {e}");
+            let hid_device = hid::RingDevice::new(hid_device, Arc::clone(&shared.recent_reports));
+            let mut hid_device = hid::PacedDevice::new(hid_device, config.write_interval);
+
+            // Catch panics so that one bad Wiimote can't wedge everything else
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                Self::io_thread(&shared, &mut hid_device, &channels, kind, &config)
+            }));
+
+            match result {
+                Ok(Ok(())) => {}
+                // Stopping the thread cancels its IO, which isn't an error
+                Ok(Err(_)) if !shared.is_connected.get() => {}
+                Ok(Err(e)) => {
+                    println!("[Wiimote] Disconnecting Wiimote due to error: {e}");
+                    Self::dump_recent_reports(&shared);
+                }
+                Err(_) => println!("[Wiimote] Disconnecting Wiimote due to panic"),
             }
 
-            is_connected.store(false, Ordering::SeqCst);
-            println!("[Wiimote] P{} Thread stopped", player_num + 1);
-            // `hid_device`, `read_tx`, and `write_rx` dropped here
+            shared.is_connected.set(false);
+            shared.report_hz.set(0.0);
+            canceller.lock().unwrap().take();
+            println!("[Wiimote] P{} Thread stopped", shared.player_num() + 1);
+            // `hid_device` and `channels` dropped here
         };
 
         self.thread_handle = Some(thread::spawn(func));
     }
 
+    fn dump_recent_reports(shared: &PollShared) {
+        let recent_reports = shared.recent_reports.lock().unwrap().to_vec();
+        if recent_reports.is_empty() {
+            return;
+        }
+
+        println!(
+            "[Wiimote] P{} Last {} reports read:",
+            shared.player_num() + 1,
+            recent_reports.len()
+        );
+        for report in &recent_reports {
+            println!("    {:?}", hid::ReportDump(report));
+        }
+    }
+
+    /// Disconnect the Wiimote if its thread stops making progress for longer
+    /// than a read timeout plus `grace`, cancelling its IO so that it can't
+    /// stay blocked forever.
+    fn watchdog_thread(
+        shared: &PollShared,
+        canceller: &Mutex<Option<hid::IoCanceller>>,
+        grace: Duration,
+    ) {
+        let mut last_progress = shared.progress.load(Ordering::SeqCst);
+        let mut last_change = Instant::now();
+
+        while shared.is_connected.get() {
+            thread::sleep(WATCHDOG_INTERVAL);
+
+            let progress = shared.progress.load(Ordering::SeqCst);
+            if progress != last_progress {
+                last_progress = progress;
+                last_change = Instant::now();
+                continue;
+            }
+
+            if last_change.elapsed() >= hid::WIIMOTE_READ_TIMEOUT + grace {
+                println!(
+                    "[Wiimote] P{} is not responding, disconnecting",
+                    shared.player_num() + 1
+                );
+                shared.is_connected.set(false);
+                if let Some(canceller) = &*canceller.lock().unwrap() {
+                    canceller.cancel();
+                }
+                break;
+            }
+        }
+    }
+
     fn stop_thread(&mut self) {
-        if self.is_connected.load(Ordering::SeqCst) {
-            self.is_connected.store(false, Ordering::SeqCst);
-            self.thread_handle.take().unwrap().join().unwrap();
+        // Cancel any IO the thread is blocked on, so that joining it can't
+        // hang on a read that never completes
+        self.signal_stop();
+
+        // The thread may have stopped on its own, so join it regardless
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                println!("[Wiimote] Thread panicked while stopping");
+            }
         }
     }
 
-    fn io_thread(
-        is_connected: &Arc<AtomicBool>,
-        mut hid_device: hid::Device,
-        read_tx: &Sender<hid::Report>,
-        write_rx: &Receiver<hid::Report>,
-        player_num: usize,
+    fn io_thread<D: HidDevice>(
+        shared: &PollShared,
+        hid_device: &mut D,
+        channels: &PollChannels,
+        kind: Option<WiimoteKind>,
+        config: &WiimoteConfig,
     ) -> hid::Result<()> {
-        Self::init(&mut hid_device, player_num)?;
+        Self::init(hid_device, shared.player_num(), kind, config)?;
 
-        while is_connected.load(Ordering::SeqCst) {
-            Self::write(&mut hid_device, write_rx, player_num)?;
-            Self::read(&mut hid_device, read_tx, player_num)?;
+        let mut stalled_reads = 0;
+        let mut window_start = Instant::now();
+        let mut window_reports = 0;
+        let mut was_paused = false;
+        let mut last_status_request = Instant::now();
+        let mut state = PollState {
+            kind,
+            config,
+            report_mode: config.initial_report_mode,
+            extension_connected: false,
+            // `init` has just initialized the MotionPlus, if it is used
+            last_extension_init: Self::motion_plus_mode(kind, config).map(|_| Instant::now()),
+        };
+        while shared.is_connected.get() {
+            shared.progress.fetch_add(1, Ordering::SeqCst);
+
+            // The player number can be changed while the thread is running
+            let player_num = shared.player_num();
+
+            if shared.is_paused.get() {
+                if !was_paused {
+                    // Only send reports when the buttons change
+                    Self::write_report_mode(hid_device, ReportMode::CoreButtons, false)?;
+                    shared.report_hz.set(0.0);
+                    was_paused = true;
+                    println!("[Wiimote] P{} paused", player_num + 1);
+                }
+
+                // Commands still have to be answered, or blocking calls would
+                // hang until the Wiimote is resumed
+                if let Ok(command) = channels.control_rx.recv_timeout(PAUSE_CHECK_INTERVAL) {
+                    Self::handle_command(hid_device, channels, shared, &mut state, command)?;
+                    // The command may have changed the report mode
+                    Self::write_report_mode(hid_device, ReportMode::CoreButtons, false)?;
+                }
+                continue;
+            }
+
+            if was_paused {
+                Self::write_report_mode(hid_device, state.report_mode, false)?;
+                Self::write_request_status(hid_device)?;
+                last_status_request = Instant::now();
+                stalled_reads = 0;
+                window_start = Instant::now();
+                window_reports = 0;
+                was_paused = false;
+                println!("[Wiimote] P{} resumed", player_num + 1);
+            }
+
+            if let Some(interval) = config.status_interval {
+                if last_status_request.elapsed() >= interval {
+                    // Keep rumbling, since a plain status request turns it off
+                    let mut status_report = report::request_status();
+                    report::set_rumble(&mut status_report, shared.is_rumbling.get());
+                    hid_device.write(&status_report)?;
+                    last_status_request = Instant::now();
+                }
+            }
+
+            Self::write(hid_device, channels, shared, &mut state)?;
+            let report = Self::read(hid_device, &channels.read_tx)?;
+
+            if let Some(status) = StatusReport::from_report(&report) {
+                Self::handle_status(hid_device, shared, &mut state, status)?;
+            }
+
+            if !report.is_empty() {
+                window_reports += 1;
+            }
+            let window_length = window_start.elapsed();
+            if window_length >= REPORT_RATE_WINDOW {
+                shared
+                    .report_hz
+                    .set(window_reports as f32 / window_length.as_secs_f32());
+                window_start = Instant::now();
+                window_reports = 0;
+            }
+
+            let stall_recovery = config
+                .stall_recovery
+                .filter(|recovery| recovery.max_stalled_reads > 0);
+            if let Some(recovery) = stall_recovery {
+                if recovery.is_stalled(&report) {
+                    stalled_reads += 1;
+                } else {
+                    stalled_reads = 0;
+                }
+
+                if stalled_reads >= recovery.max_stalled_reads {
+                    println!(
+                        "[Wiimote] P{} stalled, resetting report mode",
+                        player_num + 1
+                    );
+                    Self::write_report_mode(hid_device, state.report_mode, false)?;
+                    Self::write_request_status(hid_device)?;
+                    stalled_reads = 0;
+                }
+            }
         }
 
         Ok(())
     }
 
-    fn init(hid_device: &mut hid::Device, player_num: usize) -> hid::Result<()> {
-        // Set reporting mode to non-continuous core buttons and turn on rumble.
-        let mode_report = [
-            OUTPUT_REPORT,
-            OutputReportID::ReportMode as u8,
-            if RUMBLE_ON_CONNECT { 0x01 } else { 0x00 },
-            InputReportID::CoreButtons as u8,
-        ];
-        // Request status and turn off rumble.
-        let req_status_report = [OUTPUT_REPORT, OutputReportID::RequestStatus as u8, 0x00];
-        let led_1 = [
-            OUTPUT_REPORT,
-            OutputReportID::Led as u8,
-            Led::player(player_num).bits(),
-        ];
+    /// Keep track of a status report, however it was read.
+    ///
+    /// An extension loses its initialization whenever it is connected, which
+    /// includes the Wiimote reconnecting with one plugged in, so it is
+    /// initialized again here.
+    fn handle_status<D: HidDevice>(
+        hid_device: &mut D,
+        shared: &PollShared,
+        state: &mut PollState,
+        status: StatusReport,
+    ) -> hid::Result<()> {
+        *shared.last_status.lock().unwrap() = Some(status);
+
+        let cooled_down = match state.last_extension_init {
+            Some(time) => time.elapsed() >= EXTENSION_REINIT_COOLDOWN,
+            None => true,
+        };
+        if status.extension_connected && !state.extension_connected && cooled_down {
+            println!(
+                "[Wiimote] P{} extension connected, initializing",
+                shared.player_num() + 1
+            );
+            Self::init_extension(hid_device, state.kind, state.config, state.report_mode)?;
+            shared.extension_inits.fetch_add(1, Ordering::SeqCst);
+            state.last_extension_init = Some(Instant::now());
+        } else {
+            // The Wiimote can stop sending data reports after a status report
+            // until the report mode is set again, which initializing the
+            // extension already does
+            Self::write_report_mode(hid_device, state.report_mode, shared.is_rumbling.get())?;
+        }
+        state.extension_connected = status.extension_connected;
+
+        Ok(())
+    }
 
+    fn write_report_mode<D: HidDevice>(
+        hid_device: &mut D,
+        mode: ReportMode,
+        rumble: bool,
+    ) -> hid::Result<()> {
+        // Non-continuous reporting
+        let mut mode_report = report::report_mode(false, mode);
+        report::set_rumble(&mut mode_report, rumble);
         hid_device.write(&mode_report)?;
-        thread::sleep(RUMBLE_DURATION);
-        hid_device.write(&req_status_report)?;
-        hid_device.write(&led_1)?;
 
         Ok(())
     }
 
-    fn write(
-        hid_device: &mut hid::Device,
-        write_rx: &Receiver<hid::Report>,
+    fn write_request_status<D: HidDevice>(hid_device: &mut D) -> hid::Result<()> {
+        // This also turns off rumble
+        hid_device.write(&report::request_status())?;
+
+        Ok(())
+    }
+
+    fn init<D: HidDevice>(
+        hid_device: &mut D,
         player_num: usize,
+        kind: Option<WiimoteKind>,
+        config: &WiimoteConfig,
+    ) -> hid::Result<()> {
+        let leds = config
+            .initial_leds
+            .unwrap_or_else(|| Led::player(player_num));
+
+        // Set reporting mode and turn on rumble if needed.
+        Self::write_report_mode(
+            hid_device,
+            config.initial_report_mode,
+            config.rumble_on_connect,
+        )?;
+
+        let start = Instant::now();
+        let rumble_duration = if config.rumble_on_connect {
+            config.rumble_duration
+        } else {
+            Duration::ZERO
+        };
+
+        if let Some(animation) = &config.connect_animation {
+            while start.elapsed() < config.connect_animation_duration {
+                let elapsed = start.elapsed();
+                // Every output report sets the rumble, so keep it going
+                let mut led_report = report::led(animation.frame(elapsed));
+                report::set_rumble(&mut led_report, elapsed < rumble_duration);
+                hid_device.write(&led_report)?;
+
+                thread::sleep(LED_FRAME_INTERVAL);
+            }
+        }
+
+        if let Some(remaining) = rumble_duration.checked_sub(start.elapsed()) {
+            thread::sleep(remaining);
+        }
+        // Request status and turn off rumble.
+        Self::write_request_status(hid_device)?;
+        hid_device.write(&report::led(leds))?;
+
+        if let Some(mode) = Self::motion_plus_mode(kind, config) {
+            Self::enable_motion_plus(hid_device, kind, mode)?;
+        }
+
+        Ok(())
+    }
+
+    fn motion_plus_mode(
+        kind: Option<WiimoteKind>,
+        config: &WiimoteConfig,
+    ) -> Option<MotionPlusMode> {
+        match (config.motion_plus, kind) {
+            (Some(mode), _) => Some(mode),
+            (None, Some(WiimoteKind::MotionPlusInside)) if config.auto_motion_plus => {
+                Some(MotionPlusMode::NunchukPassthrough)
+            }
+            _ => None,
+        }
+    }
+
+    /// Initialize a newly connected extension (or the MotionPlus, if it is
+    /// used), then set the report mode again since the Wiimote stops sending
+    /// data reports when an extension is connected.
+    fn init_extension<D: HidDevice>(
+        hid_device: &mut D,
+        kind: Option<WiimoteKind>,
+        config: &WiimoteConfig,
+        report_mode: ReportMode,
+    ) -> hid::Result<()> {
+        match Self::motion_plus_mode(kind, config) {
+            Some(mode) => Self::enable_motion_plus(hid_device, kind, mode)?,
+            None => {
+                for (address, value) in EXTENSION_INIT_WRITES {
+                    hid_device.write(&report::register_write(address, &[value]).unwrap())?;
+                }
+            }
+        }
+
+        Self::write_report_mode(hid_device, report_mode, false)
+    }
+
+    fn enable_motion_plus<D: HidDevice>(
+        hid_device: &mut D,
+        kind: Option<WiimoteKind>,
+        mode: MotionPlusMode,
+    ) -> hid::Result<()> {
+        // The extension has to be initialized before it can be passed through
+        if mode.is_passthrough() {
+            for (address, value) in EXTENSION_INIT_WRITES {
+                hid_device.write(&report::register_write(address, &[value]).unwrap())?;
+            }
+        }
+
+        // A standalone MotionPlus has to be initialized like an extension
+        // first, but a built-in one is always ready to be activated
+        if kind != Some(WiimoteKind::MotionPlusInside) {
+            hid_device
+                .write(&report::register_write(MotionPlusMode::INIT_ADDRESS, &[0x55]).unwrap())?;
+        }
+
+        hid_device.write(
+            &report::register_write(MotionPlusMode::ACTIVATE_ADDRESS, &[mode.into()]).unwrap(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Write the pending commands and reports, before the next read.
+    ///
+    /// Every pending write is drained (commands first), up to
+    /// [`MAX_WRITES_PER_READ`], so that a burst of LED or rumble reports isn't
+    /// held up by the read timeout between each one. If `is_rumbling` is true,
+    /// the rumble bit is set on each report so that the rumble isn't turned
+    /// off by them. `state` is kept up to date with any changes that the
+    /// commands make, e.g. to the report mode.
+    fn write<D: HidDevice>(
+        hid_device: &mut D,
+        channels: &PollChannels,
+        shared: &PollShared,
+        state: &mut PollState,
     ) -> hid::Result<()> {
-        // let req_status_report = [OUTPUT_REPORT, OutputReportID::RequestStatus as u8, 0x00];
-        // hid_device.write(&req_status_report)?;
+        for _ in 0..MAX_WRITES_PER_READ {
+            if !Self::write_one(hid_device, channels, shared, state)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a single command or report, returning whether there was one.
+    fn write_one<D: HidDevice>(
+        hid_device: &mut D,
+        channels: &PollChannels,
+        shared: &PollShared,
+        state: &mut PollState,
+    ) -> hid::Result<bool> {
+        let write_rx = &channels.write_rx;
+        match channels.control_rx.try_recv() {
+            Ok(command) => {
+                Self::handle_command(hid_device, channels, shared, state, command)?;
+            }
+            Err(_) => match write_rx.try_recv() {
+                Ok(mut report) => {
+                    // println!("write: {report:0x?}");
+                    println!("Write queue length: {}", write_rx.len());
+                    if shared.is_rumbling.get() {
+                        report::set_rumble(&mut report, true);
+                    }
+                    hid_device.write(&report)?;
+                }
+                Err(_) => return Ok(false),
+            },
+        }
 
-        if let Ok(report) = write_rx.try_recv() {
-            // println!("P{} write: {report:0x?}", player_num + 1);
-            println!("Write queue length: {}", write_rx.len());
-            hid_device.write(&report)?;
+        Ok(true)
+    }
+
+    /// Carry out a command from the [`WiimotePollThread`].
+    fn handle_command<D: HidDevice>(
+        hid_device: &mut D,
+        channels: &PollChannels,
+        shared: &PollShared,
+        state: &mut PollState,
+        command: PollCommand,
+    ) -> hid::Result<()> {
+        let is_rumbling = shared.is_rumbling.get();
+        match command {
+            PollCommand::SetReportMode(mode) => {
+                Self::write_report_mode(hid_device, mode, is_rumbling)?;
+                state.report_mode = mode;
+            }
+            PollCommand::Write(mut report) => {
+                if is_rumbling {
+                    report::set_rumble(&mut report, true);
+                }
+                hid_device.write(&report)?;
+            }
+            PollCommand::WriteRaw {
+                mut report,
+                keep_rumble,
+            } => {
+                if keep_rumble && is_rumbling {
+                    report::set_rumble(&mut report, true);
+                }
+                hid_device.write(&report)?;
+            }
+            PollCommand::WriteAcked {
+                mut report,
+                report_mode: new_mode,
+                reply_tx,
+            } => {
+                if is_rumbling {
+                    report::set_rumble(&mut report, true);
+                }
+                hid_device.write(&report)?;
+                let result = match Self::wait_for_ack_error(hid_device, &channels.read_tx, &report)?
+                {
+                    Some(0) => {
+                        if let Some(mode) = new_mode {
+                            state.report_mode = mode;
+                        }
+                        Ok(())
+                    }
+                    Some(error) => Err(AckError::Wiimote {
+                        report_id: report[1],
+                        error,
+                    }),
+                    None => Err(AckError::TimedOut),
+                };
+                let _ = reply_tx.send(result);
+            }
+            PollCommand::Sequence {
+                reports,
+                wait_for_acks,
+            } => {
+                Self::write_sequence(hid_device, &channels.read_tx, &reports, wait_for_acks)?;
+            }
+            PollCommand::ReadCalibration(reply_tx) => {
+                let calibration =
+                    Self::read_calibration(hid_device, &channels.read_tx, shared, state)?;
+                let _ = reply_tx.send(calibration);
+            }
+            PollCommand::CalibrateAtRest { samples, reply_tx } => {
+                let calibration = Self::calibrate_at_rest_inner(
+                    hid_device,
+                    &channels.read_tx,
+                    &shared.progress,
+                    state.report_mode,
+                    is_rumbling,
+                    samples,
+                )?;
+                let _ = reply_tx.send(calibration);
+            }
+            PollCommand::ReadMemory {
+                request,
+                address,
+                size,
+                reply_tx,
+            } => {
+                let data =
+                    Self::read_memory(hid_device, &channels.read_tx, address, &request, size)?;
+                let _ = reply_tx.send(data);
+            }
+            PollCommand::WriteMemory {
+                report,
+                address,
+                reply_tx,
+            } => {
+                hid_device.write(&report)?;
+                let result = match Self::wait_for_ack_error(hid_device, &channels.read_tx, &report)?
+                {
+                    Some(0) => Ok(()),
+                    Some(error) => Err(MemoryError::Wiimote { address, error }),
+                    None => Err(MemoryError::TimedOut),
+                };
+                let _ = reply_tx.send(result);
+            }
+            PollCommand::Wake => {}
         }
 
         Ok(())
     }
 
-    fn read(
-        hid_device: &mut hid::Device,
+    fn write_sequence<D: HidDevice>(
+        hid_device: &mut D,
         read_tx: &Sender<hid::Report>,
-        player_num: usize,
+        reports: &[hid::Report],
+        wait_for_acks: bool,
     ) -> hid::Result<()> {
+        for report in reports {
+            hid_device.write(report)?;
+
+            if wait_for_acks && !Self::wait_for_ack(hid_device, read_tx, report)? {
+                println!(
+                    "[Wiimote] {:?} wasn't acknowledged, dropping the rest of the sequence",
+                    hid::ReportDump(report)
+                );
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read reports until the Wiimote acknowledges `report`, returning whether
+    /// it was acknowledged without an error.
+    fn wait_for_ack<D: HidDevice>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+        report: &hid::Report,
+    ) -> hid::Result<bool> {
+        let error = Self::wait_for_ack_error(hid_device, read_tx, report)?;
+        Ok(error == Some(0))
+    }
+
+    /// Read reports until the Wiimote acknowledges `report`, returning the
+    /// error code it was acknowledged with, or `None` if it wasn't.
+    fn wait_for_ack_error<D: HidDevice>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+        report: &hid::Report,
+    ) -> hid::Result<Option<u8>> {
+        let ack_id = InputReportID::Ack as u8;
+        Self::wait_for_report(hid_device, read_tx, |read_report| {
+            match read_report.as_slice() {
+                [INPUT_REPORT, id, _, _, acked_id, error, ..]
+                    if *id == ack_id && Some(acked_id) == report.get(1) =>
+                {
+                    Some(*error)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Read reports until `f` returns `Some`, or until [`ACK_TIMEOUT`] has
+    /// passed. Every report read is still sent to `read_tx`.
+    fn wait_for_report<D: HidDevice, T>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+        mut f: impl FnMut(&hid::Report) -> Option<T>,
+    ) -> hid::Result<Option<T>> {
+        let start = Instant::now();
+
+        while start.elapsed() < ACK_TIMEOUT {
+            let read_report = hid_device.read()?;
+            if read_report.is_empty() {
+                continue;
+            }
+            let _ = read_tx.send(read_report.clone());
+
+            if let Some(value) = f(&read_report) {
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Collect `samples` accelerometer readings, switching to a report mode
+    /// with the accelerometer in it for the time being.
+    fn calibrate_at_rest_inner<D: HidDevice>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+        progress: &AtomicUsize,
+        report_mode: ReportMode,
+        is_rumbling: bool,
+        samples: usize,
+    ) -> hid::Result<Result<RestCalibration, RestCalibrationError>> {
+        // Report continuously, since the readings might not change at all
+        let accel_mode = match report_mode.accel_range() {
+            Some(_) => report_mode,
+            None => ReportMode::CoreAccel,
+        };
+        let mut mode_report = report::report_mode(true, accel_mode);
+        report::set_rumble(&mut mode_report, is_rumbling);
+        hid_device.write(&mode_report)?;
+
+        let mut accel = Vec::with_capacity(samples);
+        while accel.len() < samples {
+            // Sampling can take longer than the watchdog's grace period
+            progress.fetch_add(1, Ordering::SeqCst);
+            let sample = Self::wait_for_report(hid_device, read_tx, |report| {
+                AccelData::try_from(report).ok()
+            })?;
+            match sample {
+                Some(sample) => accel.push(sample),
+                None => break,
+            }
+        }
+
+        Self::write_report_mode(hid_device, report_mode, is_rumbling)?;
+
+        Ok(RestCalibration::from_samples(&accel))
+    }
+
+    /// Write a memory read `request` and collect the `size` bytes of data
+    /// that the Wiimote sends back.
+    fn read_memory<D: HidDevice>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+        address: u32,
+        request: &hid::Report,
+        size: usize,
+    ) -> hid::Result<Result<Vec<u8>, MemoryError>> {
+        hid_device.write(request)?;
+
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let reply =
+                Self::wait_for_report(hid_device, read_tx, report::DataReadReport::from_report)?;
+            let reply = match reply {
+                Some(reply) => reply,
+                None => return Ok(Err(MemoryError::TimedOut)),
+            };
+            if reply.is_error() {
+                return Ok(Err(MemoryError::Wiimote {
+                    address,
+                    error: reply.error,
+                }));
+            }
+
+            data.extend_from_slice(&reply.data);
+        }
+        data.truncate(size);
+
+        Ok(Ok(data))
+    }
+
+    /// Read the accelerometer calibration and, if an extension is connected,
+    /// the extension calibration, one after the other.
+    fn read_calibration<D: HidDevice>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+        shared: &PollShared,
+        state: &mut PollState,
+    ) -> hid::Result<Result<Calibration, MemoryError>> {
+        // Find out whether there is an extension to read from
+        Self::write_request_status(hid_device)?;
+        let status = Self::wait_for_report(hid_device, read_tx, StatusReport::from_report)?;
+        let status = match status {
+            Some(status) => status,
+            None => return Ok(Err(MemoryError::TimedOut)),
+        };
+        // The status report doesn't reach the loop, so handle it like the
+        // loop would, which also initializes a newly connected extension
+        // before its calibration is read
+        Self::handle_status(hid_device, shared, state, status)?;
+
+        let request =
+            report::eeprom_read(AccelCalibration::ADDRESS, AccelCalibration::LENGTH as u16)
+                .unwrap();
+        let accel = match Self::read_memory(
+            hid_device,
+            read_tx,
+            AccelCalibration::ADDRESS,
+            &request,
+            AccelCalibration::LENGTH,
+        )? {
+            Ok(data) => AccelCalibration::from_bytes(data.as_slice().try_into().unwrap()),
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let mut extension = None;
+        if status.extension_connected {
+            let request = report::register_read(
+                Calibration::EXTENSION_ADDRESS,
+                Calibration::EXTENSION_LENGTH as u16,
+            )
+            .unwrap();
+            match Self::read_memory(
+                hid_device,
+                read_tx,
+                Calibration::EXTENSION_ADDRESS,
+                &request,
+                Calibration::EXTENSION_LENGTH,
+            )? {
+                Ok(data) => extension = Some(data.as_slice().try_into().unwrap()),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+
+        Ok(Ok(Calibration { accel, extension }))
+    }
+
+    /// Read a report from the Wiimote and send it to `read_tx`.
+    ///
+    /// The report is also returned, and will be empty if the read timed out.
+    fn read<D: HidDevice>(
+        hid_device: &mut D,
+        read_tx: &Sender<hid::Report>,
+    ) -> hid::Result<hid::Report> {
         let report = hid_device.read()?;
-        // println!("P{} read: {report:0x?}", player_num + 1);
+        // println!("read: {report:0x?}");
         if !report.is_empty() {
-            read_tx.send(report);
+            let _ = read_tx.send(report.clone());
+        }
+
+        Ok(report)
+    }
+
+    /// Change which player the Wiimote belongs to, and show it on the LEDs.
+    pub fn set_player_number(&self, player: PlayerNumber) {
+        self.shared
+            .player_num
+            .store(player.index(), Ordering::SeqCst);
+        let _ = self
+            .control_tx
+            .send(PollCommand::Write(report::led(player.leds())));
+    }
+
+    /// Change the reporting mode of the Wiimote, before any reports waiting in
+    /// the write channel.
+    ///
+    /// This mode is also restored after pausing, stalling or connecting an
+    /// extension, instead of [`WiimoteConfig::initial_report_mode`]. Reports
+    /// that were already read are still in the old mode, but every input
+    /// report's ID is the mode it was sent in, so decoding them with e.g.
+    /// [`report::DecodedReport`] always uses the right layout.
+    pub fn set_report_mode(&self, mode: ReportMode) {
+        self.shared.set_report_mode(mode);
+        let _ = self.control_tx.send(PollCommand::SetReportMode(mode));
+    }
+
+    /// Change the reporting mode of the Wiimote like
+    /// [`WiimotePollThread::set_report_mode`], but block until the Wiimote
+    /// acknowledges the change.
+    ///
+    /// This is slower, so it is for when the change has to be confirmed, e.g.
+    /// to diagnose a Wiimote that doesn't seem to switch modes. The mode is
+    /// only restored later on if it was acknowledged.
+    pub fn set_report_mode_acked(&self, mode: ReportMode) -> Result<(), AckError> {
+        let mut mode_report = report::report_mode(false, mode);
+        report::request_ack(&mut mode_report);
+        self.write_acked(mode_report, Some(mode))
+    }
+
+    /// Set the LEDs like [`Feedback::set_leds`], but block until the Wiimote
+    /// acknowledges the change.
+    pub fn set_leds_acked(&self, leds: Led) -> Result<(), AckError> {
+        let mut led_report = report::led(leds);
+        report::request_ack(&mut led_report);
+        self.write_acked(led_report, None)
+    }
+
+    fn write_acked(
+        &self,
+        report: hid::Report,
+        report_mode: Option<ReportMode>,
+    ) -> Result<(), AckError> {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.control_tx
+            .send(PollCommand::WriteAcked {
+                report,
+                report_mode,
+                reply_tx,
+            })
+            .map_err(|_| AckError::NotConnected)?;
+
+        reply_rx.recv().map_err(|_| AckError::NotConnected)??;
+        if let Some(mode) = report_mode {
+            self.shared.set_report_mode(mode);
         }
 
         Ok(())
     }
 
-    pub fn is_connected(&self) -> bool {
-        self.is_connected.load(Ordering::SeqCst)
+    /// The most recent reports read from the Wiimote, oldest first, up to
+    /// [`WiimoteConfig::recent_reports`] of them.
+    ///
+    /// This includes the reports read by the thread itself, e.g. while
+    /// waiting for acknowledgements, and is kept after the Wiimote
+    /// disconnects.
+    pub fn recent_reports(&self) -> Vec<hid::Report> {
+        self.shared.recent_reports.lock().unwrap().to_vec()
     }
-}
 
-impl Drop for WiimotePollThread {
-    fn drop(&mut self) {
-        self.stop_thread();
+    /// The report mode that was last set, which the Wiimote is in (or is
+    /// about to be in).
+    ///
+    /// This is the mode that is restored after pausing, stalling or
+    /// connecting an extension, so while the Wiimote is paused it is actually
+    /// only sending the buttons.
+    pub fn current_report_mode(&self) -> ReportMode {
+        self.shared.report_mode()
+    }
+
+    /// Which kind of Wiimote this is, if it is known.
+    ///
+    /// Some quirks only apply to one kind, e.g. only the RVL-CNT-01-TR has a
+    /// built-in MotionPlus.
+    pub fn kind(&self) -> Option<WiimoteKind> {
+        self.kind
+    }
+
+    /// Create an [`EventDecoder`] for the reports read from this Wiimote.
+    ///
+    /// Unlike [`EventDecoder::new`], the decoder knows when this thread
+    /// initializes an extension, so it emits
+    /// [`WiimoteEvent::ExtensionReinitialized`], and which mode the MotionPlus
+    /// was activated in, even if [`WiimoteConfig::auto_motion_plus`] chose it.
+    pub fn event_decoder(&self, config: &WiimoteConfig) -> EventDecoder {
+        EventDecoder::for_poll_thread(
+            config,
+            Self::motion_plus_mode(self.kind, config),
+            Arc::clone(&self.shared.extension_inits),
+        )
+    }
+
+    /// The last status report received from the Wiimote, if there has been
+    /// one.
+    ///
+    /// A status report is requested when the Wiimote connects, and then every
+    /// [`WiimoteConfig::status_interval`].
+    pub fn last_status(&self) -> Option<StatusReport> {
+        *self.shared.last_status.lock().unwrap()
+    }
+
+    /// Get a [`WiimoteCommander`] that can send commands to this thread from
+    /// anywhere.
+    pub fn commander(&self) -> WiimoteCommander {
+        WiimoteCommander {
+            control_tx: self.control_tx.clone(),
+            shared: self.shared.cloned_handle(),
+        }
+    }
+
+    /// Ask the Wiimote to send a status report.
+    ///
+    /// The report is written before any reports waiting in the write channel.
+    pub fn request_status(&self) {
+        // The thread may have stopped, in which case there's nothing to do
+        let _ = self
+            .control_tx
+            .send(PollCommand::Write(report::request_status()));
+    }
+
+    /// Write `report` to the Wiimote exactly as it is, before any reports
+    /// waiting in the write channel.
+    ///
+    /// This is an escape hatch for sending reports that the library doesn't
+    /// know about, so the report isn't checked at all. It must start with
+    /// [`hid::OUTPUT_REPORT`] and the report ID. If `keep_rumble` is true and
+    /// the rumble is on, the rumble bit is set so that it isn't turned off.
+    ///
+    /// Sending the wrong report can leave the Wiimote in a state that the
+    /// thread doesn't expect, e.g. with reporting turned off, or make it stop
+    /// responding until it is turned off and on again.
+    pub fn send_raw(&self, report: hid::Report, keep_rumble: bool) {
+        let _ = self.control_tx.send(PollCommand::WriteRaw {
+            report,
+            keep_rumble,
+        });
+    }
+
+    /// Write `reports` to the Wiimote back-to-back, without any other reports
+    /// being written in between.
+    ///
+    /// If `wait_for_acks` is true, each report has to be acknowledged by the
+    /// Wiimote before the next one is written. If a report isn't acknowledged,
+    /// or the Wiimote reports an error, the rest of the sequence is dropped.
+    /// Any reports read while waiting are still sent to the read channel.
+    pub fn send_sequence(&self, reports: Vec<hid::Report>, wait_for_acks: bool) {
+        let _ = self.control_tx.send(PollCommand::Sequence {
+            reports,
+            wait_for_acks,
+        });
+    }
+
+    /// Read the calibration of the accelerometer and the extension (if one is
+    /// connected) in one go, blocking until it has been read.
+    ///
+    /// The reads are done back-to-back, without any other reports being
+    /// written in between. The result is cached, so only the first successful
+    /// call actually talks to the Wiimote.
+    pub fn read_all_calibration(&self) -> Result<Calibration, MemoryError> {
+        let mut cached = self.calibration.lock().unwrap();
+        if let Some(calibration) = *cached {
+            return Ok(calibration);
+        }
+
+        let (reply_tx, reply_rx) = bounded(1);
+        self.control_tx
+            .send(PollCommand::ReadCalibration(reply_tx))
+            .map_err(|_| MemoryError::NotConnected)?;
+        let calibration = reply_rx.recv().map_err(|_| MemoryError::NotConnected)??;
+        *cached = Some(calibration);
+
+        Ok(calibration)
+    }
+
+    /// Measure the offset and noise of the accelerometer from `samples`
+    /// readings, blocking until they have been collected.
+    ///
+    /// The Wiimote should be lying still, e.g. flat on a table, the whole
+    /// time. If it moves, [`RestCalibrationError::Moved`] is returned. The
+    /// accelerometer is turned on while the readings are taken if the current
+    /// report mode doesn't include it, and at 100 reports per second this
+    /// takes about `samples / 100` seconds. That has to be shorter than
+    /// [`WiimoteConfig::watchdog_grace`], or the Wiimote is disconnected.
+    pub fn calibrate_at_rest(
+        &self,
+        samples: usize,
+    ) -> Result<RestCalibration, RestCalibrationError> {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.control_tx
+            .send(PollCommand::CalibrateAtRest { samples, reply_tx })
+            .map_err(|_| RestCalibrationError::NotConnected)?;
+
+        reply_rx
+            .recv()
+            .map_err(|_| RestCalibrationError::NotConnected)?
+    }
+
+    /// Read `size` bytes from the Wiimote's control registers, starting at
+    /// `address`, blocking until they have been read.
+    ///
+    /// The read is done before any reports waiting in the write channel.
+    pub fn read_register(&self, address: u32, size: u16) -> Result<Vec<u8>, MemoryError> {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.control_tx
+            .send(PollCommand::ReadMemory {
+                request: report::register_read(address, size)?,
+                address,
+                size: size as usize,
+                reply_tx,
+            })
+            .map_err(|_| MemoryError::NotConnected)?;
+
+        reply_rx.recv().map_err(|_| MemoryError::NotConnected)?
+    }
+
+    /// Find out what the Wiimote is, including its MotionPlus and extension,
+    /// blocking until the registers have been read.
+    ///
+    /// A MotionPlus that is active doesn't show up at its own address, but as
+    /// the extension instead.
+    pub fn identify(&self) -> Result<WiimoteIdentity, MemoryError> {
+        // The Wiimote reports an error when there is nothing to read
+        let read_id = |address| match self.read_register(address, 6) {
+            Ok(data) => Ok(data.try_into().ok()),
+            Err(MemoryError::Wiimote { .. }) => Ok(None),
+            Err(e) => Err(e),
+        };
+
+        Ok(WiimoteIdentity {
+            kind: self.kind,
+            motion_plus_id: read_id(WiimoteIdentity::MOTION_PLUS_ID_ADDRESS)?,
+            extension_id: read_id(WiimoteIdentity::EXTENSION_ID_ADDRESS)?,
+        })
+    }
+
+    /// Write `data` to the Wiimote's control registers, starting at
+    /// `address`, blocking until the Wiimote acknowledges the write.
+    ///
+    /// At most [`report::MAX_WRITE_LENGTH`] bytes can be written at once. The
+    /// write is done before any reports waiting in the write channel.
+    pub fn write_register(&self, address: u32, data: &[u8]) -> Result<(), MemoryError> {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.control_tx
+            .send(PollCommand::WriteMemory {
+                report: report::register_write(address, data)?,
+                address,
+                reply_tx,
+            })
+            .map_err(|_| MemoryError::NotConnected)?;
+
+        reply_rx.recv().map_err(|_| MemoryError::NotConnected)?
+    }
+
+    /// Stop reading from and writing to the Wiimote, without disconnecting
+    /// it.
+    ///
+    /// The Wiimote is switched to [`ReportMode::CoreButtons`] so that it only
+    /// sends reports when its buttons change, and the thread sleeps until
+    /// [`WiimotePollThread::resume`] is called. Nothing is read while paused,
+    /// so the read timeout and stall recovery don't apply, and any reports
+    /// sent to the write channel are only written after resuming. Calls that
+    /// block on the Wiimote (e.g. [`WiimotePollThread::read_register`]) are
+    /// still answered, reading only the reports they wait for.
+    ///
+    /// The Wiimote is still considered connected while it is paused. Note
+    /// that Windows buffers some of the reports sent while paused, so they
+    /// are read straight after resuming.
+    pub fn pause(&self) {
+        self.shared.is_paused.set(true);
+    }
+
+    /// Start polling the Wiimote again after [`WiimotePollThread::pause`],
+    /// restoring the current report mode (see
+    /// [`WiimotePollThread::current_report_mode`]).
+    pub fn resume(&self) {
+        self.shared.is_paused.set(false);
+        self.wake_thread();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.is_paused.get()
+    }
+
+    /// Tell the thread to stop as soon as possible without waiting for it,
+    /// cancelling any read or write that it is blocked on.
+    pub(crate) fn signal_stop(&self) {
+        self.shared.is_connected.set(false);
+        self.wake_thread();
+        if let Some(canceller) = &*self.canceller.lock().unwrap() {
+            canceller.cancel();
+        }
+    }
+
+    fn wake_thread(&self) {
+        let _ = self.control_tx.send(PollCommand::Wake);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.shared.is_connected.get()
+    }
+
+    /// How many reports per second were received from the Wiimote, averaged
+    /// over the last second.
+    pub fn report_hz(&self) -> f32 {
+        self.shared.report_hz.get()
+    }
+}
+
+impl Feedback for WiimotePollThread {
+    /// Set the LEDs, before any reports waiting in the write channel.
+    fn set_leds(&self, leds: Led) {
+        let _ = self.control_tx.send(PollCommand::Write(report::led(leds)));
+    }
+
+    /// Turn the rumble on or off, before any reports waiting in the write
+    /// channel.
+    ///
+    /// While the rumble is on, it is also set on every report written by the
+    /// thread, as any report without the rumble bit turns it off.
+    fn set_rumble(&self, rumble: bool) {
+        self.shared.is_rumbling.set(rumble);
+        let _ = self
+            .control_tx
+            .send(PollCommand::Write(report::rumble(rumble)));
+    }
+}
+
+impl Drop for WiimotePollThread {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
+/// What a Wiimote says it is, e.g. to include in a bug report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WiimoteIdentity {
+    pub kind: Option<WiimoteKind>,
+    /// The identifier of the MotionPlus, whether it is built in or plugged
+    /// in, or `None` if there isn't one.
+    pub motion_plus_id: Option<[u8; 6]>,
+    /// The identifier of the extension, or `None` if there isn't one.
+    pub extension_id: Option<[u8; 6]>,
+}
+
+impl WiimoteIdentity {
+    /// Where the extension identifier is.
+    pub const EXTENSION_ID_ADDRESS: u32 = 0xa400fa;
+    /// Where the identifier of an inactive MotionPlus is.
+    pub const MOTION_PLUS_ID_ADDRESS: u32 = 0xa600fa;
+}
+
+impl fmt::Display for WiimoteIdentity {
+    /// Shows everything on one line, e.g. `RVL-CNT-01-TR, MotionPlus 00 00 a6
+    /// 20 00 05, no extension`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = |f: &mut fmt::Formatter<'_>, name: &str, id: Option<[u8; 6]>| match id {
+            Some(id) => {
+                write!(f, "{name}")?;
+                for byte in id {
+                    write!(f, " {byte:02x}")?;
+                }
+                Ok(())
+            }
+            None => write!(f, "no {name}"),
+        };
+
+        match self.kind {
+            Some(WiimoteKind::Original) => write!(f, "RVL-CNT-01")?,
+            Some(WiimoteKind::MotionPlusInside) => write!(f, "RVL-CNT-01-TR")?,
+            Some(WiimoteKind::ProController) => write!(f, "RVL-CNT-01-UC")?,
+            None => write!(f, "Unknown model")?,
+        }
+        write!(f, ", ")?;
+        id(f, "MotionPlus", self.motion_plus_id)?;
+        write!(f, ", ")?;
+        id(f, "extension", self.extension_id)
+    }
+}
+
+/// A cheap handle for sending commands to a [`WiimotePollThread`], which can
+/// be cloned and handed to different parts of an application.
+///
+/// The commands work the same as the methods of the same name on the thread,
+/// and share its rumble state. Once the thread has stopped, they do nothing.
+/// A commander doesn't keep the thread running.
+pub struct WiimoteCommander {
+    control_tx: Sender<PollCommand>,
+    shared: PollShared,
+}
+
+impl WiimoteCommander {
+    pub fn is_connected(&self) -> bool {
+        self.shared.is_connected.get()
+    }
+
+    /// See [`WiimotePollThread::request_status`].
+    pub fn request_status(&self) {
+        let _ = self
+            .control_tx
+            .send(PollCommand::Write(report::request_status()));
+    }
+
+    /// See [`WiimotePollThread::set_report_mode`].
+    pub fn set_report_mode(&self, mode: ReportMode) {
+        self.shared.set_report_mode(mode);
+        let _ = self.control_tx.send(PollCommand::SetReportMode(mode));
+    }
+
+    /// Whether the rumble is on, as last set through the thread or any of its
+    /// commanders.
+    pub fn is_rumbling(&self) -> bool {
+        self.shared.is_rumbling.get()
+    }
+}
+
+impl Feedback for WiimoteCommander {
+    /// See [`WiimotePollThread::set_leds`].
+    fn set_leds(&self, leds: Led) {
+        let _ = self.control_tx.send(PollCommand::Write(report::led(leds)));
+    }
+
+    /// See [`WiimotePollThread::set_rumble`].
+    fn set_rumble(&self, rumble: bool) {
+        self.shared.is_rumbling.set(rumble);
+        let _ = self
+            .control_tx
+            .send(PollCommand::Write(report::rumble(rumble)));
+    }
+}
+
+impl Clone for WiimoteCommander {
+    fn clone(&self) -> Self {
+        Self {
+            control_tx: self.control_tx.clone(),
+            shared: self.shared.cloned_handle(),
+        }
+    }
+}
+
+/// A Wiimote that is being polled on its own thread, with channels to receive
+/// the reports read from it and to send reports to write to it.
+pub struct WiimoteHandle {
+    poll_thread: WiimotePollThread,
+    read_rx: Receiver<hid::Report>,
+    write_tx: Sender<hid::Report>,
+    device_info: hid::DeviceInfo,
+}
+
+impl WiimoteHandle {
+    /// Open the Wiimote and start polling it as the given (zero indexed)
+    /// player.
+    ///
+    /// Opening the HID device can fail for a moment after the Wiimote is
+    /// paired, which the [`WiimoteManager`] handles by trying again on a
+    /// later [`WiimoteManager::update`].
+    ///
+    /// [`WiimoteManager`]: crate::manager::WiimoteManager
+    /// [`WiimoteManager::update`]: crate::manager::WiimoteManager::update
+    pub fn open(
+        device_info: hid::DeviceInfo,
+        player_num: usize,
+        config: WiimoteConfig,
+    ) -> io::Result<Self> {
+        let hid_device = hid::Device::open(&device_info.path)?;
+        hid_device.caps().check_wiimote()?;
+        let canceller = hid_device.io_canceller();
+
+        Ok(Self::from_device(
+            hid_device,
+            Some(canceller),
+            device_info,
+            player_num,
+            config,
+        ))
+    }
+
+    /// Start polling an already opened device as the Wiimote described by
+    /// `device_info`, e.g. a [`hid::MockDevice`] in tests.
+    pub(crate) fn from_device<D: HidDevice + Send + 'static>(
+        hid_device: D,
+        canceller: Option<hid::IoCanceller>,
+        device_info: hid::DeviceInfo,
+        player_num: usize,
+        config: WiimoteConfig,
+    ) -> Self {
+        let (read_tx, read_rx) = unbounded();
+        let (write_tx, write_rx) = unbounded();
+        let poll_thread = WiimotePollThread::with_canceller(
+            hid_device,
+            canceller,
+            read_tx,
+            write_rx,
+            player_num,
+            device_info.wiimote_kind(),
+            config,
+        );
+
+        Self {
+            poll_thread,
+            read_rx,
+            write_tx,
+            device_info,
+        }
+    }
+
+    /// The reports read from the Wiimote.
+    pub fn read_rx(&self) -> &Receiver<hid::Report> {
+        &self.read_rx
+    }
+
+    /// Block until the next data report arrives, and decode it in the mode it
+    /// was sent in.
+    ///
+    /// Reports that don't carry data, like status reports and
+    /// acknowledgements, are skipped. Returns `Ok(None)` if no data report
+    /// arrives within `timeout`.
+    ///
+    /// This takes reports from [`Self::read_rx`], so any reports skipped or
+    /// returned here won't be seen by anything else reading from it, and vice
+    /// versa. Don't use both at the same time.
+    pub fn read_next_decoded(&self, timeout: Duration) -> hid::Result<Option<DecodedReport>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let report = match self.read_rx.recv_deadline(deadline) {
+                Ok(report) => report,
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => return Err(hid::Error::NotConnected),
+            };
+
+            if let Ok(decoded) = DecodedReport::try_from(&report) {
+                return Ok(Some(decoded));
+            }
+        }
+    }
+
+    /// Reports sent here are written to the Wiimote.
+    pub fn write_tx(&self) -> &Sender<hid::Report> {
+        &self.write_tx
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.poll_thread.is_connected()
+    }
+
+    /// See [`WiimotePollThread::report_hz`].
+    pub fn report_hz(&self) -> f32 {
+        self.poll_thread.report_hz()
+    }
+
+    /// See [`WiimotePollThread::last_status`].
+    pub fn last_status(&self) -> Option<StatusReport> {
+        self.poll_thread.last_status()
+    }
+
+    /// See [`WiimotePollThread::kind`].
+    pub fn kind(&self) -> Option<WiimoteKind> {
+        self.poll_thread.kind()
+    }
+
+    /// See [`WiimotePollThread::event_decoder`].
+    pub fn event_decoder(&self, config: &WiimoteConfig) -> EventDecoder {
+        self.poll_thread.event_decoder(config)
+    }
+
+    /// See [`WiimotePollThread::request_status`].
+    pub fn request_status(&self) {
+        self.poll_thread.request_status();
+    }
+
+    pub(crate) fn signal_stop(&self) {
+        self.poll_thread.signal_stop();
+    }
+
+    /// See [`WiimotePollThread::set_player_number`].
+    pub fn set_player_number(&self, player: PlayerNumber) {
+        self.poll_thread.set_player_number(player);
+    }
+
+    /// See [`WiimotePollThread::set_report_mode`].
+    pub fn set_report_mode(&self, mode: ReportMode) {
+        self.poll_thread.set_report_mode(mode);
+    }
+
+    /// See [`WiimotePollThread::set_report_mode_acked`].
+    pub fn set_report_mode_acked(&self, mode: ReportMode) -> Result<(), AckError> {
+        self.poll_thread.set_report_mode_acked(mode)
+    }
+
+    /// See [`WiimotePollThread::set_leds_acked`].
+    pub fn set_leds_acked(&self, leds: Led) -> Result<(), AckError> {
+        self.poll_thread.set_leds_acked(leds)
+    }
+
+    /// See [`WiimotePollThread::current_report_mode`].
+    pub fn current_report_mode(&self) -> ReportMode {
+        self.poll_thread.current_report_mode()
+    }
+
+    /// See [`WiimotePollThread::recent_reports`].
+    pub fn recent_reports(&self) -> Vec<hid::Report> {
+        self.poll_thread.recent_reports()
+    }
+
+    /// See [`WiimotePollThread::commander`].
+    pub fn commander(&self) -> WiimoteCommander {
+        self.poll_thread.commander()
+    }
+
+    /// See [`WiimotePollThread::send_raw`].
+    pub fn send_raw(&self, report: hid::Report, keep_rumble: bool) {
+        self.poll_thread.send_raw(report, keep_rumble);
+    }
+
+    /// See [`WiimotePollThread::send_sequence`].
+    pub fn send_sequence(&self, reports: Vec<hid::Report>, wait_for_acks: bool) {
+        self.poll_thread.send_sequence(reports, wait_for_acks);
+    }
+
+    /// See [`WiimotePollThread::read_all_calibration`].
+    pub fn read_all_calibration(&self) -> Result<Calibration, MemoryError> {
+        self.poll_thread.read_all_calibration()
+    }
+
+    /// See [`WiimotePollThread::calibrate_at_rest`].
+    pub fn calibrate_at_rest(
+        &self,
+        samples: usize,
+    ) -> Result<RestCalibration, RestCalibrationError> {
+        self.poll_thread.calibrate_at_rest(samples)
+    }
+
+    /// See [`WiimotePollThread::read_register`].
+    pub fn read_register(&self, address: u32, size: u16) -> Result<Vec<u8>, MemoryError> {
+        self.poll_thread.read_register(address, size)
+    }
+
+    /// See [`WiimotePollThread::identify`].
+    pub fn identify(&self) -> Result<WiimoteIdentity, MemoryError> {
+        self.poll_thread.identify()
+    }
+
+    /// See [`WiimotePollThread::write_register`].
+    pub fn write_register(&self, address: u32, data: &[u8]) -> Result<(), MemoryError> {
+        self.poll_thread.write_register(address, data)
+    }
+
+    /// See [`WiimotePollThread::pause`].
+    pub fn pause(&self) {
+        self.poll_thread.pause();
+    }
+
+    /// See [`WiimotePollThread::resume`].
+    pub fn resume(&self) {
+        self.poll_thread.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.poll_thread.is_paused()
+    }
+
+    pub fn device_info(&self) -> &hid::DeviceInfo {
+        &self.device_info
+    }
+
+    pub fn device_path(&self) -> &str {
+        &self.device_info.path
+    }
+
+    pub fn address(&self) -> Option<Address> {
+        self.device_info.address()
+    }
+
+    /// The address of the Bluetooth radio that the Wiimote is connected to,
+    /// or `None` if it can't be worked out.
+    ///
+    /// This asks every radio which devices it has connected each time, so it
+    /// shouldn't be called every frame. See [`bluetooth::radio_address_of`]
+    /// for how the radio is matched.
+    pub fn radio_address(&self) -> Option<Address> {
+        bluetooth::radio_address_of(self.address()?)
+    }
+}
+
+impl Feedback for WiimoteHandle {
+    /// See [`WiimotePollThread::set_leds`].
+    fn set_leds(&self, leds: Led) {
+        self.poll_thread.set_leds(leds);
+    }
+
+    /// See [`WiimotePollThread::set_rumble`].
+    fn set_rumble(&self, rumble: bool) {
+        self.poll_thread.set_rumble(rumble);
+    }
+}
+
+/// A single Wiimote, connected without a [`WiimoteScanner`].
+///
+/// This is the simplest way to talk to a Wiimote that is already connected to
+/// the computer: the Wiimote is opened and initialized as player 1, and its
+/// events can then be received with [`Wiimote::next_event`] and
+/// [`Wiimote::poll`].
+///
+/// [`WiimoteScanner`]: crate::scanner::WiimoteScanner
+pub struct Wiimote {
+    poll_thread: WiimotePollThread,
+    read_rx: Receiver<hid::Report>,
+    decoder: EventDecoder,
+    device_path: String,
+}
+
+impl Wiimote {
+    /// Connect to the Wiimote with the given HID device path.
+    ///
+    /// Opening the HID device is tried a few times in case the Wiimote was
+    /// only just paired, blocking while waiting to try again.
+    pub fn connect_by_path(path: &str) -> io::Result<Self> {
+        Self::connect_by_path_with_config(path, WiimoteConfig::default())
+    }
+
+    /// The same as [`Wiimote::connect_by_path`], but with a custom config.
+    pub fn connect_by_path_with_config(path: &str, config: WiimoteConfig) -> io::Result<Self> {
+        let hid_device = hid::Device::open_with_retry(
+            || hid::Device::open(path),
+            CONNECT_ATTEMPTS,
+            CONNECT_RETRY_DELAY,
+        )?;
+        hid_device.caps().check_wiimote()?;
+        let kind = hid_device.get_info().and_then(|d| d.wiimote_kind());
+        let (read_tx, read_rx) = unbounded();
+        // Nothing is written to the Wiimote after it has been initialized
+        let (_, write_rx) = unbounded();
+        let canceller = hid_device.io_canceller();
+        let poll_thread = WiimotePollThread::with_canceller(
+            hid_device,
+            Some(canceller),
+            read_tx,
+            write_rx,
+            0,
+            kind,
+            config.clone(),
+        );
+        let decoder = poll_thread.event_decoder(&config);
+
+        Ok(Self {
+            poll_thread,
+            read_rx,
+            decoder,
+            device_path: path.to_string(),
+        })
+    }
+
+    /// Connect to the first Wiimote found among the HID devices.
+    ///
+    /// Returns `None` if there are no Wiimotes connected. Note that this
+    /// doesn't search for Wiimotes over Bluetooth, so the Wiimote must have
+    /// already been paired.
+    pub fn connect_first() -> io::Result<Option<Self>> {
+        let device_enumerator = hid::DeviceEnumerator::new()?;
+        let device_info = device_enumerator.devices().find(|d| d.is_wiimote());
+
+        device_info
+            .map(|d| Self::connect_by_path(&d.path))
+            .transpose()
+    }
+
+    /// Block until the next event is received from the Wiimote.
+    ///
+    /// Once the Wiimote has disconnected, any buttons that were held down are
+    /// released with [`WiimoteEvent::ButtonUp`], and then this will always
+    /// return [`WiimoteEvent::Disconnected`].
+    pub fn next_event(&mut self) -> WiimoteEvent {
+        if let Some(event) = self.decoder.pending() {
+            return event;
+        }
+
+        for report in self.read_rx.iter() {
+            if let Some(event) = self.decoder.decode(&report) {
+                return event;
+            }
+        }
+
+        self.decoder.disconnect()
+    }
+
+    /// Get the next event from the Wiimote without blocking.
+    ///
+    /// Returns `None` if there are no events waiting to be received.
+    pub fn poll(&mut self) -> Option<WiimoteEvent> {
+        if let Some(event) = self.decoder.pending() {
+            return Some(event);
+        }
+
+        loop {
+            match self.read_rx.try_recv() {
+                Ok(report) => {
+                    if let Some(event) = self.decoder.decode(&report) {
+                        return Some(event);
+                    }
+                }
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(self.decoder.disconnect()),
+            }
+        }
+    }
+
+    /// Block until `button` is pressed, or until `timeout` has passed.
+    ///
+    /// Returns whether the button was pressed. If the button is already known
+    /// to be held down, it has to be released and pressed again. Any events
+    /// received while waiting are discarded.
+    pub fn wait_for_button(
+        &mut self,
+        button: CoreButtons,
+        timeout: Option<Duration>,
+    ) -> hid::Result<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            // Drop the events that were decoded along with an earlier report
+            while self.decoder.pending().is_some() {}
+
+            let was_pressed = self
+                .decoder
+                .buttons()
+                .unwrap_or_else(CoreButtons::empty)
+                .contains(button);
+
+            let report = match deadline {
+                Some(deadline) => match self.read_rx.recv_deadline(deadline) {
+                    Ok(report) => report,
+                    Err(RecvTimeoutError::Timeout) => return Ok(false),
+                    Err(RecvTimeoutError::Disconnected) => return Err(hid::Error::NotConnected),
+                },
+                None => self.read_rx.recv().map_err(|_| hid::Error::NotConnected)?,
+            };
+
+            // The buttons may not be the first event decoded from the report
+            let mut pressed = false;
+            let mut event = self.decoder.decode(&report);
+            while let Some(decoded) = event {
+                if let WiimoteEvent::ButtonsChanged(buttons) = decoded {
+                    pressed |= buttons.contains(button) && !was_pressed;
+                }
+                event = self.decoder.pending();
+            }
+
+            if pressed {
+                return Ok(true);
+            }
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.poll_thread.is_connected()
+    }
+
+    /// See [`WiimotePollThread::report_hz`].
+    pub fn report_hz(&self) -> f32 {
+        self.poll_thread.report_hz()
+    }
+
+    /// See [`WiimotePollThread::last_status`].
+    pub fn last_status(&self) -> Option<StatusReport> {
+        self.poll_thread.last_status()
+    }
+
+    /// See [`WiimotePollThread::kind`].
+    pub fn kind(&self) -> Option<WiimoteKind> {
+        self.poll_thread.kind()
+    }
+
+    /// See [`WiimotePollThread::set_player_number`].
+    pub fn set_player_number(&self, player: PlayerNumber) {
+        self.poll_thread.set_player_number(player);
+    }
+
+    /// See [`WiimotePollThread::set_report_mode`].
+    pub fn set_report_mode(&self, mode: ReportMode) {
+        self.poll_thread.set_report_mode(mode);
+    }
+
+    /// See [`WiimotePollThread::set_report_mode_acked`].
+    pub fn set_report_mode_acked(&self, mode: ReportMode) -> Result<(), AckError> {
+        self.poll_thread.set_report_mode_acked(mode)
+    }
+
+    /// See [`WiimotePollThread::set_leds_acked`].
+    pub fn set_leds_acked(&self, leds: Led) -> Result<(), AckError> {
+        self.poll_thread.set_leds_acked(leds)
+    }
+
+    /// See [`WiimotePollThread::current_report_mode`].
+    pub fn current_report_mode(&self) -> ReportMode {
+        self.poll_thread.current_report_mode()
+    }
+
+    /// See [`WiimotePollThread::recent_reports`].
+    pub fn recent_reports(&self) -> Vec<hid::Report> {
+        self.poll_thread.recent_reports()
+    }
+
+    /// See [`WiimotePollThread::commander`].
+    pub fn commander(&self) -> WiimoteCommander {
+        self.poll_thread.commander()
+    }
+
+    /// See [`WiimotePollThread::read_all_calibration`].
+    pub fn read_all_calibration(&self) -> Result<Calibration, MemoryError> {
+        self.poll_thread.read_all_calibration()
+    }
+
+    /// See [`WiimotePollThread::calibrate_at_rest`].
+    pub fn calibrate_at_rest(
+        &self,
+        samples: usize,
+    ) -> Result<RestCalibration, RestCalibrationError> {
+        self.poll_thread.calibrate_at_rest(samples)
+    }
+
+    /// See [`WiimotePollThread::read_register`].
+    pub fn read_register(&self, address: u32, size: u16) -> Result<Vec<u8>, MemoryError> {
+        self.poll_thread.read_register(address, size)
+    }
+
+    /// See [`WiimotePollThread::identify`].
+    pub fn identify(&self) -> Result<WiimoteIdentity, MemoryError> {
+        self.poll_thread.identify()
+    }
+
+    /// See [`WiimotePollThread::write_register`].
+    pub fn write_register(&self, address: u32, data: &[u8]) -> Result<(), MemoryError> {
+        self.poll_thread.write_register(address, data)
+    }
+
+    pub fn device_path(&self) -> &str {
+        &self.device_path
+    }
+}
+
+impl Feedback for Wiimote {
+    /// See [`WiimotePollThread::set_leds`].
+    fn set_leds(&self, leds: Led) {
+        self.poll_thread.set_leds(leds);
+    }
+
+    /// See [`WiimotePollThread::set_rumble`].
+    fn set_rumble(&self, rumble: bool) {
+        self.poll_thread.set_rumble(rumble);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hid::MockDevice;
+
+    use std::iter;
+
+    fn report(bytes: &[u8]) -> hid::Report {
+        bytes.iter().copied().collect()
+    }
+
+    // Start a poll thread on a mock device that times out when it has nothing
+    // to read, returning the thread, the device and the reports it reads.
+    // Rumbling on connect is turned off so that it doesn't show up in writes
+    fn mock_poll_thread(
+        config: WiimoteConfig,
+    ) -> (WiimotePollThread, MockDevice, Receiver<hid::Report>) {
+        let device = MockDevice::default().timing_out();
+        let (read_tx, read_rx) = unbounded();
+        let (_, write_rx) = unbounded();
+        let config = WiimoteConfig {
+            rumble_on_connect: false,
+            ..config
+        };
+        let poll_thread =
+            WiimotePollThread::new(device.clone(), read_tx, write_rx, 0, None, config);
+        (poll_thread, device, read_rx)
+    }
+
+    #[test]
+    fn identity_display() {
+        let identity = WiimoteIdentity {
+            kind: Some(WiimoteKind::MotionPlusInside),
+            motion_plus_id: Some([0x00, 0x00, 0xa6, 0x20, 0x00, 0x05]),
+            extension_id: Some([0x00, 0x00, 0xa4, 0x20, 0x00, 0x00]),
+        };
+        assert_eq!(
+            identity.to_string(),
+            "RVL-CNT-01-TR, MotionPlus 00 00 a6 20 00 05, extension 00 00 a4 20 00 00"
+        );
+
+        let identity = WiimoteIdentity {
+            kind: None,
+            motion_plus_id: None,
+            extension_id: None,
+        };
+        assert_eq!(
+            identity.to_string(),
+            "Unknown model, no MotionPlus, no extension"
+        );
+    }
+
+    #[test]
+    fn stall_ignores_the_report_id() {
+        let recovery = StallRecovery {
+            max_stalled_reads: 3,
+            count_timeouts: false,
+        };
+
+        let zeroed = report(&[INPUT_REPORT, 0x30, 0, 0]);
+        assert!(recovery.is_stalled(&zeroed));
+
+        let pressed = report(&[INPUT_REPORT, 0x30, 0, 0x08]);
+        assert!(!recovery.is_stalled(&pressed));
+    }
+
+    #[test]
+    fn stall_counts_timeouts_if_configured() {
+        let mut recovery = StallRecovery {
+            max_stalled_reads: 3,
+            count_timeouts: false,
+        };
+        assert!(!recovery.is_stalled(&hid::Report::new()));
+
+        recovery.count_timeouts = true;
+        assert!(recovery.is_stalled(&hid::Report::new()));
+    }
+
+    #[test]
+    fn report_mode_reads_back_after_being_set() {
+        let (mut poll_thread, _, _) = mock_poll_thread(WiimoteConfig::default());
+
+        poll_thread.set_report_mode(ReportMode::CoreAccelIr12);
+        assert_eq!(poll_thread.current_report_mode(), ReportMode::CoreAccelIr12);
+
+        // The thread going around its loop mustn't overwrite it
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(poll_thread.current_report_mode(), ReportMode::CoreAccelIr12);
+
+        poll_thread.stop_thread();
+    }
+
+    // Wait for the poll thread to write `report` to `device`, after the first
+    // `after` writes
+    fn wait_for_write(device: &MockDevice, report: &hid::Report, after: usize) {
+        let start = Instant::now();
+        while !device.writes()[after..].contains(report) {
+            assert!(start.elapsed() < Duration::from_secs(1), "not written");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn reports_keep_their_mode_across_a_mode_change() {
+        let (poll_thread, device, read_rx) = mock_poll_thread(WiimoteConfig::default());
+
+        device.push_read(report(&[INPUT_REPORT, 0x30, 0, 0x08]));
+        poll_thread.set_report_mode(ReportMode::CoreAccel);
+        wait_for_write(
+            &device,
+            &report::report_mode(false, ReportMode::CoreAccel),
+            0,
+        );
+        device.push_read(report(&[INPUT_REPORT, 0x31, 0, 0, 0x80, 0x80, 0x9a]));
+
+        let decoded: Vec<_> = (0..2)
+            .map(|_| read_rx.recv_timeout(Duration::from_secs(1)).unwrap())
+            .map(|report| DecodedReport::try_from(&report).unwrap())
+            .collect();
+        assert_eq!(decoded[0].mode, ReportMode::CoreButtons);
+        assert_eq!(decoded[0].buttons, Some(CoreButtons::A));
+        assert_eq!(decoded[0].accel, None);
+        assert_eq!(decoded[1].mode, ReportMode::CoreAccel);
+        assert_eq!(
+            decoded[1].accel,
+            Some(AccelData {
+                x: 0x200,
+                y: 0x200,
+                z: 0x268
+            })
+        );
+    }
+
+    #[test]
+    fn report_mode_is_restored_after_a_status_report() {
+        let (poll_thread, device, read_rx) = mock_poll_thread(WiimoteConfig::default());
+
+        let mode_report = report::report_mode(false, ReportMode::CoreAccel);
+        poll_thread.set_report_mode(ReportMode::CoreAccel);
+        wait_for_write(&device, &mode_report, 0);
+        let writes_before = device.writes().len();
+
+        device.push_read(report(&[INPUT_REPORT, 0x20, 0, 0, 0, 0, 0, 0xc8]));
+        read_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(poll_thread.last_status().unwrap().battery_percent(), 100);
+        wait_for_write(&device, &mode_report, writes_before);
+    }
+
+    #[test]
+    fn status_is_requested_periodically() {
+        let (mut poll_thread, device, _) = mock_poll_thread(WiimoteConfig {
+            status_interval: Some(Duration::from_millis(20)),
+            ..WiimoteConfig::default()
+        });
+
+        thread::sleep(Duration::from_millis(200));
+        poll_thread.stop_thread();
+        let requests = device
+            .writes()
+            .iter()
+            .filter(|&write| *write == report::request_status())
+            .count();
+        assert!(requests >= 3, "only {} status requests", requests);
+    }
+
+    #[test]
+    fn acked_writes_resolve_from_the_ack() {
+        let (poll_thread, device, _) = mock_poll_thread(WiimoteConfig::default());
+        let poll_thread = Arc::new(poll_thread);
+        let mut mode_report = report::report_mode(false, ReportMode::CoreAccel);
+        report::request_ack(&mut mode_report);
+
+        // Acknowledge the mode report once it has been written, with `error`
+        let set_mode_acked = |error: u8| {
+            let writes_before = device.writes().len();
+            let waiter = {
+                let poll_thread = Arc::clone(&poll_thread);
+                thread::spawn(move || poll_thread.set_report_mode_acked(ReportMode::CoreAccel))
+            };
+            wait_for_write(&device, &mode_report, writes_before);
+            device.push_read(report(&[INPUT_REPORT, 0x22, 0, 0, 0x12, error]));
+            waiter.join().unwrap()
+        };
+
+        assert_eq!(
+            set_mode_acked(0x03),
+            Err(AckError::Wiimote {
+                report_id: 0x12,
+                error: 0x03,
+            })
+        );
+        assert_eq!(poll_thread.current_report_mode(), ReportMode::CoreButtons);
+
+        assert_eq!(set_mode_acked(0x00), Ok(()));
+        assert_eq!(poll_thread.current_report_mode(), ReportMode::CoreAccel);
+    }
+
+    #[test]
+    fn report_mode_set_through_a_commander_reads_back() {
+        let (mut poll_thread, _, _) = mock_poll_thread(WiimoteConfig::default());
+
+        let commander = poll_thread.commander();
+        commander.set_report_mode(ReportMode::CoreAccelIr12);
+        assert_eq!(poll_thread.current_report_mode(), ReportMode::CoreAccelIr12);
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(poll_thread.current_report_mode(), ReportMode::CoreAccelIr12);
+
+        poll_thread.stop_thread();
+    }
+
+    #[test]
+    fn wait_for_button_sees_a_press_behind_another_event() {
+        let config = WiimoteConfig::default();
+        let (poll_thread, device, read_rx) = mock_poll_thread(config.clone());
+        let extension_inits = Arc::new(AtomicUsize::new(0));
+        let decoder = EventDecoder::for_poll_thread(&config, None, Arc::clone(&extension_inits));
+        let mut wiimote = Wiimote {
+            poll_thread,
+            read_rx,
+            decoder,
+            device_path: String::new(),
+        };
+
+        // The press is decoded after `ExtensionReinitialized`, and along with
+        // a status event that nobody asked for
+        extension_inits.fetch_add(1, Ordering::SeqCst);
+        device.push_read(report(&[INPUT_REPORT, 0x20, 0, 0x08, 0, 0, 0, 0xc8]));
+        let pressed = wiimote
+            .wait_for_button(CoreButtons::A, Some(Duration::from_secs(1)))
+            .unwrap();
+
+        assert!(pressed);
+        assert_eq!(wiimote.decoder.pending(), None);
+    }
+
+    #[test]
+    fn blocking_calls_are_answered_while_paused() {
+        let (mut poll_thread, device, _) = mock_poll_thread(WiimoteConfig::default());
+
+        poll_thread.pause();
+        // Give the thread time to stop reading
+        thread::sleep(Duration::from_millis(50));
+
+        // Acknowledge the LED report without an error
+        device.push_read(report(&[INPUT_REPORT, 0x22, 0, 0, 0x11, 0]));
+        assert!(poll_thread.set_leds_acked(Led::LED_2).is_ok());
+        assert!(poll_thread.is_paused());
+
+        poll_thread.stop_thread();
+    }
+
+    #[test]
+    fn extension_reinitialized_after_the_thread_initializes_it() {
+        let config = WiimoteConfig::default();
+        let (poll_thread, device, read_rx) = mock_poll_thread(config.clone());
+        let mut decoder = poll_thread.event_decoder(&config);
+        let mut plain_decoder = EventDecoder::new(&config);
+
+        // A status report with an extension connected, then a report that can
+        // only be read after the extension has been initialized
+        device.push_read(report(&[INPUT_REPORT, 0x20, 0, 0, 0x02, 0, 0, 0xc0]));
+        device.push_read(report(&[INPUT_REPORT, 0x30, 0, 0]));
+        let reports: Vec<_> = (0..2)
+            .map(|_| read_rx.recv_timeout(Duration::from_secs(1)).unwrap())
+            .collect();
+
+        let count_reinits = |decoder: &mut EventDecoder| {
+            let mut events = Vec::new();
+            for report in &reports {
+                events.extend(decoder.decode(report));
+                events.extend(iter::from_fn(|| decoder.pending()));
+            }
+            events
+                .into_iter()
+                .filter(|&event| event == WiimoteEvent::ExtensionReinitialized)
+                .count()
+        };
+        assert_eq!(count_reinits(&mut decoder), 1);
+        assert_eq!(count_reinits(&mut plain_decoder), 0);
+    }
+
+    // A device that panics as soon as it is read from
+    struct PanickingDevice;
+
+    impl HidDevice for PanickingDevice {
+        fn read(&mut self) -> hid::Result<hid::Report> {
+            panic!("injected panic");
+        }
+
+        fn write(&mut self, buf: &[u8]) -> hid::Result<usize> {
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn panic_in_the_poll_thread_disconnects_cleanly() {
+        let (read_tx, read_rx) = unbounded();
+        let (_write_tx, write_rx) = unbounded();
+        let config = WiimoteConfig {
+            rumble_on_connect: false,
+            ..WiimoteConfig::default()
+        };
+        let mut poll_thread =
+            WiimotePollThread::new(PanickingDevice, read_tx, write_rx, 0, None, config);
+
+        // The read channel is closed once the thread has stopped
+        assert_eq!(
+            read_rx.recv_timeout(Duration::from_secs(1)),
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected)
+        );
+        assert!(!poll_thread.is_connected());
+        poll_thread.stop_thread();
+    }
+
+    // A data read report carrying `data`, which is at most 16 bytes long
+    fn data_read(address: u16, data: &[u8]) -> hid::Report {
+        let [address_high, address_low] = address.to_be_bytes();
+        let size_error = ((data.len() - 1) as u8) << 4;
+        let mut read = report(&[
+            INPUT_REPORT,
+            0x21,
+            0,
+            0,
+            size_error,
+            address_high,
+            address_low,
+        ]);
+        read.extend(data.iter().copied().chain(iter::repeat(0)).take(16));
+        read
+    }
+
+    #[test]
+    fn calibration_status_goes_through_the_status_handling() {
+        let (poll_thread, device, _) = mock_poll_thread(WiimoteConfig::default());
+
+        // Only the calibration read can read these while paused
+        poll_thread.pause();
+        thread::sleep(Duration::from_millis(50));
+        device.push_read(report(&[INPUT_REPORT, 0x20, 0, 0, 0x02, 0, 0, 0xc0]));
+        device.push_read(data_read(0x0016, &[0x80; 10]));
+        device.push_read(data_read(0x0020, &[0; 16]));
+
+        let calibration = poll_thread.read_all_calibration().unwrap();
+        assert!(calibration.extension.is_some());
+        assert!(poll_thread.last_status().unwrap().extension_connected);
+        let init_write = report::register_write(EXTENSION_INIT_WRITES[0].0, &[0x55]).unwrap();
+        assert!(device.writes().contains(&init_write));
     }
 }